@@ -0,0 +1,370 @@
+use crate::{
+    evaluation::metrics::Scores,
+    models::{DataEntry, Match, Outcome},
+    Markdown, NetworkStats, Predictions, Predictor, Testing,
+};
+use std::collections::{BTreeMap, HashMap};
+
+// outcome counts (home win, draw, away win) and the count of each scoreline
+type Counts = ([f64; 3], BTreeMap<[u8; 2], u32>);
+
+/// Always predicts a home win 1 : 0.
+#[derive(Clone, Debug, Default)]
+pub struct AlwaysHome;
+
+/**
+    Predicts the home win, draw and away win frequencies of the league of a match in the training
+    matches, all leagues for a league without training matches. The predicted score is the most
+    common scoreline of the most frequent outcome in the league.
+**/
+#[derive(Clone, Debug, Default)]
+pub struct LeagueFrequencies {
+    // counts per league and of all leagues
+    leagues: HashMap<String, Counts>,
+    all: Counts,
+}
+
+/**
+    Predicts the most common scoreline of the training matches for every match.
+    The outcome probabilities are the frequencies of all training matches.
+**/
+#[derive(Clone, Debug, Default)]
+pub struct CommonScoreline {
+    scoreline: [u8; 2],
+    frequencies: [f64; 3],
+}
+
+/**
+    Repeats the result of the last training match between both clubs before the match,
+    the venue doesn't matter. Falls back to ```CommonScoreline``` if both clubs haven't met.
+**/
+#[derive(Clone, Debug, Default)]
+pub struct HeadToHead {
+    matches: Vec<Match>,
+    fallback: CommonScoreline,
+}
+
+/**
+    The exact result and winner rates (in percent) and the scores of a model and of the baselines
+    on the same test set. The model is the first row.
+**/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BaselineReport(pub Vec<(String, [f64; 2], Scores)>);
+
+/// Returns all baselines, not fitted.
+pub fn baselines() -> Vec<Box<dyn Predictor>> {
+    vec![
+        Box::new(AlwaysHome),
+        Box::new(LeagueFrequencies::default()),
+        Box::new(CommonScoreline::default()),
+        Box::new(HeadToHead::default()),
+    ]
+}
+
+/**
+    Fits all baselines to the training matches and tests them next to the (fitted) model.
+
+    **Example**:
+
+    ```text
+    let report = evaluate(&guru, &model, &training_set, &training_matches, &test_set, &test_matches);
+    println!("{}", report.to_table());
+    if !report.unbeaten().is_empty() { .. }
+    ```
+**/
+pub fn evaluate<T: Testing, P: Predictor + ?Sized>(
+    tester: &T,
+    model: &P,
+    training_set: &[DataEntry],
+    training_matches: &[Match],
+    test_set: &[DataEntry],
+    test_matches: &[Match],
+) -> BaselineReport {
+    let mut report = vec![row(model.name(), tester.test(model, test_set, test_matches))];
+    for mut baseline in baselines() {
+        baseline.fit(training_set, training_matches);
+        report.push(row(baseline.name(), tester.test(baseline.as_ref(), test_set, test_matches)));
+    }
+    BaselineReport(report)
+}
+
+// the exact result and the winner rate in percent, and the scores of the predictions
+fn row(name: String, (stats, predictions): ([NetworkStats; 2], Predictions)) -> (String, [f64; 2], Scores) {
+    (name, [stats[0].correct(), stats[1].correct()], Scores::from(&predictions))
+}
+
+// counts the outcomes and scorelines of the matches with result
+fn count<'a>(matches: impl Iterator<Item = &'a Match>) -> Counts {
+    let mut outcomes = [0f64; 3];
+    let mut scorelines = BTreeMap::new();
+    for result in matches.filter_map(|m| m.result) {
+        outcomes[Outcome::from(result).index()] += 1f64;
+        *scorelines.entry(result).or_default() += 1;
+    }
+    (outcomes, scorelines)
+}
+
+// the outcome frequencies, even if there are no outcomes
+fn frequencies(outcomes: [f64; 3]) -> [f64; 3] {
+    let total: f64 = outcomes.iter().sum();
+    if total == 0f64 {
+        return [1f64 / 3f64; 3];
+    }
+    [outcomes[0] / total, outcomes[1] / total, outcomes[2] / total]
+}
+
+// the most common scoreline with one of the given outcomes, 1 : 0 if there is none
+fn most_common(scorelines: &BTreeMap<[u8; 2], u32>, outcome: Option<Outcome>) -> [u8; 2] {
+    scorelines
+        .iter()
+        .filter(|(s, _)| outcome.map(|o| Outcome::from(**s) == o).unwrap_or(true))
+        .fold(None, |best: Option<(&[u8; 2], &u32)>, (s, n)| match best {
+            Some((_, b)) if b >= n => best,
+            _ => Some((s, n)),
+        })
+        .map(|(s, _)| *s)
+        .unwrap_or([1, 0])
+}
+
+// the outcome with the highest probability, the first one for ties
+fn most_frequent(probabilities: [f64; 3]) -> Outcome {
+    [Outcome::Home, Outcome::Draw, Outcome::Away]
+        .iter()
+        .fold(Outcome::Home, |best, o| {
+            if probabilities[o.index()] > probabilities[best.index()] {
+                *o
+            } else {
+                best
+            }
+        })
+}
+
+// the predicted scores of a scoreline
+fn goals(scoreline: [u8; 2]) -> [f64; 2] {
+    [f64::from(scoreline[0]), f64::from(scoreline[1])]
+}
+
+impl LeagueFrequencies {
+    // the outcome counts and scorelines of the league, all leagues if the league is unknown
+    fn counts(&self, league: &str) -> &Counts {
+        self.leagues.get(league).unwrap_or(&self.all)
+    }
+}
+
+impl HeadToHead {
+    // the last result between both clubs before the match, from the view of the home team
+    fn last(&self, m: &Match) -> Option<[u8; 2]> {
+        self.matches
+            .iter()
+            .filter(|p| p.date < m.date)
+            .filter_map(|p| match p.result {
+                Some(r) if p.home == m.home && p.away == m.away => Some((p.date, r)),
+                Some(r) if p.home == m.away && p.away == m.home => Some((p.date, [r[1], r[0]])),
+                _ => None,
+            })
+            .max_by_key(|(date, _)| *date)
+            .map(|(_, r)| r)
+    }
+}
+
+impl BaselineReport {
+    /// Returns the names of the baselines whose winner rate the model doesn't beat.
+    pub fn unbeaten(&self) -> Vec<String> {
+        match self.0.split_first() {
+            Some(((_, model, _), baselines)) => baselines
+                .iter()
+                .filter(|(_, rates, _)| model[1] <= rates[1])
+                .map(|(name, _, _)| name.clone())
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+impl Predictor for AlwaysHome {
+    fn fit(&mut self, _training_set: &[DataEntry], _matches: &[Match]) {}
+
+    fn predict_scores(&self, _entry: &DataEntry, _m: &Match) -> [f64; 2] {
+        [1f64, 0f64]
+    }
+
+    fn predict_outcome(&self, _entry: &DataEntry, _m: &Match) -> [f64; 3] {
+        [1f64, 0f64, 0f64]
+    }
+
+    fn name(&self) -> String {
+        String::from("Always Home Win")
+    }
+}
+
+impl Predictor for LeagueFrequencies {
+    fn fit(&mut self, _training_set: &[DataEntry], matches: &[Match]) {
+        self.all = count(matches.iter());
+        self.leagues.clear();
+        for m in matches {
+            if !self.leagues.contains_key(&m.league) {
+                let counts = count(matches.iter().filter(|o| o.league == m.league));
+                self.leagues.insert(m.league.clone(), counts);
+            }
+        }
+    }
+
+    fn predict_scores(&self, _entry: &DataEntry, m: &Match) -> [f64; 2] {
+        let (outcomes, scorelines) = self.counts(&m.league);
+        goals(most_common(scorelines, Some(most_frequent(*outcomes))))
+    }
+
+    fn predict_outcome(&self, _entry: &DataEntry, m: &Match) -> [f64; 3] {
+        frequencies(self.counts(&m.league).0)
+    }
+
+    fn name(&self) -> String {
+        String::from("League Frequencies")
+    }
+}
+
+impl Predictor for CommonScoreline {
+    fn fit(&mut self, _training_set: &[DataEntry], matches: &[Match]) {
+        let (outcomes, scorelines) = count(matches.iter());
+        self.scoreline = most_common(&scorelines, None);
+        self.frequencies = frequencies(outcomes);
+    }
+
+    fn predict_scores(&self, _entry: &DataEntry, _m: &Match) -> [f64; 2] {
+        goals(self.scoreline)
+    }
+
+    fn predict_outcome(&self, _entry: &DataEntry, _m: &Match) -> [f64; 3] {
+        self.frequencies
+    }
+
+    fn name(&self) -> String {
+        String::from("Most Common Scoreline")
+    }
+}
+
+impl Predictor for HeadToHead {
+    fn fit(&mut self, training_set: &[DataEntry], matches: &[Match]) {
+        self.matches = matches.to_vec();
+        self.fallback.fit(training_set, matches);
+    }
+
+    fn predict_scores(&self, entry: &DataEntry, m: &Match) -> [f64; 2] {
+        match self.last(m) {
+            Some(result) => goals(result),
+            None => self.fallback.predict_scores(entry, m),
+        }
+    }
+
+    fn predict_outcome(&self, entry: &DataEntry, m: &Match) -> [f64; 3] {
+        match self.last(m) {
+            Some(result) => {
+                let mut probabilities = [0f64; 3];
+                probabilities[Outcome::from(result).index()] = 1f64;
+                probabilities
+            }
+            None => self.fallback.predict_outcome(entry, m),
+        }
+    }
+
+    fn name(&self) -> String {
+        String::from("Last Head-to-Head")
+    }
+}
+
+/// Formats ```BaselineReport``` into a Markdown Table, including header.
+impl Markdown for BaselineReport {
+    fn to_table(&self) -> String {
+        let unbeaten = self.unbeaten();
+        let mut s = String::new();
+        s.push_str("|Model|Result|Winner|Brier|RPS|Log-Loss|Beaten|\n");
+        s.push_str("|:-|-:|-:|-:|-:|-:|:-:|\n");
+        for (i, (name, rates, scores)) in self.0.iter().enumerate() {
+            let beaten = if i == 0 {
+                ""
+            } else if unbeaten.contains(name) {
+                "no"
+            } else {
+                "yes"
+            };
+            s.push_str(&format!(
+                "|{}|{:.1}%|{:.1}%|{:.4}|{:.4}|{:.4}|{}|\n",
+                name, rates[0], rates[1], scores.brier, scores.rps, scores.log_loss, beaten
+            ));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{date, entry, fixture, played},
+        Guru,
+    };
+
+    fn matches() -> Vec<Match> {
+        vec![
+            played(0, "A", "B", [2, 1]),
+            played(1, "B", "C", [1, 1]),
+            played(2, "C", "A", [2, 1]),
+            played(3, "A", "C", [1, 0]),
+            played(4, "C", "B", [2, 1]),
+            Match::new(date(5), String::from("Cup"), String::from("D"), String::from("A"), Some([0, 0])),
+        ]
+    }
+
+    #[test]
+    fn league_frequencies_fall_back_to_all_leagues() {
+        let mut model = LeagueFrequencies::default();
+        model.fit(&[], &matches());
+        let league = model.predict_outcome(&entry(), &fixture(6, "A", "B"));
+        assert_eq!(league, [0.8, 0.2, 0f64]);
+        assert_eq!(model.predict_scores(&entry(), &fixture(6, "A", "B")), [2f64, 1f64]);
+        let cup = Match::new(date(6), String::from("Cup"), String::from("A"), String::from("D"), None);
+        assert_eq!(model.predict_outcome(&entry(), &cup), [0f64, 1f64, 0f64]);
+        let unknown = Match::new(date(6), String::from("Other"), String::from("A"), String::from("D"), None);
+        assert_eq!(model.predict_outcome(&entry(), &unknown), [4f64 / 6f64, 2f64 / 6f64, 0f64]);
+    }
+
+    #[test]
+    fn common_scoreline_predicts_the_most_frequent_result() {
+        let mut model = CommonScoreline::default();
+        model.fit(&[], &matches());
+        assert_eq!(model.predict_scores(&entry(), &fixture(6, "A", "B")), [2f64, 1f64]);
+        let mut empty = CommonScoreline::default();
+        empty.fit(&[], &[]);
+        assert_eq!(empty.predict_scores(&entry(), &fixture(6, "A", "B")), [1f64, 0f64]);
+        assert_eq!(empty.predict_outcome(&entry(), &fixture(6, "A", "B")), [1f64 / 3f64; 3]);
+    }
+
+    #[test]
+    fn head_to_head_repeats_the_last_result_from_the_home_view() {
+        let mut model = HeadToHead::default();
+        model.fit(&[], &matches());
+        // C beat A 2 : 1 on day 2 and lost 0 : 1 on day 3
+        assert_eq!(model.predict_scores(&entry(), &fixture(6, "C", "A")), [0f64, 1f64]);
+        assert_eq!(model.predict_outcome(&entry(), &fixture(6, "C", "A")), [0f64, 0f64, 1f64]);
+        // only matches before the match count
+        assert_eq!(model.predict_scores(&entry(), &fixture(3, "C", "A")), [2f64, 1f64]);
+        // B and D never met
+        assert_eq!(model.predict_scores(&entry(), &fixture(6, "B", "D")), [2f64, 1f64]);
+    }
+
+    #[test]
+    fn the_model_is_compared_with_every_baseline() {
+        let matches = matches();
+        let (training, test) = matches.split_at(4);
+        let entries = vec![entry(); matches.len()];
+        let guru = Guru::new(&matches);
+        let report = evaluate(&guru, &AlwaysHome, &entries[..4], training, &entries[4..], test);
+        assert_eq!(report.0.len(), 1 + baselines().len());
+        assert_eq!(report.0[0].0, "Always Home Win");
+        assert_eq!(report.0[0].1, [0f64, 50f64]);
+        // the head-to-head baseline predicts a draw for C : B, the others a home win
+        assert_eq!(report.unbeaten(), vec!["Always Home Win", "League Frequencies", "Most Common Scoreline"]);
+        assert_eq!(report.0[2].1, [50f64, 50f64]);
+        assert_eq!(report.to_table().lines().count(), 2 + 5);
+    }
+}
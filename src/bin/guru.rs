@@ -3,14 +3,58 @@
 extern crate clap;
 extern crate guru;
 
-use clap::App;
+use clap::{App, ArgMatches};
 use guru::{
-    generators::DefaultInputGenerator,
+    baselines,
+    classifiers::{
+        gbdt::{Gbdt, GbdtSettings},
+        knn::Knn,
+        logistic::{LogisticRegression, LogisticSettings},
+    },
+    ensemble::{
+        stacking::{BaseModel, Stacking},
+        Combination, Ensemble, Weighting,
+    },
+    evaluation::{
+        backtest::Backtest,
+        cross_validation::{CrossValidation, Folding},
+        ledger::Ledger,
+        calibration::{forecasts, Calibrated, Method, Reliability},
+        confusion::{Confusion, ScorelineConfusion},
+        metrics::Scores,
+        segments::Segments,
+        significance::{Comparison, Intervals},
+    },
+    generators::{DefaultInputGenerator, EloInputGenerator, Generator},
     models::{Clubs, DataEntry, Match, Sets},
-    neural::nn::NN,
-    utils::{load_matches, load_network, filter_results, filter_no_results, rand_k_split, save_network},
-    Guru, Markdown, Stats, Testing, Training,
+    neural::{
+        distill::{distill_set, Fidelity},
+        ensemble::{Aggregation, NetworkEnsemble},
+        nn::{NormKind, PredictorSettings, NN},
+        prune::{
+            compact, connections, fine_tune, prune_connections, prune_units, Criterion,
+            PruneReport, PruneStep,
+        },
+        uncertainty::UncertaintyMode,
+    },
+    goals::{
+        bivariate::BivariatePoisson,
+        poisson::{Poisson, PoissonSettings},
+    },
+    ratings::{
+        bradley_terry::BradleyTerry,
+        elo::{Elo, EloSettings},
+        glicko::{Glicko, GlickoSettings},
+        power::{PowerRanking, RankBy},
+    },
+    tune::{search, SearchSpace, Strategy},
+    utils::{
+        append_ledger, filter_no_results, filter_results, load_calibrator, load_ensemble, load_experiment, load_ledger, load_matches, load_network,
+        rand_k_split, save_calibrator, save_ensemble, save_experiment, save_network,
+    },
+    Guru, Markdown, Predictor, Stats, Testing, Training,
 };
+use chrono::{NaiveDate, Utc};
 use std::{collections::HashMap, str::FromStr};
 
 fn stats(clubs: &Clubs) -> HashMap<String, Stats> {
@@ -21,10 +65,432 @@ fn stats(clubs: &Clubs) -> HashMap<String, Stats> {
     league_stats
 }
 
+// parses a comma separated list of values
+fn list<T: FromStr>(opts: &ArgMatches, name: &str, default: &str) -> Vec<T>
+where
+    T::Err: std::fmt::Debug,
+{
+    opts.value_of(name)
+        .unwrap_or(default)
+        .split(',')
+        .map(|v| v.trim().parse().unwrap())
+        .collect()
+}
+
+// parses a single value
+fn value<T: FromStr>(opts: &ArgMatches, name: &str, default: T) -> T
+where
+    T::Err: std::fmt::Debug,
+{
+    if opts.is_present(name) {
+        opts.value_of(name).unwrap().parse().unwrap()
+    } else {
+        default
+    }
+}
+
+fn elo(opts: &ArgMatches) -> Elo {
+    let defaults = EloSettings::default();
+    Elo::new(EloSettings {
+        k: value(opts, "elo-k", defaults.k),
+        home_advantage: value(opts, "elo-home-advantage", defaults.home_advantage),
+        carry_over: value(opts, "elo-carry-over", defaults.carry_over),
+        ..defaults
+    })
+}
+
+fn glicko(opts: &ArgMatches) -> Glicko {
+    let defaults = GlickoSettings::default();
+    Glicko::new(GlickoSettings {
+        period: value(opts, "glicko-period", defaults.period),
+        tau: value(opts, "glicko-tau", defaults.tau),
+        ..defaults
+    })
+}
+
+fn logistic(opts: &ArgMatches) -> LogisticRegression {
+    let defaults = LogisticSettings::default();
+    LogisticRegression::new(LogisticSettings {
+        l2: value(opts, "l2", defaults.l2),
+        ..defaults
+    })
+}
+
+fn gbdt(opts: &ArgMatches, scale: f64) -> Gbdt {
+    let defaults = GbdtSettings::default();
+    Gbdt::new(GbdtSettings {
+        trees: value(opts, "gbdt-trees", defaults.trees),
+        depth: value(opts, "gbdt-depth", defaults.depth),
+        shrinkage: value(opts, "gbdt-shrinkage", defaults.shrinkage),
+        subsample: value(opts, "gbdt-subsample", defaults.subsample),
+        scale,
+        ..defaults
+    })
+}
+
+fn poisson_settings(opts: &ArgMatches, dixon_coles: bool) -> PoissonSettings {
+    let defaults = PoissonSettings::default();
+    PoissonSettings {
+        dixon_coles,
+        decay: value(opts, "decay", defaults.decay),
+        ..defaults
+    }
+}
+
+fn ensemble(opts: &ArgMatches, max: u8) -> Ensemble {
+    let members = list::<String>(opts, "ensemble-members", "elo,dixon-coles,bradley-terry")
+        .iter()
+        .map(|member| match member.as_str() {
+            // an ensemble member would create ensembles without end, networks aren't models of model()
+            "ensemble" | "network" | "stacking" => panic!("{} can't be an ensemble member", member),
+            _ => model(member, opts, max).unwrap_or_else(|| panic!("unknown ensemble member {}", member)),
+        })
+        .collect();
+    let mut ensemble = Ensemble::new(members);
+    ensemble.combination = match opts.value_of("combine") {
+        Some("majority") => Combination::MajorityVote,
+        Some("weighted") => Combination::WeightedAverage,
+        _ => Combination::Average,
+    };
+    ensemble.weighting = match opts.value_of("ensemble-weights") {
+        Some("performance") => Weighting::Performance,
+        Some("error") => Weighting::Error,
+        _ if ensemble.combination == Combination::WeightedAverage => Weighting::Error,
+        _ => Weighting::Equal,
+    };
+    ensemble
+}
+
+// creates a model that isn't a network, None for the network
+fn model(name: &str, opts: &ArgMatches, max: u8) -> Option<Box<dyn Predictor>> {
+    match name {
+        "elo" => Some(Box::new(elo(opts))),
+        "glicko" => Some(Box::new(glicko(opts))),
+        "bradley-terry" => Some(Box::new(BradleyTerry::default())),
+        "poisson" => Some(Box::new(Poisson::new(poisson_settings(opts, false)))),
+        "dixon-coles" => Some(Box::new(Poisson::new(poisson_settings(opts, true)))),
+        "bivariate-poisson" => Some(Box::new(BivariatePoisson::new(poisson_settings(opts, false)))),
+        "logistic" => Some(Box::new(logistic(opts))),
+        "knn" => Some(Box::new(Knn::new(value(opts, "knn-k", 10)))),
+        "gbdt" => Some(Box::new(gbdt(opts, f64::from(max)))),
+        "ensemble" => Some(Box::new(ensemble(opts, max))),
+        _ => None,
+    }
+}
+
+// creates a base model of a stacking that isn't a network
+fn base_model<'a>(name: String, opts: &'a ArgMatches, max: u8) -> BaseModel<'a> {
+    Box::new(move || model(&name, opts, max).unwrap_or_else(|| panic!("{} can't be a base model", name)))
+}
+
+// tests a model on the training and the test set
+fn report<P: Predictor + ?Sized>(
+    guru: &Guru,
+    model: &P,
+    sets: &Sets,
+    training_set: &[DataEntry],
+    test_set: &[DataEntry],
+    opts: &ArgMatches,
+    max: u8,
+) {
+    let (test_results, predictions) = guru.test(model, training_set, &sets.data[1]);
+    println!("Testing on (seen) Training Data");
+    println!("{}", predictions);
+    println!("Result {}\n", test_results[0].to_string());
+    println!("Winner {}\n", test_results[1].to_string());
+    println!("{}", Scores::from(&predictions).to_table());
+    println!("--------------------------\n\n");
+    let (test_results, test_predictions) = guru.test(model, test_set, &sets.data[0]);
+    println!("Testing on (unseen) Test Data");
+    println!("{}", test_predictions);
+    println!("Result {}\n", test_results[0].to_string());
+    println!("Winner {}\n", test_results[1].to_string());
+    println!("{}", Scores::from(&test_predictions).to_table());
+    println!("Bootstrap intervals (1000 samples)\n");
+    println!("{}", Intervals::bootstrap(&test_predictions, 1000, 0.95).to_table());
+    let reliability = Reliability::new(&forecasts(&test_predictions), None, 10);
+    println!("Reliability of the outcome probabilities (ECE {:.4})\n", reliability.ece());
+    println!("{}", reliability.to_table());
+    println!("Confusion of the predicted outcomes\n");
+    println!("{}", Confusion::from(&test_predictions).to_table());
+    if opts.is_present("breakdown") {
+        println!("Confusion of the predicted results\n");
+        println!("{}", ScorelineConfusion::from(&test_predictions).to_table());
+        println!("Breakdown by segment\n");
+        println!("{}", Segments::new(&test_predictions, &sets.data[0]).to_table());
+    }
+    println!("--------------------------\n\n");
+    let baselines = baselines::evaluate(guru, model, training_set, &sets.data[1], test_set, &sets.data[0]);
+    println!("Baselines on (unseen) Test Data\n");
+    println!("{}", baselines.to_table());
+    let unbeaten = baselines.unbeaten();
+    if !unbeaten.is_empty() {
+        println!("WARNING: {} does not beat the baselines: {}\n", model.name(), unbeaten.join(", "));
+    }
+    if let Some(mut other) = opts.value_of("compare").and_then(|name| self::model(name, opts, max)) {
+        guru.train(other.as_mut(), training_set, &sets.data[1]);
+        let (_, other_predictions) = guru.test(other.as_ref(), test_set, &sets.data[0]);
+        let comparison = Comparison::new((&model.name(), &test_predictions), (&other.name(), &other_predictions));
+        println!("Paired tests on (unseen) Test Data\n");
+        println!("{}", comparison.to_table());
+    }
+}
+
+// the calibration method given by --calibrate
+fn calibration(opts: &ArgMatches) -> Option<Method> {
+    match opts.value_of("calibrate") {
+        Some("platt") => Some(Method::Platt),
+        Some("isotonic") => Some(Method::Isotonic),
+        Some("temperature") => Some(Method::Temperature),
+        _ => None,
+    }
+}
+
+// the clubs, the split matches and the entries a model is fitted to, tested on and predicts
+struct Data<'a> {
+    clubs: &'a Clubs,
+    sets: &'a Sets<'a>,
+    training_set: &'a [DataEntry],
+    test_set: &'a [DataEntry],
+    prediction_set: &'a [DataEntry],
+    prediction_matches: &'a [Match],
+}
+
+// fits a model to the training set
+fn fit<P: Predictor + ?Sized>(guru: &Guru, model: &mut P, data: &Data) {
+    println!("Fitting {} Model...", model.name());
+    guru.train(model, data.training_set, &data.sets.data[1]);
+}
+
+// tests a fitted model and predicts the future matches
+fn test<P: Predictor + ?Sized>(guru: &Guru, model: &P, data: &Data, opts: &ArgMatches, max: u8) {
+    report(guru, model, data.sets, data.training_set, data.test_set, opts, max);
+    let (_test_results, predictions) = guru.test(model, data.prediction_set, data.prediction_matches);
+    println!("Predicting future matches: \n");
+    println!("{}", predictions.to_table());
+}
+
+// fits a model, calibrated if --calibrate is given, prints the details of the fitted model and tests it
+fn run<P: Predictor>(guru: &Guru, model: P, data: &Data, opts: &ArgMatches, max: u8, details: impl Fn(&P)) {
+    match calibration(opts) {
+        Some(method) => {
+            let mut calibrated = Calibrated::new(Box::new(model), method);
+            fit(guru, &mut calibrated, data);
+            details(&calibrated.model);
+            test(guru, &calibrated, data, opts, max);
+        }
+        None => {
+            let mut model = model;
+            fit(guru, &mut model, data);
+            details(&model);
+            test(guru, &model, data, opts, max);
+        }
+    }
+}
+
+// fits the model given by --model, false for the networks
+fn fit_model(name: &str, guru: &Guru, data: &Data, opts: &ArgMatches, max: u8) -> bool {
+    match name {
+        "elo" => run(guru, elo(opts), data, opts, max, |_| ()),
+        "glicko" => run(guru, glicko(opts), data, opts, max, |ratings| {
+            println!("Glicko-2 Ratings\n");
+            println!("{}", ratings.ratings().to_table());
+        }),
+        "bradley-terry" => run(guru, BradleyTerry::default(), data, opts, max, |ratings| {
+            println!("Bradley-Terry Strengths\n");
+            println!("{}", ratings.ranking(data.clubs).to_table());
+        }),
+        "poisson" | "dixon-coles" => {
+            let settings = poisson_settings(opts, name == "dixon-coles");
+            run(guru, Poisson::new(settings), data, opts, max, |poisson| {
+                for m in data.prediction_matches {
+                    println!("Score probabilities {} : {}\n", m.home, m.away);
+                    println!("{}", poisson.score_matrix(m).to_table());
+                }
+            })
+        }
+        "bivariate-poisson" => run(guru, BivariatePoisson::new(poisson_settings(opts, false)), data, opts, max, |poisson| {
+            for m in data.prediction_matches {
+                println!("Score probabilities {} : {}\n", m.home, m.away);
+                println!("{}", poisson.score_matrix(m).to_table());
+            }
+        }),
+        "logistic" => run(guru, logistic(opts), data, opts, max, |_| ()),
+        "knn" => run(guru, Knn::new(value(opts, "knn-k", 10)), data, opts, max, |knn| {
+            for (entry, m) in data.prediction_set.iter().zip(data.prediction_matches) {
+                println!("Most similar matches to {} : {}\n", m.home, m.away);
+                println!("{}", knn.neighbours(entry).to_table());
+            }
+        }),
+        "gbdt" => run(guru, gbdt(opts, f64::from(max)), data, opts, max, |trees| {
+            let rounds = trees.rounds();
+            println!("Boosting rounds: {} (goals), {} (outcome)\n", rounds[0], rounds[1]);
+            println!("Input Importance\n");
+            println!("{}", trees.importance().to_table());
+        }),
+        "ensemble" => run(guru, ensemble(opts, max), data, opts, max, |ensemble| {
+            for (entry, m) in data.prediction_set.iter().zip(data.prediction_matches) {
+                println!("{} : {}\n", m.home, m.away);
+                println!("{}", ensemble.breakdown(entry, m).to_table());
+            }
+        }),
+        _ => return false,
+    }
+    true
+}
+
+fn tune(opts: &ArgMatches, sorted: &[Match], clubs: &Clubs) -> std::io::Result<()> {
+    let (matches, entries, max) = played_entries(sorted, clubs);
+    let space = SearchSpace {
+        hidden: opts
+            .value_of("hidden")
+            .unwrap_or("12-8-5,8-4,16,6")
+            .split(',')
+            .map(|c| c.split('-').map(|h| h.trim().parse().unwrap()).collect())
+            .collect(),
+        rate: list(opts, "rates", "0.1,0.2,0.3"),
+        momentum: list(opts, "momentums", "0.1,0.3,0.5"),
+        error: list(opts, "errors", "0.02,0.05,0.1"),
+    };
+    let trials: usize = opts.value_of("trials").unwrap_or("20").parse().unwrap();
+    let strategy = match opts.value_of("strategy") {
+        Some("grid") => Strategy::Grid,
+        Some("halving") => Strategy::SuccessiveHalving {
+            candidates: trials,
+            eta: 3,
+        },
+        _ => Strategy::Random(trials),
+    };
+    let folds: usize = opts.value_of("folds").unwrap_or("4").parse().unwrap();
+    let max_epochs: u32 = opts.value_of("max-epochs").unwrap_or("20000").parse().unwrap();
+    let results = search(&space, strategy, &entries, &matches, folds, max_epochs, f64::from(max));
+    let table = results.to_table();
+    println!("{}", table);
+    std::fs::write(opts.value_of("results").unwrap_or("tune.md"), &table)?;
+    if let Some(best) = results.best() {
+        save_experiment(&best.experiment, opts.value_of("output").unwrap_or("experiment.json"))?;
+    }
+    Ok(())
+}
+
+// all matches with result in chronological order and their entries, features only use earlier matches
+fn played_entries(sorted: &[Match], clubs: &Clubs) -> (Vec<Match>, Vec<DataEntry>, u8) {
+    let ats = Stats::all_time_highest_score_in_league(sorted);
+    let max = if ats[0] > ats[1] { ats[0] } else { ats[1] };
+    let (matches, entries) = scaled_entries(sorted, clubs, max);
+    (matches, entries, max)
+}
+
+// the matches with result and their entries, the goals of the outputs are divided by max
+fn scaled_entries(sorted: &[Match], clubs: &Clubs, max: u8) -> (Vec<Match>, Vec<DataEntry>) {
+    let matches = filter_results(sorted);
+    let mut def_in_gen = DefaultInputGenerator {
+        values: (matches.clone(), clubs, stats(clubs)),
+    };
+    let entries: Vec<DataEntry> = matches
+        .iter()
+        .map(|m| DataEntry::from((m, clubs, max, &mut def_in_gen)))
+        .collect();
+    (matches, entries)
+}
+
+// creates the models of the --model of an evaluation subcommand, a network has the default layers
+fn factory<'a>(opts: &'a ArgMatches, entries: &[DataEntry], max: u8) -> BaseModel<'a> {
+    let name = opts.value_of("model").unwrap_or("elo");
+    if name != "network" {
+        return base_model(String::from(name), opts, max);
+    }
+    let layer_sizes = vec![entries[0].inputs.len() as u32, 12, 8, 5, entries[0].outputs.len() as u32];
+    let settings = PredictorSettings {
+        rate: 0.2,
+        momentum: 0.3,
+        error: value(opts, "error", 0.1),
+        scale: f64::from(max),
+    };
+    Box::new(move || {
+        let mut net = NN::new(&layer_sizes);
+        net.set_predictor_settings(settings);
+        Box::new(net)
+    })
+}
+
+// the goals of a backtest are divided by a fixed maximum, the highest score of all matches is a
+// later result for most steps
+const BACKTEST_SCALE: u8 = 10;
+
+fn backtest(opts: &ArgMatches, sorted: &[Match], clubs: &Clubs) -> std::io::Result<()> {
+    let max = BACKTEST_SCALE;
+    let (matches, entries) = scaled_entries(sorted, clubs, max);
+    let backtest = Backtest {
+        window: value(opts, "window", 1),
+        min_training: value(opts, "min-training", 10),
+    };
+    let report = backtest.run(&Guru::new(sorted), &factory(opts, &entries, max), &entries, &matches);
+    println!("Walk-forward Backtest\n");
+    println!("{}", report.to_table());
+    println!("{}", report.scores().to_table());
+    println!("Bootstrap intervals (1000 samples)\n");
+    println!("{}", Intervals::bootstrap(&report.predictions, 1000, 0.95).to_table());
+    Ok(())
+}
+
+fn cross_validate(opts: &ArgMatches, sorted: &[Match], clubs: &Clubs) -> std::io::Result<()> {
+    let (matches, entries, max) = played_entries(sorted, clubs);
+    let cv = CrossValidation {
+        k: value(opts, "folds", 5),
+        folding: match opts.value_of("folding") {
+            Some("stratified") => Folding::Stratified,
+            Some("time") => Folding::TimeBlocked,
+            _ => Folding::Random,
+        },
+    };
+    let report = cv.run(&Guru::new(sorted), &factory(opts, &entries, max), &entries, &matches);
+    println!("{}-fold Cross Validation\n", cv.k);
+    println!("{}", report.to_table());
+    println!("Bootstrap intervals of the pooled fold predictions (1000 samples)\n");
+    println!("{}", Intervals::bootstrap(&report.predictions, 1000, 0.95).to_table());
+    Ok(())
+}
+
+fn predict(opts: &ArgMatches, sorted: &[Match], clubs: &Clubs) -> std::io::Result<()> {
+    let (matches, entries, max) = played_entries(sorted, clubs);
+    let future = filter_no_results(sorted);
+    let mut def_in_gen = DefaultInputGenerator {
+        values: (matches.clone(), clubs, stats(clubs)),
+    };
+    let future_entries: Vec<DataEntry> = future
+        .iter()
+        .map(|m| DataEntry::from((m, clubs, max, &mut def_in_gen)))
+        .collect();
+    let guru = Guru::new(sorted);
+    let mut model = factory(opts, &entries, max)();
+    println!("Fitting {} Model...", model.name());
+    guru.train(model.as_mut(), &entries, &matches);
+    let (_, predictions) = guru.test(model.as_ref(), &future_entries, &future);
+    println!("Predicting future matches: \n");
+    println!("{}", predictions.to_table());
+    let id = opts.value_of("id").map(String::from).unwrap_or_else(|| model.name());
+    let ledger = Ledger::new(&id, Utc::now().into(), &predictions);
+    append_ledger(opts.value_of("ledger").unwrap_or("guru.ledger"), &ledger)
+}
+
+fn rank(opts: &ArgMatches, sorted: &[Match]) -> std::io::Result<()> {
+    let by = match opts.value_of("by") {
+        Some("colley") => RankBy::Colley,
+        Some("pi") => RankBy::Pi,
+        Some("elo") => RankBy::Elo,
+        _ => RankBy::Massey,
+    };
+    let until = opts
+        .value_of("date")
+        .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").expect("date must be formatted as YYYY-MM-DD"));
+    println!("{}", PowerRanking::new(sorted, until, by).to_table());
+    Ok(())
+}
+
 fn main() -> std::io::Result<()> {
     let yaml = load_yaml!("../../config/cli.yml");
     let opts = App::from_yaml(yaml).get_matches();
-    let error = f64::from_str(opts.value_of("error").unwrap()).unwrap();
     let all_matches = if let Some(f) = opts.value_of("data") {
         load_matches(f)?
     } else {
@@ -40,6 +506,48 @@ fn main() -> std::io::Result<()> {
     // Clubs is required because ```Club```(s) are taken from a set of matches (data.json) without
     // ids
     let clubs: Clubs = Clubs::from(sorted.as_slice());
+    if let Some(tune_opts) = opts.subcommand_matches("tune") {
+        return tune(tune_opts, &sorted, &clubs);
+    }
+    if let Some(backtest_opts) = opts.subcommand_matches("backtest") {
+        return backtest(backtest_opts, &sorted, &clubs);
+    }
+    if let Some(cv_opts) = opts.subcommand_matches("cv") {
+        return cross_validate(cv_opts, &sorted, &clubs);
+    }
+    if let Some(predict_opts) = opts.subcommand_matches("predict") {
+        return predict(predict_opts, &sorted, &clubs);
+    }
+    if let Some(reconcile_opts) = opts.subcommand_matches("reconcile") {
+        let record = load_ledger(reconcile_opts.value_of("ledger").unwrap_or("guru.ledger"))?.reconcile(&sorted);
+        println!("Live Track Record\n");
+        println!("{}", record.to_table());
+        return Ok(());
+    }
+    if let Some(rank_opts) = opts.subcommand_matches("rank") {
+        return rank(rank_opts, &sorted);
+    }
+    let experiment = match opts.value_of("experiment") {
+        Some(f) => Some(load_experiment(f)?),
+        None => None,
+    };
+    let error = match (opts.value_of("error"), &experiment) {
+        (Some(e), _) => Some(f64::from_str(e).unwrap()),
+        (None, Some(experiment)) => Some(experiment.error),
+        (None, None) => None,
+    };
+    let (rate, momentum) = match &experiment {
+        Some(experiment) => (experiment.rate, experiment.momentum),
+        None => (0.2, 0.3),
+    };
+    // checked before training, the passes are only run after it
+    let passes: usize = value(&opts, "passes", 100);
+    if passes == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--passes must be at least 1",
+        ));
+    }
     let stats = stats(&clubs);
     let guru = Guru::new(&sorted);
 
@@ -81,59 +589,329 @@ fn main() -> std::io::Result<()> {
     let ats = Stats::all_time_highest_score_in_league(&sorted);
     // TODO: let Generator do that
     let max = if ats[0] > ats[1] { ats[0] } else { ats[1] };
-    let mut def_in_gen = DefaultInputGenerator {
+    let def_in_gen = DefaultInputGenerator {
         values: (sets.data[0].clone(), &clubs, stats.clone()),
     };
+    // ratings only use matches before the date of a match, but only the results of the training
+    // set may be processed, the test matches of a random split can be older than training matches
+    let mut elo_features = elo(&opts);
+    let mut generator: Box<dyn Generator> = if opts.is_present("elo-features") {
+        elo_features.process(&sets.data[1]);
+        Box::new(EloInputGenerator {
+            generator: def_in_gen,
+            elo: &elo_features,
+        })
+    } else {
+        Box::new(def_in_gen)
+    };
     let training_set: Vec<DataEntry> = sets.data[1]
         .iter()
-        .map(|m| DataEntry::from((m, &clubs, max, &mut def_in_gen)))
+        .map(|m| DataEntry::from((m, &clubs, max, &mut generator)))
         .collect();
     let test_set: Vec<DataEntry> = sets.data[0]
         .iter()
-        .map(|m| DataEntry::from((m, &clubs, max, &mut def_in_gen)))
+        .map(|m| DataEntry::from((m, &clubs, max, &mut generator)))
         .collect();
     let prediction_set: Vec<DataEntry> = prediction_matches
         .iter()
-        .map(|m| DataEntry::from((m, &clubs, max, &mut def_in_gen)))
+        .map(|m| DataEntry::from((m, &clubs, max, &mut generator)))
         .collect();
 
+    let data = Data {
+        clubs: &clubs,
+        sets: &sets,
+        training_set: &training_set,
+        test_set: &test_set,
+        prediction_set: &prediction_set,
+        prediction_matches: &prediction_matches,
+    };
+    if let Some(name) = opts.value_of("model") {
+        if fit_model(name, &guru, &data, &opts, max) {
+            return Ok(());
+        }
+    }
+    // networks of a stacking train for the default number of epochs without --error, networks with
+    // normalization layers always do (clap rejects --error for them, the error of an experiment is ignored)
+    let error = if opts.is_present("batch-norm") || opts.is_present("layer-norm") {
+        0f64
+    } else if opts.value_of("model") == Some("stacking") {
+        error.unwrap_or(0f64)
+    } else {
+        match error {
+            Some(error) => error,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "--error or --experiment is required to train a network",
+                ))
+            }
+        }
+    };
+
     // Creating the network
     //let _hidden_size = (training_set[0].inputs.len() as f64 * 0.66).round() as u32;
-    let mut net = if opts.is_present("load-network") {
-        load_network()?
-    } else {
-        NN::new(&[
+    let layer_sizes = match &experiment {
+        Some(experiment) => {
+            experiment.layer_sizes(training_set[0].inputs.len(), training_set[0].outputs.len())
+        }
+        None => vec![
             training_set[0].inputs.len() as u32,
             12,
             8,
             5,
             training_set[0].outputs.len() as u32,
-        ])
+        ],
+    };
+    let settings = PredictorSettings {
+        rate,
+        momentum,
+        error,
+        scale: f64::from(max),
     };
+    // creates an untrained network with the normalization layers given by --batch-norm and --layer-norm
+    let new_network = || {
+        let mut net = NN::new(&layer_sizes);
+        net.set_predictor_settings(settings);
+        for (arg, kind) in &[("batch-norm", NormKind::Batch), ("layer-norm", NormKind::Layer)] {
+            if let Some(positions) = opts.value_of(arg) {
+                for position in positions.split(',') {
+                    net.add_norm(position.trim().parse().unwrap(), *kind);
+                }
+            }
+        }
+        net
+    };
+    if opts.value_of("model") == Some("stacking") {
+        let bases: Vec<BaseModel> = list::<String>(&opts, "stack-members", "network,elo,dixon-coles")
+            .into_iter()
+            .map(|member| -> BaseModel {
+                if member == "network" {
+                    let network = new_network();
+                    return Box::new(move || Box::new(network.clone()));
+                }
+                base_model(member, &opts, max)
+            })
+            .collect();
+        let mut stacking = Stacking::new(bases, value(&opts, "stack-folds", 5));
+        println!("Fitting Stacking Model...");
+        guru.train(&mut stacking, &training_set, &sets.data[1]);
+        println!("Out-of-fold predictions of the base models\n");
+        println!("{}", stacking.out_of_fold().to_table());
+        report(&guru, &stacking, &sets, &training_set, &test_set, &opts, max);
+        let (_test_results, predictions) = guru.test(&stacking, &prediction_set, &prediction_matches);
+        println!("Predicting future matches: \n");
+        println!("{}", predictions.to_table());
+        return Ok(());
+    }
+    let aggregation = if opts.is_present("bagging") {
+        Some((Aggregation::Bagging, opts.value_of("bagging").unwrap()))
+    } else {
+        opts.value_of("boosting").map(|size| (Aggregation::Boosting, size))
+    };
+    if aggregation.is_some() || opts.is_present("load-ensemble") {
+        let mut ensemble = if opts.is_present("load-ensemble") {
+            let mut ensemble = load_ensemble()?;
+            for member in ensemble.members.iter_mut() {
+                member.set_predictor_settings(settings);
+            }
+            ensemble
+        } else {
+            let (aggregation, size) = aggregation.unwrap();
+            let size: usize = size.parse().unwrap();
+            NetworkEnsemble::new(aggregation, (0..size).map(|_| new_network()).collect())
+        };
+        if !opts.is_present("no-train") && !opts.is_present("load-ensemble") {
+            guru.train(&mut ensemble, &training_set, &sets.data[1]);
+            if let Some(oob) = ensemble.out_of_bag() {
+                println!(
+                    "Out-of-bag estimate: error {:.4}, winners {:.1}% ({} matches)\n",
+                    oob.error, oob.winners, oob.covered
+                );
+            }
+            let weights: Vec<String> = ensemble.weights().iter().map(|w| format!("{:.3}", w)).collect();
+            println!("Network weights: {}\n", weights.join(", "));
+        }
+        if opts.is_present("save-network") {
+            save_ensemble(&ensemble)?;
+        }
+        report(&guru, &ensemble, &sets, &training_set, &test_set, &opts, max);
+        let (_test_results, predictions) = guru.test(&ensemble, &prediction_set, &prediction_matches);
+        println!("Predicting future matches: \n");
+        println!("{}", predictions.to_table());
+        return Ok(());
+    }
+    let mut net = if opts.is_present("load-network") {
+        let mut net = load_network()?;
+        // the scale depends on the data set, not on the file
+        net.set_predictor_settings(settings);
+        net
+    } else {
+        new_network()
+    };
+    // the calibrator is fitted to the network's forecasts of the last 20% of the training set, a
+    // network changed after training by distillation or pruning would no longer match it
+    let changed = opts.is_present("distill") || opts.is_present("prune") || opts.is_present("prune-units");
+    if calibration(&opts).is_some() && changed {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--calibrate can't be combined with --distill, --prune or --prune-units",
+        ));
+    }
+    // networks with normalization layers can't be pruned, checked before they are trained
+    let pruned = opts.is_present("prune") || opts.is_present("prune-units");
+    if pruned && net.has_norms() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--prune and --prune-units can't be combined with --batch-norm or --layer-norm",
+        ));
+    }
+    let mut calibrator = None;
     if !opts.is_present("no-train") {
-        println!("Training Prediction Network...");
-        guru.train(&mut net, &training_set, 0.3, 0.2, error);
+        if let Some(size) = opts.value_of("distill") {
+            let size: usize = size.parse().unwrap();
+            let alpha: f64 = if opts.is_present("distill-alpha") {
+                opts.value_of("distill-alpha").unwrap().parse().unwrap()
+            } else {
+                0.5
+            };
+            let mut teachers = vec![];
+            for i in 0..size {
+                println!("Training Teacher Network {} of {}...", i + 1, size);
+                let mut teacher = new_network();
+                guru.train(&mut teacher, &training_set, &sets.data[1]);
+                teachers.push(teacher);
+            }
+            println!("Training Student Network...");
+            let distilled = distill_set(&teachers, &training_set, &prediction_set, alpha);
+            let distilled_matches: Vec<Match> =
+                sets.data[1].iter().chain(prediction_matches.iter()).cloned().collect();
+            guru.train(&mut net, &distilled, &distilled_matches);
+            println!("Student tracking the Teacher Ensemble");
+            println!("--------------");
+            let scale = f64::from(max);
+            println!("training: {}", Fidelity::measure(&teachers, &net, &training_set, scale));
+            println!("test: {}", Fidelity::measure(&teachers, &net, &test_set, scale));
+            println!("prediction: {}\n", Fidelity::measure(&teachers, &net, &prediction_set, scale));
+        } else if let Some(method) = calibration(&opts) {
+            // trained on the first 80% of the training set, calibrated on the rest, then trained on all
+            println!("Training and Calibrating Prediction Network...");
+            let mut calibrated = Calibrated::new(Box::new(net), method);
+            guru.train(&mut calibrated, &training_set, &sets.data[1]);
+            calibrator = Some(calibrated.calibrator().clone());
+            net = *calibrated.model;
+        } else {
+            println!("Training Prediction Network...");
+            guru.train(&mut net, &training_set, &sets.data[1]);
+        }
+    } else if calibration(&opts).is_some() {
+        calibrator = Some(load_calibrator()?);
     }
 
+    if opts.is_present("prune") || opts.is_present("prune-units") {
+        let share = |name: &str| -> f64 {
+            if opts.is_present(name) {
+                opts.value_of(name).unwrap().parse().unwrap()
+            } else {
+                0.0
+            }
+        };
+        let (connection_share, unit_share) = (share("prune"), share("prune-units"));
+        let criterion = match opts.value_of("prune-criterion") {
+            Some("sensitivity") => Criterion::Sensitivity,
+            _ => Criterion::Magnitude,
+        };
+        let steps: usize = if opts.is_present("prune-steps") {
+            opts.value_of("prune-steps").unwrap().parse().unwrap()
+        } else {
+            4
+        };
+        let epochs: u32 = if opts.is_present("prune-epochs") {
+            opts.value_of("prune-epochs").unwrap().parse().unwrap()
+        } else {
+            1000
+        };
+        let training_examples: Vec<(Vec<f64>, Vec<f64>)> = training_set
+            .iter()
+            .map(|e| (e.inputs.clone(), e.outputs.clone()))
+            .collect();
+        let test_examples: Vec<(Vec<f64>, Vec<f64>)> = test_set
+            .iter()
+            .map(|e| (e.inputs.clone(), e.outputs.clone()))
+            .collect();
+        let original_connections = connections(&net);
+        let original_units: usize = net.layers[..net.layers.len() - 1].iter().map(|l| l.len()).sum();
+        let mut report = PruneReport::default();
+        let (stats, _) = guru.test(&net, &test_set, &sets.data[0]);
+        report.0.push(PruneStep::measure(&net, original_connections, &training_examples, &test_examples, &stats));
+        for step in 1..=steps {
+            println!("Pruning Step {} of {}...", step, steps);
+            let progress = step as f64 / steps as f64;
+            // targets are relative to the original network, compaction removes nodes in between
+            let units: usize = net.layers[..net.layers.len() - 1].iter().map(|l| l.len()).sum();
+            let unit_target = (unit_share * progress * original_units as f64).round() as usize;
+            prune_units(&mut net, &training_examples, criterion, unit_target.saturating_sub(original_units - units));
+            let connection_target = (connection_share * progress * original_connections as f64).round() as usize;
+            let pruned = original_connections - connections(&net);
+            prune_connections(&mut net, &training_examples, criterion, connection_target.saturating_sub(pruned));
+            fine_tune(&mut net, &training_examples, epochs, rate, momentum);
+            compact(&mut net);
+            let (stats, _) = guru.test(&net, &test_set, &sets.data[0]);
+            report.0.push(PruneStep::measure(&net, original_connections, &training_examples, &test_examples, &stats));
+        }
+        println!("Pruning Network\n");
+        println!("{}", report.to_table());
+    }
+
+    let calibrated = calibrator.map(|calibrator| Calibrated::with_calibrator(Box::new(net.clone()), calibrator));
+
     if opts.is_present("save-network") {
         save_network(&net)?;
+        if let Some(calibrated) = &calibrated {
+            save_calibrator(calibrated.calibrator())?;
+        }
     }
 
     // testing / validating
-    let (test_results, predictions) = guru.test(&mut net, &training_set, &sets.data[1]);
-    println!("Testing on (seen) Training Data");
-    println!("{}", predictions);
-    println!("Result {}\n", test_results[0].to_string());
-    println!("Winner {}", test_results[1].to_string());
-    println!("--------------------------\n\n");
-    let (test_results, test_predictions) = guru.test(&mut net, &test_set, &sets.data[0]);
-    println!("Testing on (unseen) Test Data");
-    println!("{}", test_predictions);
-    println!("Result {}\n", test_results[0].to_string());
-    println!("Winner {}", test_results[1].to_string());
-    println!("--------------------------\n\n");
+    let predictor: &dyn Predictor = match &calibrated {
+        Some(calibrated) => calibrated,
+        None => &net,
+    };
+    report(&guru, predictor, &sets, &training_set, &test_set, &opts, max);
     // predict future matches
-    let (_test_results, predictions) = guru.test(&mut net, &prediction_set, &prediction_matches);
+    let (_test_results, mut predictions) = guru.test(predictor, &prediction_set, &prediction_matches);
+    if let Some(mode) = opts.value_of("uncertainty") {
+        let level: f64 = if opts.is_present("interval") {
+            opts.value_of("interval").unwrap().parse().unwrap()
+        } else {
+            0.9
+        };
+        let mut nets = vec![net];
+        let mode = if mode == "ensemble" {
+            let size: usize = if opts.is_present("ensemble-size") {
+                opts.value_of("ensemble-size").unwrap().parse().unwrap()
+            } else {
+                5
+            };
+            // the prediction network is the first member, the others are trained from scratch
+            for i in 1..size {
+                println!("Training Ensemble Member {} of {}...", i + 1, size);
+                let mut member = new_network();
+                guru.train(&mut member, &training_set, &sets.data[1]);
+                nets.push(member);
+            }
+            UncertaintyMode::DeepEnsemble
+        } else {
+            UncertaintyMode::MonteCarloDropout {
+                passes,
+                rate: if opts.is_present("dropout-rate") {
+                    opts.value_of("dropout-rate").unwrap().parse().unwrap()
+                } else {
+                    0.1
+                },
+            }
+        };
+        guru.estimate_uncertainty(&nets, mode, level, &prediction_set, &mut predictions);
+    }
     // TODO: Fix empty
     println!("Predicting future matches: \n");
     println!("{}", predictions.to_table());
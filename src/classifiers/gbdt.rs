@@ -0,0 +1,548 @@
+use crate::{
+    models::{DataEntry, Match, Outcome},
+    Markdown, Predictor,
+};
+use rand::{seq::SliceRandom, thread_rng};
+
+/**
+    The settings of the gradient boosted trees.
+
+    * ```trees```: The maximum number of boosting rounds.
+    * ```depth```: The maximum depth of each tree, 1 is a single split.
+    * ```shrinkage```: The learning rate, each tree adds this share of its prediction.
+    * ```subsample```: The share of the training entries each tree is fitted to, drawn without replacement.
+    * ```min_leaf```: The minimum number of entries in a leaf.
+    * ```patience```: Early stopping, the boosting stops after this number of rounds without improvement
+      of the validation loss. ```None``` boosts all rounds without validation.
+    * ```validation```: The share of the training entries held out for early stopping (the last entries).
+    * ```scale```: The predicted outputs are multiplied by ```scale``` to get the expected goals,
+      ie. the maximum used to normalize the outputs of the entries.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GbdtSettings {
+    pub trees: usize,
+    pub depth: usize,
+    pub shrinkage: f64,
+    pub subsample: f64,
+    pub min_leaf: usize,
+    pub patience: Option<usize>,
+    pub validation: f64,
+    pub scale: f64,
+}
+
+/**
+    Gradient boosted regression trees (Friedman, 2001). Two boosted ensembles are fitted to the
+    inputs of the entries:
+
+    * Regression (squared loss) of the outputs, the normalized goals of both teams.
+    * Classification (softmax, one tree per outcome and round) of the home win, draw and away win.
+
+    Each tree is fitted to the residuals of the previous rounds, its leaves are Newton steps.
+    Trees only compare inputs with thresholds, the inputs don't need to be normalized.
+
+    **Example**:
+
+    ```text
+    let mut gbdt = Gbdt::new(GbdtSettings { depth: 2, ..GbdtSettings::default() });
+    gbdt.fit_entries(&training_set, &matches);
+    println!("{}", gbdt.importance().to_table());
+    ```
+**/
+#[derive(Clone, Debug)]
+pub struct Gbdt {
+    pub settings: GbdtSettings,
+    goals: Boosted,
+    outcome: Boosted,
+}
+
+/// The share of the total split gain of each input, most important input first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Importance(pub Vec<(usize, f64)>);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Loss {
+    Squared,
+    Softmax,
+}
+
+// a boosted ensemble with one tree per target and round
+#[derive(Clone, Debug)]
+struct Boosted {
+    loss: Loss,
+    init: Vec<f64>,
+    rounds: Vec<Vec<Tree>>,
+    shrinkage: f64,
+}
+
+#[derive(Clone, Debug)]
+enum Tree {
+    Leaf(f64),
+    Split {
+        feature: usize,
+        threshold: f64,
+        gain: f64,
+        left: Box<Tree>,
+        right: Box<Tree>,
+    },
+}
+
+impl Default for GbdtSettings {
+    fn default() -> Self {
+        GbdtSettings {
+            trees: 200,
+            depth: 3,
+            shrinkage: 0.1,
+            subsample: 0.8,
+            min_leaf: 3,
+            patience: Some(20),
+            validation: 0.2,
+            scale: 1f64,
+        }
+    }
+}
+
+impl Default for Gbdt {
+    fn default() -> Self {
+        Gbdt::new(GbdtSettings::default())
+    }
+}
+
+impl Gbdt {
+    pub fn new(settings: GbdtSettings) -> Self {
+        if settings.depth == 0 || settings.min_leaf == 0 {
+            panic!("depth and min_leaf must be at least 1");
+        }
+        if settings.shrinkage <= 0f64 || settings.subsample <= 0f64 || settings.subsample > 1f64 {
+            panic!("shrinkage must be positive and subsample between 0 and 1");
+        }
+        if settings.validation < 0f64 || settings.validation >= 1f64 {
+            panic!("validation must be between 0 and 1");
+        }
+        if settings.scale <= 0f64 {
+            panic!("scale must be a positive number");
+        }
+        Gbdt {
+            settings,
+            goals: Boosted::new(Loss::Squared, 2, settings.shrinkage),
+            outcome: Boosted::new(Loss::Softmax, 3, settings.shrinkage),
+        }
+    }
+
+    /**
+        Fits both ensembles to the entries with result, ```matches``` are the matches the entries
+        were created from. Previously fitted trees are discarded.
+    **/
+    pub fn fit_entries(&mut self, entries: &[DataEntry], matches: &[Match]) {
+        let played: Vec<(&DataEntry, [u8; 2])> = entries
+            .iter()
+            .zip(matches)
+            .filter_map(|(e, m)| m.result.map(|r| (e, r)))
+            .collect();
+        let inputs: Vec<&[f64]> = played.iter().map(|(e, _)| e.inputs.as_slice()).collect();
+        let goals: Vec<Vec<f64>> = played.iter().map(|(e, _)| e.outputs.clone()).collect();
+        let outcomes: Vec<Vec<f64>> = played
+            .iter()
+            .map(|(_, r)| {
+                let mut target = vec![0f64; 3];
+                target[Outcome::from(*r).index()] = 1f64;
+                target
+            })
+            .collect();
+        self.goals = Boosted::new(Loss::Squared, 2, self.settings.shrinkage);
+        self.goals.fit(&inputs, &goals, &self.settings);
+        self.outcome = Boosted::new(Loss::Softmax, 3, self.settings.shrinkage);
+        self.outcome.fit(&inputs, &outcomes, &self.settings);
+    }
+
+    /// Returns the expected goals of the home and the away team.
+    pub fn expected_goals(&self, entry: &DataEntry) -> [f64; 2] {
+        let goals = self.goals.predict(&entry.inputs);
+        [
+            (goals[0] * self.settings.scale).max(0f64),
+            (goals[1] * self.settings.scale).max(0f64),
+        ]
+    }
+
+    /// Returns the probabilities of a home win, a draw and an away win.
+    pub fn probabilities(&self, entry: &DataEntry) -> [f64; 3] {
+        let p = self.outcome.predict(&entry.inputs);
+        [p[0], p[1], p[2]]
+    }
+
+    /// Returns the number of boosting rounds kept after early stopping (goals, outcome).
+    pub fn rounds(&self) -> [usize; 2] {
+        [self.goals.rounds.len(), self.outcome.rounds.len()]
+    }
+
+    /// Returns the share of the split gain of each input index over all trees of both ensembles.
+    pub fn importance(&self) -> Importance {
+        let mut gains: Vec<f64> = vec![];
+        for tree in self.goals.rounds.iter().chain(&self.outcome.rounds).flatten() {
+            tree.gains(&mut gains);
+        }
+        let total: f64 = gains.iter().sum();
+        let mut importance: Vec<(usize, f64)> = gains
+            .into_iter()
+            .map(|g| if total > 0f64 { g / total } else { 0f64 })
+            .enumerate()
+            .collect();
+        importance.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+        Importance(importance)
+    }
+}
+
+impl Boosted {
+    fn new(loss: Loss, targets: usize, shrinkage: f64) -> Self {
+        Boosted {
+            loss,
+            init: vec![0f64; targets],
+            rounds: vec![],
+            shrinkage,
+        }
+    }
+
+    // boosts rounds of trees, the last entries are held out for early stopping
+    fn fit(&mut self, inputs: &[&[f64]], targets: &[Vec<f64>], settings: &GbdtSettings) {
+        let k = self.init.len();
+        let held_out = if settings.patience.is_some() {
+            (inputs.len() as f64 * settings.validation).round() as usize
+        } else {
+            0
+        };
+        let (train, validate) = inputs.split_at(inputs.len() - held_out);
+        let (train_targets, validate_targets) = targets.split_at(targets.len() - held_out);
+        if train.is_empty() {
+            return;
+        }
+        self.init = (0..k)
+            .map(|t| {
+                let mean = train_targets.iter().map(|y| y[t]).sum::<f64>() / train.len() as f64;
+                match self.loss {
+                    Loss::Squared => mean,
+                    Loss::Softmax => mean.max(1e-6).ln(),
+                }
+            })
+            .collect();
+        let mut raw: Vec<Vec<f64>> = vec![self.init.clone(); train.len()];
+        let mut validation_raw: Vec<Vec<f64>> = vec![self.init.clone(); validate.len()];
+        let (mut best, mut best_rounds) = (self.loss(&validation_raw, validate_targets), 0);
+        let size = ((train.len() as f64 * settings.subsample).round() as usize).max(1);
+        let mut indices: Vec<usize> = (0..train.len()).collect();
+        let mut rng = thread_rng();
+        for _ in 0..settings.trees {
+            indices.shuffle(&mut rng);
+            let sample = &indices[..size];
+            let mut round = vec![];
+            for t in 0..k {
+                let mut residuals = vec![0f64; train.len()];
+                let mut hessians = vec![1f64; train.len()];
+                for &i in sample {
+                    let p = self.transform(&raw[i]);
+                    residuals[i] = train_targets[i][t] - p[t];
+                    if self.loss == Loss::Softmax {
+                        hessians[i] = (p[t] * (1f64 - p[t])).max(1e-6);
+                    }
+                }
+                let factor = match self.loss {
+                    Loss::Squared => 1f64,
+                    Loss::Softmax => (k as f64 - 1f64) / k as f64,
+                };
+                round.push(Tree::grow(train, sample, &residuals, &hessians, factor, settings, 0));
+            }
+            for (i, x) in train.iter().enumerate() {
+                for (t, tree) in round.iter().enumerate() {
+                    raw[i][t] += self.shrinkage * tree.predict(x);
+                }
+            }
+            for (i, x) in validate.iter().enumerate() {
+                for (t, tree) in round.iter().enumerate() {
+                    validation_raw[i][t] += self.shrinkage * tree.predict(x);
+                }
+            }
+            self.rounds.push(round);
+            if let Some(patience) = settings.patience {
+                if validate.is_empty() {
+                    continue;
+                }
+                let loss = self.loss(&validation_raw, validate_targets);
+                if loss < best {
+                    best = loss;
+                    best_rounds = self.rounds.len();
+                } else if self.rounds.len() - best_rounds >= patience {
+                    break;
+                }
+            }
+        }
+        if settings.patience.is_some() && !validate.is_empty() {
+            self.rounds.truncate(best_rounds);
+        }
+    }
+
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        let mut raw = self.init.clone();
+        for round in &self.rounds {
+            for (r, tree) in raw.iter_mut().zip(round) {
+                *r += self.shrinkage * tree.predict(x);
+            }
+        }
+        self.transform(&raw)
+    }
+
+    // the prediction of raw scores: the scores for squared loss, probabilities for softmax
+    fn transform(&self, raw: &[f64]) -> Vec<f64> {
+        match self.loss {
+            Loss::Squared => raw.to_vec(),
+            Loss::Softmax => {
+                let max = raw.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                let exp: Vec<f64> = raw.iter().map(|r| (r - max).exp()).collect();
+                let total: f64 = exp.iter().sum();
+                exp.iter().map(|e| e / total).collect()
+            }
+        }
+    }
+
+    // the mean squared error or the cross entropy of raw scores
+    fn loss(&self, raw: &[Vec<f64>], targets: &[Vec<f64>]) -> f64 {
+        let total: f64 = raw
+            .iter()
+            .zip(targets)
+            .map(|(r, y)| {
+                let p = self.transform(r);
+                match self.loss {
+                    Loss::Squared => p.iter().zip(y).map(|(p, y)| (p - y).powi(2)).sum::<f64>(),
+                    Loss::Softmax => -p.iter().zip(y).map(|(p, y)| y * p.max(1e-12).ln()).sum::<f64>(),
+                }
+            })
+            .sum();
+        total / raw.len().max(1) as f64
+    }
+}
+
+impl Tree {
+    // grows a least squares regression tree on the residuals, the leaves are Newton steps
+    fn grow(
+        inputs: &[&[f64]],
+        sample: &[usize],
+        residuals: &[f64],
+        hessians: &[f64],
+        factor: f64,
+        settings: &GbdtSettings,
+        depth: usize,
+    ) -> Tree {
+        let sum: f64 = sample.iter().map(|&i| residuals[i]).sum();
+        let leaf = || {
+            let hessian: f64 = sample.iter().map(|&i| hessians[i]).sum();
+            Tree::Leaf(if hessian > 0f64 { factor * sum / hessian } else { 0f64 })
+        };
+        if depth >= settings.depth || sample.len() < 2 * settings.min_leaf {
+            return leaf();
+        }
+        let features = inputs[sample[0]].len();
+        // feature, threshold, gain
+        let best = (0..features)
+            .filter_map(|feature| {
+                Tree::split(inputs, sample, residuals, feature, settings.min_leaf)
+                    .map(|(threshold, gain)| (feature, threshold, gain))
+            })
+            .fold(None, |best: Option<(usize, f64, f64)>, split| match best {
+                Some((_, _, gain)) if gain >= split.2 => best,
+                _ => Some(split),
+            });
+        match best {
+            Some((feature, threshold, gain)) => {
+                let (left, right): (Vec<usize>, Vec<usize>) =
+                    sample.iter().partition(|&&i| inputs[i][feature] <= threshold);
+                Tree::Split {
+                    feature,
+                    threshold,
+                    gain,
+                    left: Box::new(Tree::grow(inputs, &left, residuals, hessians, factor, settings, depth + 1)),
+                    right: Box::new(Tree::grow(inputs, &right, residuals, hessians, factor, settings, depth + 1)),
+                }
+            }
+            None => leaf(),
+        }
+    }
+
+    // the threshold and the gain of the best split on a feature, None if no split is possible
+    fn split(
+        inputs: &[&[f64]],
+        sample: &[usize],
+        residuals: &[f64],
+        feature: usize,
+        min_leaf: usize,
+    ) -> Option<(f64, f64)> {
+        let mut sorted: Vec<(f64, f64)> = sample.iter().map(|&i| (inputs[i][feature], residuals[i])).collect();
+        sorted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let (n, sum) = (sorted.len() as f64, sorted.iter().map(|(_, r)| r).sum::<f64>());
+        let mut left = 0f64;
+        let mut best: Option<(f64, f64)> = None;
+        for (j, pair) in sorted.windows(2).enumerate() {
+            left += pair[0].1;
+            let count = j + 1;
+            if pair[0].0 == pair[1].0 || count < min_leaf || sorted.len() - count < min_leaf {
+                continue;
+            }
+            let right = sum - left;
+            let count = count as f64;
+            let gain = left * left / count + right * right / (n - count) - sum * sum / n;
+            if best.map(|(_, g)| gain > g).unwrap_or(gain > 0f64) {
+                best = Some(((pair[0].0 + pair[1].0) / 2f64, gain));
+            }
+        }
+        best
+    }
+
+    fn predict(&self, x: &[f64]) -> f64 {
+        match self {
+            Tree::Leaf(value) => *value,
+            Tree::Split {
+                feature,
+                threshold,
+                left,
+                right,
+                ..
+            } => {
+                if x[*feature] <= *threshold {
+                    left.predict(x)
+                } else {
+                    right.predict(x)
+                }
+            }
+        }
+    }
+
+    // adds the split gains of the tree to the gains of each feature
+    fn gains(&self, gains: &mut Vec<f64>) {
+        if let Tree::Split {
+            feature,
+            gain,
+            left,
+            right,
+            ..
+        } = self
+        {
+            if gains.len() <= *feature {
+                gains.resize(feature + 1, 0f64);
+            }
+            gains[*feature] += gain;
+            left.gains(gains);
+            right.gains(gains);
+        }
+    }
+}
+
+/// Formats ```Importance``` into a Markdown Table, including header.
+impl Markdown for Importance {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Rank|Input|Importance|\n");
+        s.push_str("|-:|-:|-:|\n");
+        for (i, (input, importance)) in self.0.iter().enumerate() {
+            s.push_str(&format!("|{}|{}|{:.1}%|\n", i + 1, input, importance * 100f64));
+        }
+        s
+    }
+}
+
+/**
+    The predicted scores are the regressed outputs multiplied by ```GbdtSettings::scale```,
+    the outcome probabilities come from the classification ensemble.
+**/
+impl Predictor for Gbdt {
+    fn fit(&mut self, training_set: &[DataEntry], matches: &[Match]) {
+        self.fit_entries(training_set, matches);
+    }
+
+    fn predict_scores(&self, entry: &DataEntry, _m: &Match) -> [f64; 2] {
+        self.expected_goals(entry)
+    }
+
+    fn predict_outcome(&self, entry: &DataEntry, _m: &Match) -> [f64; 3] {
+        self.probabilities(entry)
+    }
+
+    fn name(&self) -> String {
+        String::from("Gradient Boosted Trees")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::played;
+
+    // the home team wins 2 : 0 if the first input is above 0.5, the second input is constant
+    fn data() -> (Vec<DataEntry>, Vec<Match>) {
+        (0..40)
+            .map(|day| {
+                let strong = day % 2 == 0;
+                let x = if strong { 0.6 + day as f64 / 100f64 } else { 0.4 - day as f64 / 100f64 };
+                let result = if strong { [2, 0] } else { [0, 2] };
+                let entry = DataEntry {
+                    inputs: vec![x, 1f64],
+                    outputs: vec![f64::from(result[0]) / 4f64, f64::from(result[1]) / 4f64],
+                };
+                (entry, played(day, "A", "B", result))
+            })
+            .unzip()
+    }
+
+    fn settings() -> GbdtSettings {
+        GbdtSettings {
+            subsample: 1f64,
+            patience: None,
+            scale: 4f64,
+            ..GbdtSettings::default()
+        }
+    }
+
+    #[test]
+    fn splits_where_the_residuals_change_sign() {
+        let inputs: Vec<&[f64]> = vec![&[1f64], &[2f64], &[3f64], &[4f64]];
+        let split = Tree::split(&inputs, &[0, 1, 2, 3], &[-1f64, -1f64, 1f64, 1f64], 0, 1);
+        assert_eq!(split, Some((2.5, 4f64)));
+        // both leaves need at least min_leaf entries
+        assert_eq!(Tree::split(&inputs, &[0, 1, 2, 3], &[-1f64, 1f64, 1f64, 1f64], 0, 2), Some((2.5, 1f64)));
+    }
+
+    #[test]
+    fn learns_goals_and_outcomes_from_the_informative_input() {
+        let (entries, matches) = data();
+        let mut gbdt = Gbdt::new(settings());
+        gbdt.fit(&entries, &matches);
+        assert_eq!(gbdt.rounds(), [200, 200]);
+        let strong = &entries[0];
+        let goals = gbdt.predict_scores(strong, &matches[0]);
+        assert!((goals[0] - 2f64).abs() < 0.01 && goals[1] < 0.01);
+        assert!(gbdt.predict_outcome(strong, &matches[0])[0] > 0.9);
+        assert!(gbdt.predict_outcome(&entries[1], &matches[1])[2] > 0.9);
+        assert_eq!(gbdt.importance().0, vec![(0, 1f64)]);
+        assert_eq!(gbdt.importance().to_table().lines().count(), 2 + 1);
+    }
+
+    #[test]
+    fn early_stopping_keeps_the_best_rounds() {
+        let (mut entries, mut matches) = data();
+        // the validation matches (the last 20%) contradict the training matches
+        for (entry, m) in entries.iter_mut().zip(matches.iter_mut()).skip(32) {
+            entry.outputs.reverse();
+            m.result = m.result.map(|r| [r[1], r[0]]);
+        }
+        let mut gbdt = Gbdt::new(GbdtSettings {
+            patience: Some(5),
+            ..settings()
+        });
+        gbdt.fit(&entries, &matches);
+        assert_eq!(gbdt.rounds(), [0, 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "depth and min_leaf must be at least 1")]
+    fn trees_have_at_least_one_split() {
+        Gbdt::new(GbdtSettings {
+            depth: 0,
+            ..GbdtSettings::default()
+        });
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    classifiers::Standardizer,
+    models::{DataEntry, Match, Outcome},
+    Markdown, Predictor,
+};
+
+/**
+    A k-nearest-neighbour model. A match is compared to all training matches by the euclidean
+    distance of their standardized input features, the ```k``` closest ones decide:
+
+    * The outcome probabilities are the shares of home wins, draws and away wins of the neighbours.
+    * The predicted scores are the mean goals of the neighbours.
+
+    **Example**:
+
+    ```text
+    k = 5, neighbours: 2 : 1, 1 : 0, 1 : 1, 0 : 2, 3 : 1
+    probabilities: 0.6 / 0.2 / 0.2, predicted goals: 1.4 : 1.0
+    ```
+**/
+#[derive(Clone, Debug)]
+pub struct Knn {
+    pub k: usize,
+    // standardized inputs of the training matches with result
+    inputs: Vec<Vec<f64>>,
+    matches: Vec<Match>,
+    standardizer: Standardizer,
+}
+
+/// The most similar historical matches of a match and their distance, closest first.
+#[derive(Clone, Debug, Default)]
+pub struct Neighbours(pub Vec<(Match, f64)>);
+
+impl Default for Knn {
+    fn default() -> Self {
+        Knn::new(10)
+    }
+}
+
+impl Knn {
+    pub fn new(k: usize) -> Self {
+        if k == 0 {
+            panic!("k must be at least 1");
+        }
+        Knn {
+            k,
+            inputs: vec![],
+            matches: vec![],
+            standardizer: Standardizer::default(),
+        }
+    }
+
+    /**
+        Stores the entries with result, ```matches``` are the matches the entries were created from.
+        Previously stored entries are discarded.
+    **/
+    pub fn fit_entries(&mut self, entries: &[DataEntry], matches: &[Match]) {
+        let played: Vec<(&DataEntry, &Match)> = entries
+            .iter()
+            .zip(matches)
+            .filter(|(_, m)| m.result.is_some())
+            .collect();
+        let inputs: Vec<&[f64]> = played.iter().map(|(e, _)| e.inputs.as_slice()).collect();
+        self.standardizer = Standardizer::new(&inputs);
+        self.inputs = played.iter().map(|(e, _)| self.standardizer.apply(&e.inputs)).collect();
+        self.matches = played.into_iter().map(|(_, m)| m.clone()).collect();
+    }
+
+    /// Returns the ```k``` training matches closest to the entry.
+    pub fn neighbours(&self, entry: &DataEntry) -> Neighbours {
+        let x = self.standardizer.apply(&entry.inputs);
+        let mut distances: Vec<(usize, f64)> = self
+            .inputs
+            .iter()
+            .map(|inputs| inputs.iter().zip(&x).map(|(a, b)| (a - b).powi(2)).sum::<f64>().sqrt())
+            .enumerate()
+            .collect();
+        distances.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap().then(a.0.cmp(&b.0)));
+        Neighbours(
+            distances
+                .into_iter()
+                .take(self.k)
+                .map(|(i, d)| (self.matches[i].clone(), d))
+                .collect(),
+        )
+    }
+}
+
+/// Formats ```Neighbours``` into a Markdown Table, including header.
+impl Markdown for Neighbours {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Date|Home|Away|Result|Distance|\n");
+        s.push_str("|:-|:-|:-|:-:|-:|\n");
+        for (m, distance) in &self.0 {
+            let result = m.result.unwrap_or([0, 0]);
+            s.push_str(&format!(
+                "|{}|{}|{}|{} : {}|{:.3}|\n",
+                m.date.format("%Y-%m-%d"),
+                m.home,
+                m.away,
+                result[0],
+                result[1],
+                distance
+            ));
+        }
+        s
+    }
+}
+
+/// The input features of a match decide its neighbours, ```matches``` are only used for their results.
+impl Predictor for Knn {
+    fn fit(&mut self, training_set: &[DataEntry], matches: &[Match]) {
+        self.fit_entries(training_set, matches);
+    }
+
+    fn predict_scores(&self, entry: &DataEntry, _m: &Match) -> [f64; 2] {
+        let neighbours = self.neighbours(entry).0;
+        if neighbours.is_empty() {
+            return [0f64, 0f64];
+        }
+        let mut goals = [0f64; 2];
+        for (m, _) in &neighbours {
+            let result = m.result.unwrap();
+            goals[0] += f64::from(result[0]);
+            goals[1] += f64::from(result[1]);
+        }
+        let n = neighbours.len() as f64;
+        [goals[0] / n, goals[1] / n]
+    }
+
+    fn predict_outcome(&self, entry: &DataEntry, _m: &Match) -> [f64; 3] {
+        let neighbours = self.neighbours(entry).0;
+        if neighbours.is_empty() {
+            return [1f64 / 3f64; 3];
+        }
+        let mut probabilities = [0f64; 3];
+        for (m, _) in &neighbours {
+            probabilities[Outcome::from(m.result.unwrap()).index()] += 1f64 / neighbours.len() as f64;
+        }
+        probabilities
+    }
+
+    fn name(&self) -> String {
+        format!("{}-NN", self.k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::played;
+
+    fn entry(input: f64) -> DataEntry {
+        DataEntry {
+            inputs: vec![input],
+            outputs: vec![],
+        }
+    }
+
+    #[test]
+    fn the_closest_matches_decide() {
+        let results = [[2, 1], [1, 0], [1, 1], [0, 2], [3, 1], [0, 5]];
+        let matches: Vec<Match> = results
+            .iter()
+            .enumerate()
+            .map(|(day, r)| played(day as i64, "A", "B", *r))
+            .collect();
+        let entries: Vec<DataEntry> = [0f64, 1f64, 2f64, 3f64, 4f64, 10f64].iter().map(|x| entry(*x)).collect();
+        let mut knn = Knn::new(5);
+        knn.fit(&entries, &matches);
+        let p = knn.predict_outcome(&entry(2f64), &matches[0]);
+        assert!((p[0] - 0.6).abs() < 1e-12);
+        assert!((p[1] - 0.2).abs() < 1e-12);
+        assert!((p[2] - 0.2).abs() < 1e-12);
+        let goals = knn.predict_scores(&entry(2f64), &matches[0]);
+        assert!((goals[0] - 1.4).abs() < 1e-12);
+        assert!((goals[1] - 1f64).abs() < 1e-12);
+        let neighbours = knn.neighbours(&entry(2f64));
+        assert_eq!(neighbours.0[0].0.result, Some([1, 1]));
+        assert_eq!(neighbours.0[0].1, 0f64);
+        assert_eq!(neighbours.to_table().lines().count(), 2 + 5);
+        assert_eq!(knn.name(), "5-NN");
+    }
+
+    #[test]
+    fn without_training_matches_all_outcomes_are_even() {
+        let knn = Knn::default();
+        let m = played(0, "A", "B", [0, 0]);
+        assert_eq!(knn.predict_outcome(&entry(1f64), &m), [1f64 / 3f64; 3]);
+        assert_eq!(knn.predict_scores(&entry(1f64), &m), [0f64, 0f64]);
+    }
+
+    #[test]
+    #[should_panic(expected = "k must be at least 1")]
+    fn k_must_be_at_least_one() {
+        Knn::new(0);
+    }
+}
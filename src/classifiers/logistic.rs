@@ -0,0 +1,235 @@
+use crate::{
+    classifiers::Standardizer,
+    models::{DataEntry, Match, Outcome},
+    ratings::OutcomeFit,
+    Predictor,
+};
+
+/**
+    The settings of the logistic regression.
+
+    * ```l2```: The strength of the L2 penalty on the weights (not on the intercepts).
+    * ```rate```: The step size of the gradient descent.
+    * ```epochs```: The number of gradient descent steps over the whole training set.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LogisticSettings {
+    pub l2: f64,
+    pub rate: f64,
+    pub epochs: u32,
+}
+
+/**
+    A multinomial logistic regression that predicts the probabilities of a home win, a draw and
+    an away win from the input features of a match. The inputs are standardized to the training set.
+
+    ```text
+    z[outcome] = intercept[outcome] + weights[outcome] * inputs
+    P(outcome) = exp(z[outcome]) / (exp(z[home]) + exp(z[draw]) + exp(z[away]))
+    ```
+
+    The weights minimize the cross entropy of the training matches plus ```l2 / 2 * |weights|^2```.
+
+    **Note**:
+
+    * The expected goals are fitted linearly to ```P(home) + P(draw) / 2``` of the training matches,
+      as for the rating systems.
+**/
+#[derive(Clone, Debug)]
+pub struct LogisticRegression {
+    pub settings: LogisticSettings,
+    // one row per outcome, the intercept is the last column
+    weights: Vec<Vec<f64>>,
+    standardizer: Standardizer,
+    fit: OutcomeFit,
+}
+
+impl Default for LogisticSettings {
+    fn default() -> Self {
+        LogisticSettings {
+            l2: 0.1,
+            rate: 0.5,
+            epochs: 2000,
+        }
+    }
+}
+
+impl Default for LogisticRegression {
+    fn default() -> Self {
+        LogisticRegression::new(LogisticSettings::default())
+    }
+}
+
+impl LogisticRegression {
+    pub fn new(settings: LogisticSettings) -> Self {
+        if settings.l2 < 0f64 {
+            panic!("l2 must not be negative");
+        }
+        if settings.rate <= 0f64 {
+            panic!("the rate must be a positive number");
+        }
+        LogisticRegression {
+            settings,
+            weights: vec![],
+            standardizer: Standardizer::default(),
+            fit: OutcomeFit::default(),
+        }
+    }
+
+    /**
+        Fits the weights to the entries with result, ```matches``` are the matches the entries
+        were created from. Previously fitted weights are discarded.
+    **/
+    pub fn fit_entries(&mut self, entries: &[DataEntry], matches: &[Match]) {
+        let played: Vec<(&DataEntry, [u8; 2])> = entries
+            .iter()
+            .zip(matches)
+            .filter_map(|(e, m)| m.result.map(|r| (e, r)))
+            .collect();
+        let inputs: Vec<&[f64]> = played.iter().map(|(e, _)| e.inputs.as_slice()).collect();
+        self.standardizer = Standardizer::new(&inputs);
+        let size = inputs.first().map(|i| i.len()).unwrap_or(0);
+        self.weights = vec![vec![0f64; size + 1]; 3];
+        self.fit = OutcomeFit::default();
+        if played.is_empty() {
+            return;
+        }
+        let samples: Vec<(Vec<f64>, usize)> = played
+            .iter()
+            .map(|(e, r)| {
+                let mut x = self.standardizer.apply(&e.inputs);
+                x.push(1f64);
+                (x, Outcome::from(*r).index())
+            })
+            .collect();
+        let n = samples.len() as f64;
+        for _ in 0..self.settings.epochs {
+            let mut gradient = vec![vec![0f64; size + 1]; 3];
+            for (x, outcome) in &samples {
+                let p = self.softmax(x);
+                for (o, row) in gradient.iter_mut().enumerate() {
+                    let error = p[o] - if o == *outcome { 1f64 } else { 0f64 };
+                    for (g, xi) in row.iter_mut().zip(x) {
+                        *g += error * xi / n;
+                    }
+                }
+            }
+            for (weights, gradient) in self.weights.iter_mut().zip(&gradient) {
+                for (i, (w, g)) in weights.iter_mut().zip(gradient).enumerate() {
+                    let penalty = if i < size { self.settings.l2 * *w } else { 0f64 };
+                    *w -= self.settings.rate * (g + penalty);
+                }
+            }
+        }
+        for (e, r) in &played {
+            let p = self.probabilities(e);
+            self.fit.add(p[0] + p[1] / 2f64, *r);
+        }
+    }
+
+    /// Returns the probabilities of a home win, a draw and an away win.
+    pub fn probabilities(&self, entry: &DataEntry) -> [f64; 3] {
+        if self.weights.is_empty() {
+            return [1f64 / 3f64; 3];
+        }
+        let mut x = self.standardizer.apply(&entry.inputs);
+        x.push(1f64);
+        self.softmax(&x)
+    }
+
+    /// Returns the weights of the standardized inputs and the intercept (last) of each outcome.
+    pub fn weights(&self) -> &[Vec<f64>] {
+        &self.weights
+    }
+
+    // the outcome probabilities of standardized inputs, including the intercept input
+    fn softmax(&self, x: &[f64]) -> [f64; 3] {
+        let mut z = [0f64; 3];
+        for (z, weights) in z.iter_mut().zip(&self.weights) {
+            *z = weights.iter().zip(x).map(|(w, x)| w * x).sum();
+        }
+        let max = z.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let exp = [(z[0] - max).exp(), (z[1] - max).exp(), (z[2] - max).exp()];
+        let total: f64 = exp.iter().sum();
+        [exp[0] / total, exp[1] / total, exp[2] / total]
+    }
+}
+
+/**
+    The outcome probabilities come from the regression, the predicted scores are the expected
+    goals derived from them.
+**/
+impl Predictor for LogisticRegression {
+    fn fit(&mut self, training_set: &[DataEntry], matches: &[Match]) {
+        self.fit_entries(training_set, matches);
+    }
+
+    fn predict_scores(&self, entry: &DataEntry, _m: &Match) -> [f64; 2] {
+        let p = self.probabilities(entry);
+        self.fit.goals(p[0] + p[1] / 2f64)
+    }
+
+    fn predict_outcome(&self, entry: &DataEntry, _m: &Match) -> [f64; 3] {
+        self.probabilities(entry)
+    }
+
+    fn name(&self) -> String {
+        String::from("Logistic Regression")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::played;
+
+    fn entry(inputs: Vec<f64>) -> DataEntry {
+        DataEntry { inputs, outputs: vec![] }
+    }
+
+    #[test]
+    fn constant_inputs_predict_the_outcome_frequencies() {
+        let matches = vec![
+            played(0, "A", "B", [1, 0]),
+            played(1, "B", "A", [2, 0]),
+            played(2, "A", "B", [1, 1]),
+            played(3, "B", "A", [0, 1]),
+        ];
+        let entries = vec![entry(vec![1f64]); 4];
+        let mut model = LogisticRegression::default();
+        model.fit_entries(&entries, &matches);
+        let p = model.probabilities(&entry(vec![1f64]));
+        assert!((p[0] - 0.5).abs() < 1e-3);
+        assert!((p[1] - 0.25).abs() < 1e-3);
+        assert!((p[2] - 0.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn the_weights_follow_the_inputs() {
+        // the home team wins if the input is positive
+        let results = [[2, 0], [0, 2]];
+        let matches: Vec<Match> = (0..20).map(|day| played(day, "A", "B", results[day as usize % 2])).collect();
+        let entries: Vec<DataEntry> = (0..20).map(|day| entry(vec![1f64 - 2f64 * (day % 2) as f64])).collect();
+        let mut model = LogisticRegression::default();
+        model.fit(&entries, &matches);
+        assert!(model.weights()[0][0] > 0f64 && model.weights()[2][0] < 0f64);
+        let strong = model.predict_outcome(&entry(vec![1f64]), &matches[0]);
+        assert!(strong[0] > 0.8);
+        let scores = model.predict_scores(&entry(vec![1f64]), &matches[0]);
+        assert!(scores[0] > scores[1]);
+    }
+
+    #[test]
+    fn an_unfitted_model_predicts_even_outcomes() {
+        assert_eq!(LogisticRegression::default().probabilities(&entry(vec![1f64])), [1f64 / 3f64; 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "l2 must not be negative")]
+    fn l2_must_not_be_negative() {
+        LogisticRegression::new(LogisticSettings {
+            l2: -1f64,
+            ..LogisticSettings::default()
+        });
+    }
+}
@@ -0,0 +1,57 @@
+/// Gradient boosted regression and classification trees with early stopping.
+pub mod gbdt;
+/// k-nearest-neighbour outcome model that lists the most similar historical matches.
+pub mod knn;
+/// Multinomial logistic regression with L2 regularization.
+pub mod logistic;
+
+/**
+    Standardizes input features to zero mean and unit variance, fitted to a training set.
+    Features without variance are centered only.
+**/
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Standardizer {
+    mean: Vec<f64>,
+    deviation: Vec<f64>,
+}
+
+impl Standardizer {
+    pub(crate) fn new(inputs: &[&[f64]]) -> Self {
+        let n = inputs.len().max(1) as f64;
+        let size = inputs.first().map(|i| i.len()).unwrap_or(0);
+        let mean: Vec<f64> = (0..size)
+            .map(|f| inputs.iter().map(|i| i[f]).sum::<f64>() / n)
+            .collect();
+        let deviation = (0..size)
+            .map(|f| {
+                let variance = inputs.iter().map(|i| (i[f] - mean[f]).powi(2)).sum::<f64>() / n;
+                if variance > 0f64 {
+                    variance.sqrt()
+                } else {
+                    1f64
+                }
+            })
+            .collect();
+        Standardizer { mean, deviation }
+    }
+
+    pub(crate) fn apply(&self, inputs: &[f64]) -> Vec<f64> {
+        inputs
+            .iter()
+            .zip(self.mean.iter().zip(&self.deviation))
+            .map(|(x, (mean, deviation))| (x - mean) / deviation)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standardizes_to_the_training_inputs() {
+        let standardizer = Standardizer::new(&[&[1f64, 5f64], &[3f64, 5f64]]);
+        assert_eq!(standardizer.apply(&[1f64, 5f64]), vec![-1f64, 0f64]);
+        assert_eq!(standardizer.apply(&[4f64, 7f64]), vec![2f64, 2f64]);
+    }
+}
@@ -0,0 +1,399 @@
+/// Stacking, a meta-model trained on out-of-fold predictions of several base models.
+pub mod stacking;
+
+use crate::{
+    models::{DataEntry, Match, Outcome},
+    Markdown, Predictor,
+};
+
+/**
+    How an ```Ensemble``` combines the predictions of its members
+    (see ```docs/guide/ensemble_models.md```).
+
+    * ```Average```: The mean of the predicted goals and of the outcome probabilities.
+    * ```MajorityVote```: Each member votes for its most likely outcome. The probabilities are the
+      shares of the votes, the predicted goals the mean of the members that voted for the winning
+      outcome. A tie is decided by the summed probabilities of the members.
+    * ```WeightedAverage```: The weighted mean of the predicted goals and of the outcome probabilities.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Combination {
+    Average,
+    MajorityVote,
+    WeightedAverage,
+}
+
+/**
+    How the weights of a weighted average are derived. The members are fitted to the first
+    ```1 - validation``` share of the training set and rated on the rest, then fitted to all of it.
+
+    * ```Equal```: All members weigh the same, no validation.
+    * ```Error```: The inverse mean squared error of the predicted goals.
+    * ```Performance```: The share of correctly predicted winners (or draws).
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Weighting {
+    Equal,
+    Error,
+    Performance,
+}
+
+/**
+    Combines the predictions of several models.
+
+    **Example**:
+
+    ```text
+    let mut ensemble = Ensemble::new(vec![Box::new(Elo::default()), Box::new(Poisson::default())]);
+    ensemble.combination = Combination::WeightedAverage;
+    ensemble.weighting = Weighting::Error;
+    guru.train(&mut ensemble, &training_set, &training_matches);
+    println!("{}", ensemble.breakdown(&entry, &m).to_table());
+    ```
+**/
+pub struct Ensemble {
+    pub members: Vec<Box<dyn Predictor>>,
+    pub combination: Combination,
+    pub weighting: Weighting,
+    /// The share of the training set the weights are derived from.
+    pub validation: f64,
+    weights: Vec<f64>,
+}
+
+/// The predictions of each member of an ensemble for a match, and the combined prediction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Breakdown {
+    pub combination: Combination,
+    /// Name, weight, predicted goals and outcome probabilities of each member.
+    pub members: Vec<(String, f64, [f64; 2], [f64; 3])>,
+    pub goals: [f64; 2],
+    pub probabilities: [f64; 3],
+}
+
+// the index of the most likely outcome, the first one for ties
+fn most_likely(probabilities: [f64; 3]) -> usize {
+    (0..3).fold(0, |best, o| if probabilities[o] > probabilities[best] { o } else { best })
+}
+
+impl Ensemble {
+    pub fn new(members: Vec<Box<dyn Predictor>>) -> Self {
+        if members.is_empty() {
+            panic!("an ensemble needs at least one member");
+        }
+        let weights = vec![1f64 / members.len() as f64; members.len()];
+        Ensemble {
+            members,
+            combination: Combination::Average,
+            weighting: Weighting::Equal,
+            validation: 0.2,
+            weights,
+        }
+    }
+
+    /**
+        Returns the weight of each member, adding up to 1. Only ```Combination::WeightedAverage```
+        uses them, the other combinations weigh all members the same.
+    **/
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    /// Sets the weights of the members, they are normalized to add up to 1.
+    pub fn set_weights(&mut self, weights: Vec<f64>) {
+        if weights.len() != self.members.len() || weights.iter().any(|w| *w < 0f64) {
+            panic!("one non negative weight per member is required");
+        }
+        let total: f64 = weights.iter().sum();
+        if total <= 0f64 {
+            panic!("at least one weight must be positive");
+        }
+        self.weights = weights.iter().map(|w| w / total).collect();
+    }
+
+    /// Returns the predictions of each member and the combined prediction for a match.
+    pub fn breakdown(&self, entry: &DataEntry, m: &Match) -> Breakdown {
+        let weights = self.effective_weights();
+        let members: Vec<(String, f64, [f64; 2], [f64; 3])> = self
+            .members
+            .iter()
+            .zip(&weights)
+            .map(|(member, w)| (member.name(), *w, member.predict_scores(entry, m), member.predict_outcome(entry, m)))
+            .collect();
+        let (goals, probabilities) = match self.combination {
+            Combination::MajorityVote => {
+                let mut votes = [0f64; 3];
+                let mut summed = [0f64; 3];
+                for (_, _, _, p) in &members {
+                    votes[most_likely(*p)] += 1f64 / members.len() as f64;
+                    for (s, p) in summed.iter_mut().zip(p) {
+                        *s += p;
+                    }
+                }
+                let max = votes.iter().cloned().fold(0f64, f64::max);
+                let winner = most_likely([
+                    if votes[0] == max { summed[0] } else { -1f64 },
+                    if votes[1] == max { summed[1] } else { -1f64 },
+                    if votes[2] == max { summed[2] } else { -1f64 },
+                ]);
+                let voters: Vec<[f64; 2]> = members
+                    .iter()
+                    .filter(|(_, _, _, p)| most_likely(*p) == winner)
+                    .map(|(_, _, g, _)| *g)
+                    .collect();
+                let n = voters.len() as f64;
+                let goals = [
+                    voters.iter().map(|g| g[0]).sum::<f64>() / n,
+                    voters.iter().map(|g| g[1]).sum::<f64>() / n,
+                ];
+                (goals, votes)
+            }
+            _ => {
+                let mut goals = [0f64; 2];
+                let mut probabilities = [0f64; 3];
+                for (_, w, g, p) in &members {
+                    for (sum, g) in goals.iter_mut().zip(g) {
+                        *sum += w * g;
+                    }
+                    for (sum, p) in probabilities.iter_mut().zip(p) {
+                        *sum += w * p;
+                    }
+                }
+                (goals, probabilities)
+            }
+        };
+        Breakdown {
+            combination: self.combination,
+            members,
+            goals,
+            probabilities,
+        }
+    }
+
+    // the weights of the combination
+    fn effective_weights(&self) -> Vec<f64> {
+        match self.combination {
+            Combination::WeightedAverage => self.weights.clone(),
+            _ => vec![1f64 / self.members.len() as f64; self.members.len()],
+        }
+    }
+
+    // rates each member on the entries, the higher the better
+    fn rate(&self, entries: &[DataEntry], matches: &[Match]) -> Vec<f64> {
+        let played: Vec<(&DataEntry, &Match, [u8; 2])> = entries
+            .iter()
+            .zip(matches)
+            .filter_map(|(e, m)| m.result.map(|r| (e, m, r)))
+            .collect();
+        self.members
+            .iter()
+            .map(|member| {
+                let n = played.len().max(1) as f64;
+                match self.weighting {
+                    Weighting::Equal => 1f64,
+                    Weighting::Error => {
+                        let error: f64 = played
+                            .iter()
+                            .map(|(e, m, r)| {
+                                let g = member.predict_scores(e, m);
+                                (g[0] - f64::from(r[0])).powi(2) + (g[1] - f64::from(r[1])).powi(2)
+                            })
+                            .sum();
+                        1f64 / (error / n).max(1e-6)
+                    }
+                    Weighting::Performance => {
+                        let correct = played
+                            .iter()
+                            .filter(|(e, m, r)| {
+                                let g = member.predict_scores(e, m);
+                                Outcome::from([g[0] as u8, g[1] as u8]) == Outcome::from(*r)
+                            })
+                            .count();
+                        // a member without a correct prediction keeps a small weight
+                        (correct as f64 / n).max(1e-3)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/**
+    Fits all members. The weights are derived from the validation share of the training set,
+    unless the weighting is ```Weighting::Equal```.
+**/
+impl Predictor for Ensemble {
+    fn fit(&mut self, training_set: &[DataEntry], matches: &[Match]) {
+        if self.weighting != Weighting::Equal {
+            let split = ((training_set.len() as f64) * (1f64 - self.validation)).round() as usize;
+            if split > 0 && split < training_set.len() {
+                for member in self.members.iter_mut() {
+                    member.fit(&training_set[..split], &matches[..split]);
+                }
+                let weights = self.rate(&training_set[split..], &matches[split..]);
+                self.set_weights(weights);
+            }
+        }
+        for member in self.members.iter_mut() {
+            member.fit(training_set, matches);
+        }
+    }
+
+    fn predict_scores(&self, entry: &DataEntry, m: &Match) -> [f64; 2] {
+        self.breakdown(entry, m).goals
+    }
+
+    fn predict_outcome(&self, entry: &DataEntry, m: &Match) -> [f64; 3] {
+        self.breakdown(entry, m).probabilities
+    }
+
+    fn name(&self) -> String {
+        let combination = match self.combination {
+            Combination::Average => "Average",
+            Combination::MajorityVote => "Majority Vote",
+            Combination::WeightedAverage => "Weighted Average",
+        };
+        format!("Ensemble ({})", combination)
+    }
+}
+
+/// Formats ```Breakdown``` into a Markdown Table, including header. The last row is the combined prediction.
+impl Markdown for Breakdown {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Model|Weighted Result|Weight|Predicted Result|Home|Draw|Away|\n");
+        s.push_str("|:-|:-|-:|:-|-:|-:|-:|\n");
+        for (name, weight, goals, p) in &self.members {
+            s.push_str(&format!(
+                "|{}|[{:.1}, {:.1}]|{:.2}|[{:.1}, {:.1}]|{:.2}|{:.2}|{:.2}|\n",
+                name,
+                weight * goals[0],
+                weight * goals[1],
+                weight,
+                goals[0],
+                goals[1],
+                p[0],
+                p[1],
+                p[2]
+            ));
+        }
+        let combined = match self.combination {
+            Combination::Average => "Average",
+            Combination::MajorityVote => "Majority Vote",
+            Combination::WeightedAverage => "Weighted Avg.",
+        };
+        s.push_str(&format!(
+            "|{}|[{:.1}, {:.1}]|1.00||{:.2}|{:.2}|{:.2}|\n",
+            combined, self.goals[0], self.goals[1], self.probabilities[0], self.probabilities[1], self.probabilities[2]
+        ));
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{entry, played};
+
+    // predicts the same goals and outcome for every match
+    struct Fixed([f64; 2], [f64; 3]);
+
+    impl Predictor for Fixed {
+        fn fit(&mut self, _training_set: &[DataEntry], _matches: &[Match]) {}
+
+        fn predict_scores(&self, _entry: &DataEntry, _m: &Match) -> [f64; 2] {
+            self.0
+        }
+
+        fn predict_outcome(&self, _entry: &DataEntry, _m: &Match) -> [f64; 3] {
+            self.1
+        }
+
+        fn name(&self) -> String {
+            format!("Fixed {} : {}", self.0[0], self.0[1])
+        }
+    }
+
+    fn ensemble(members: Vec<Fixed>) -> Ensemble {
+        Ensemble::new(members.into_iter().map(|m| Box::new(m) as Box<dyn Predictor>).collect())
+    }
+
+    #[test]
+    fn the_average_is_the_mean_of_all_members() {
+        let ensemble = ensemble(vec![
+            Fixed([2f64, 0f64], [0.6, 0.3, 0.1]),
+            Fixed([1f64, 1f64], [0.2, 0.4, 0.4]),
+        ]);
+        let breakdown = ensemble.breakdown(&entry(), &played(0, "A", "B", [0, 0]));
+        assert_eq!(breakdown.goals, [1.5, 0.5]);
+        assert!((breakdown.probabilities[0] - 0.4).abs() < 1e-12);
+        assert!((breakdown.probabilities[1] - 0.35).abs() < 1e-12);
+        assert_eq!(breakdown.members.len(), 2);
+        assert_eq!(breakdown.to_table().lines().count(), 2 + 2 + 1);
+    }
+
+    #[test]
+    fn the_majority_vote_counts_the_most_likely_outcomes() {
+        let mut ensemble = ensemble(vec![
+            Fixed([2f64, 0f64], [0.6, 0.3, 0.1]),
+            Fixed([1f64, 0f64], [0.5, 0.3, 0.2]),
+            Fixed([0f64, 1f64], [0.2, 0.3, 0.5]),
+        ]);
+        ensemble.combination = Combination::MajorityVote;
+        let m = played(0, "A", "B", [0, 0]);
+        assert_eq!(ensemble.predict_outcome(&entry(), &m), [2f64 / 3f64, 0f64, 1f64 / 3f64]);
+        assert_eq!(ensemble.predict_scores(&entry(), &m), [1.5, 0f64]);
+    }
+
+    #[test]
+    fn a_tied_vote_is_decided_by_the_summed_probabilities() {
+        let mut ensemble = ensemble(vec![
+            Fixed([1f64, 0f64], [0.5, 0.3, 0.2]),
+            Fixed([0f64, 2f64], [0.1, 0.3, 0.6]),
+        ]);
+        ensemble.combination = Combination::MajorityVote;
+        assert_eq!(ensemble.predict_scores(&entry(), &played(0, "A", "B", [0, 0])), [0f64, 2f64]);
+    }
+
+    #[test]
+    fn weights_are_normalized_and_only_used_by_the_weighted_average() {
+        let mut ensemble = ensemble(vec![
+            Fixed([2f64, 0f64], [1f64, 0f64, 0f64]),
+            Fixed([0f64, 2f64], [0f64, 0f64, 1f64]),
+        ]);
+        ensemble.set_weights(vec![3f64, 1f64]);
+        assert_eq!(ensemble.weights(), &[0.75, 0.25]);
+        let m = played(0, "A", "B", [0, 0]);
+        assert_eq!(ensemble.predict_scores(&entry(), &m), [1f64, 1f64]);
+        ensemble.combination = Combination::WeightedAverage;
+        assert_eq!(ensemble.predict_scores(&entry(), &m), [1.5, 0.5]);
+        assert_eq!(ensemble.predict_outcome(&entry(), &m), [0.75, 0f64, 0.25]);
+    }
+
+    #[test]
+    fn error_weights_favour_the_accurate_member() {
+        let mut ensemble = ensemble(vec![
+            Fixed([2f64, 1f64], [0.6, 0.3, 0.1]),
+            Fixed([0f64, 2f64], [0.1, 0.3, 0.6]),
+        ]);
+        ensemble.combination = Combination::WeightedAverage;
+        ensemble.weighting = Weighting::Error;
+        let matches: Vec<Match> = (0..10).map(|day| played(day, "A", "B", [2, 1])).collect();
+        ensemble.fit(&vec![entry(); 10], &matches);
+        assert!(ensemble.weights()[0] > 0.99);
+        ensemble.weighting = Weighting::Performance;
+        ensemble.fit(&vec![entry(); 10], &matches);
+        assert!((ensemble.weights()[0] - 1f64 / 1.001).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "one non negative weight per member")]
+    fn every_member_needs_a_weight() {
+        ensemble(vec![Fixed([1f64, 0f64], [1f64, 0f64, 0f64])]).set_weights(vec![0.5, 0.5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one member")]
+    fn an_ensemble_needs_members() {
+        Ensemble::new(vec![]);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{
+    classifiers::logistic::{LogisticRegression, LogisticSettings},
+    models::{DataEntry, Match, Outcome},
+    ratings::solve,
+    Markdown, Predictor,
+};
+
+/// Creates a new, unfitted base model.
+pub type BaseModel<'a> = Box<dyn Fn() -> Box<dyn Predictor> + 'a>;
+
+// the L2 penalty of the goal regressions of the meta-model
+const RIDGE: f64 = 1f64;
+
+/**
+    Stacking (Wolpert, 1992): a meta-model learns how to combine the predictions of several
+    base models. The predicted goals and outcome probabilities of all base models are the inputs
+    of the meta-model:
+
+    * A multinomial logistic regression predicts the outcome probabilities.
+    * A ridge regression per team predicts the goals.
+
+    The meta-model is trained on out-of-fold predictions: the training matches are split into
+    ```folds``` blocks in chronological order, each block but the first is predicted by new base
+    models fitted to the earlier blocks only. No base model sees the matches it predicts for the
+    meta-model or any later match, as when it predicts new matches. Finally the base models are
+    fitted to the whole training set to predict new matches.
+
+    **Example**:
+
+    ```text
+    let bases: Vec<BaseModel> = vec![Box::new(|| Box::new(Elo::default())), Box::new(|| Box::new(Poisson::default()))];
+    let mut stacking = Stacking::new(bases, 5);
+    guru.train(&mut stacking, &training_set, &matches);
+    println!("{}", stacking.out_of_fold().to_table());
+    ```
+**/
+pub struct Stacking<'a> {
+    pub folds: usize,
+    bases: Vec<BaseModel<'a>>,
+    fitted: Vec<Box<dyn Predictor>>,
+    outcome: LogisticRegression,
+    // coefficients of the home and the away goals, the intercept is the last
+    goals: [Vec<f64>; 2],
+    out_of_fold: OutOfFold,
+}
+
+/// The exact result and winner rates (in percent) of the out-of-fold predictions of each base model.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OutOfFold(pub Vec<(String, [f64; 2])>);
+
+impl<'a> Stacking<'a> {
+    pub fn new(bases: Vec<BaseModel<'a>>, folds: usize) -> Self {
+        if bases.is_empty() {
+            panic!("stacking needs at least one base model");
+        }
+        if folds < 2 {
+            panic!("stacking needs at least two folds");
+        }
+        Stacking {
+            folds,
+            bases,
+            fitted: vec![],
+            outcome: LogisticRegression::new(LogisticSettings::default()),
+            goals: [vec![], vec![]],
+            out_of_fold: OutOfFold::default(),
+        }
+    }
+
+    /// Returns the rates of the out-of-fold predictions of each base model.
+    pub fn out_of_fold(&self) -> &OutOfFold {
+        &self.out_of_fold
+    }
+
+    // the inputs of the meta-model: the goals and probabilities of each base model
+    fn meta_inputs(models: &[Box<dyn Predictor>], entry: &DataEntry, m: &Match) -> Vec<f64> {
+        models
+            .iter()
+            .flat_map(|model| {
+                let goals = model.predict_scores(entry, m);
+                let p = model.predict_outcome(entry, m);
+                vec![goals[0], goals[1], p[0], p[1], p[2]]
+            })
+            .collect()
+    }
+
+    // the predicted goals of the meta-model
+    fn meta_goals(&self, inputs: &[f64]) -> [f64; 2] {
+        let predict = |coefficients: &[f64]| {
+            if coefficients.is_empty() {
+                return 0f64;
+            }
+            let (weights, intercept) = coefficients.split_at(coefficients.len() - 1);
+            (weights.iter().zip(inputs).map(|(w, x)| w * x).sum::<f64>() + intercept[0]).max(0f64)
+        };
+        [predict(&self.goals[0]), predict(&self.goals[1])]
+    }
+}
+
+// least squares with an L2 penalty on the weights (not on the intercept, the last coefficient)
+fn ridge(inputs: &[Vec<f64>], targets: &[f64]) -> Vec<f64> {
+    let size = inputs.first().map(|i| i.len()).unwrap_or(0) + 1;
+    let mut a = vec![vec![0f64; size]; size];
+    let mut b = vec![0f64; size];
+    for (x, y) in inputs.iter().zip(targets) {
+        let x: Vec<f64> = x.iter().cloned().chain(std::iter::once(1f64)).collect();
+        for i in 0..size {
+            for j in 0..size {
+                a[i][j] += x[i] * x[j];
+            }
+            b[i] += x[i] * y;
+        }
+    }
+    for (i, row) in a.iter_mut().enumerate().take(size - 1) {
+        row[i] += RIDGE;
+    }
+    solve(a, b)
+}
+
+/**
+    Fits the meta-model to out-of-fold predictions of the base models, then the base models to the
+    whole training set. Only entries with result are used.
+**/
+impl Predictor for Stacking<'_> {
+    fn fit(&mut self, training_set: &[DataEntry], matches: &[Match]) {
+        let played: Vec<(&DataEntry, &Match)> = training_set
+            .iter()
+            .zip(matches)
+            .filter(|(_, m)| m.result.is_some())
+            .collect();
+        let folds = self.folds.min(played.len()).max(1);
+        let mut meta: Vec<Option<Vec<f64>>> = vec![None; played.len()];
+        let mut correct = vec![[0usize; 2]; self.bases.len()];
+        let mut order: Vec<usize> = (0..played.len()).collect();
+        order.sort_by_key(|&i| played[i].1.date);
+        // the first block has no earlier matches to fit the base models to
+        for fold in 1..folds {
+            let (start, end) = (fold * order.len() / folds, (fold + 1) * order.len() / folds);
+            let (rest, held_out) = (&order[..start], &order[start..end]);
+            if rest.is_empty() {
+                continue;
+            }
+            let entries: Vec<DataEntry> = rest.iter().map(|&i| played[i].0.clone()).collect();
+            let fold_matches: Vec<Match> = rest.iter().map(|&i| played[i].1.clone()).collect();
+            let models: Vec<Box<dyn Predictor>> = self
+                .bases
+                .iter()
+                .map(|base| {
+                    let mut model = base();
+                    model.fit(&entries, &fold_matches);
+                    model
+                })
+                .collect();
+            for &i in held_out {
+                let (entry, m) = played[i];
+                let inputs = Stacking::meta_inputs(&models, entry, m);
+                let result = m.result.unwrap();
+                for (b, counts) in correct.iter_mut().enumerate() {
+                    let predicted = [inputs[b * 5] as u8, inputs[b * 5 + 1] as u8];
+                    counts[0] += (predicted == result) as usize;
+                    counts[1] += (Outcome::from(predicted) == Outcome::from(result)) as usize;
+                }
+                meta[i] = Some(inputs);
+            }
+        }
+        let (meta_entries, meta_matches): (Vec<DataEntry>, Vec<Match>) = meta
+            .into_iter()
+            .zip(&played)
+            .filter_map(|(inputs, (_, m))| inputs.map(|inputs| (DataEntry { inputs, outputs: vec![] }, (*m).clone())))
+            .unzip();
+        self.outcome.fit_entries(&meta_entries, &meta_matches);
+        let inputs: Vec<Vec<f64>> = meta_entries.iter().map(|e| e.inputs.clone()).collect();
+        for (team, coefficients) in self.goals.iter_mut().enumerate() {
+            let targets: Vec<f64> = meta_matches.iter().map(|m| f64::from(m.result.unwrap()[team])).collect();
+            *coefficients = ridge(&inputs, &targets);
+        }
+        self.fitted = self
+            .bases
+            .iter()
+            .map(|base| {
+                let mut model = base();
+                model.fit(training_set, matches);
+                model
+            })
+            .collect();
+        let n = meta_entries.len().max(1) as f64;
+        self.out_of_fold = OutOfFold(
+            self.fitted
+                .iter()
+                .zip(&correct)
+                .map(|(model, c)| (model.name(), [c[0] as f64 * 100f64 / n, c[1] as f64 * 100f64 / n]))
+                .collect(),
+        );
+    }
+
+    fn predict_scores(&self, entry: &DataEntry, m: &Match) -> [f64; 2] {
+        self.meta_goals(&Stacking::meta_inputs(&self.fitted, entry, m))
+    }
+
+    fn predict_outcome(&self, entry: &DataEntry, m: &Match) -> [f64; 3] {
+        let inputs = Stacking::meta_inputs(&self.fitted, entry, m);
+        self.outcome.probabilities(&DataEntry { inputs, outputs: vec![] })
+    }
+
+    fn name(&self) -> String {
+        let names: Vec<String> = self.fitted.iter().map(|model| model.name()).collect();
+        format!("Stacking ({})", names.join(", "))
+    }
+}
+
+/// Formats ```OutOfFold``` into a Markdown Table, including header.
+impl Markdown for OutOfFold {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Base Model|Result|Winner|\n");
+        s.push_str("|:-|-:|-:|\n");
+        for (name, rates) in &self.0 {
+            s.push_str(&format!("|{}|{:.1}%|{:.1}%|\n", name, rates[0], rates[1]));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        baselines::AlwaysHome,
+        testing::{entry, played, Chronological},
+    };
+
+    #[test]
+    fn ridge_without_variance_fits_the_mean() {
+        assert_eq!(ridge(&[vec![0f64], vec![0f64]], &[1f64, 3f64]), vec![0f64, 2f64]);
+    }
+
+    #[test]
+    fn folds_only_predict_later_matches() {
+        // the matches are not in chronological order
+        let mut matches: Vec<Match> = (0..10).map(|day| played(day, "A", "B", [1, 0])).collect();
+        matches.reverse();
+        let bases: Vec<BaseModel> = vec![Box::new(|| Box::new(Chronological::default()))];
+        let mut stacking = Stacking::new(bases, 5);
+        stacking.fit(&vec![entry(); 10], &matches);
+        assert_eq!(stacking.out_of_fold().0, vec![(String::from("Chronological"), [100f64, 100f64])]);
+        let goals = stacking.predict_scores(&entry(), &played(10, "A", "B", [0, 0]));
+        assert!(goals[0] > 0.5 && goals[1] < 0.5);
+    }
+
+    #[test]
+    fn out_of_fold_rates_leave_out_the_first_block() {
+        let matches = vec![
+            played(0, "A", "B", [0, 1]),
+            played(1, "A", "B", [0, 1]),
+            played(2, "A", "B", [1, 0]),
+            played(3, "A", "B", [2, 0]),
+        ];
+        let bases: Vec<BaseModel> = vec![Box::new(|| Box::new(AlwaysHome))];
+        let mut stacking = Stacking::new(bases, 2);
+        stacking.fit(&vec![entry(); 4], &matches);
+        assert_eq!(stacking.out_of_fold().0[0].1, [50f64, 100f64]);
+        assert_eq!(stacking.out_of_fold().to_table().lines().count(), 2 + 1);
+        assert_eq!(stacking.name(), "Stacking (Always Home Win)");
+    }
+
+    #[test]
+    #[should_panic(expected = "at least two folds")]
+    fn stacking_needs_two_folds() {
+        Stacking::new(vec![Box::new(|| Box::new(AlwaysHome))], 1);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{
+    ensemble::stacking::BaseModel,
+    evaluation::metrics::{brier, Scores},
+    models::{DataEntry, Match},
+    Markdown, Predictions, Testing, Training,
+};
+use chrono::{Duration, NaiveDate};
+
+/**
+    A walk-forward backtest. The matches are stepped through in chronological order, a step
+    covers the fixtures of ```window``` days from the next unrevealed match day on:
+
+    1. A new model is fitted to all results before the step.
+    2. The fixtures of the step are predicted.
+    3. Their results are revealed and join the training matches of the next step.
+
+    No prediction is informed by a later result. The first ```min_training``` matches are only
+    used for training.
+
+    **Example**:
+
+    ```text
+    let backtest = Backtest { window: 7, min_training: 10 };
+    let factory: BaseModel = Box::new(|| Box::new(Elo::default()));
+    let report = backtest.run(&guru, &factory, &entries, &matches);
+    println!("{}", report.to_table());
+    ```
+
+    **Note**:
+
+    * The input features of the entries must only use results before the date of their match,
+      as the ```DefaultInputGenerator``` does. The outputs (and the scale of a network) must not
+      depend on later results either, ```guru backtest``` divides the goals by a fixed 10.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Backtest {
+    pub window: i64,
+    pub min_training: usize,
+}
+
+/**
+    A step of a backtest: the fixtures from ```from``` to ```until``` (inclusive), predicted by a
+    model fitted to ```trained``` earlier results.
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub from: NaiveDate,
+    pub until: NaiveDate,
+    pub trained: usize,
+    pub tested: usize,
+    pub results: usize,
+    pub winners: usize,
+    pub brier: f64,
+}
+
+/// The steps of a backtest and all predictions of their fixtures, in chronological order.
+#[derive(Debug)]
+pub struct BacktestReport {
+    pub steps: Vec<Step>,
+    pub predictions: Predictions,
+}
+
+impl Default for Backtest {
+    fn default() -> Self {
+        Backtest {
+            window: 1,
+            min_training: 10,
+        }
+    }
+}
+
+impl Backtest {
+    /**
+        Runs the backtest, ```model``` creates a new, unfitted model for each step. ```entries```
+        and ```matches``` must be sorted by date and have the same order, all matches must have
+        a result.
+    **/
+    pub fn run<T: Testing + Training>(
+        &self,
+        tester: &T,
+        model: &BaseModel,
+        entries: &[DataEntry],
+        matches: &[Match],
+    ) -> BacktestReport {
+        if self.window < 1 {
+            panic!("a backtest step must cover at least one day");
+        }
+        let mut steps = vec![];
+        let mut predictions = Predictions(vec![]);
+        let mut start = 0;
+        while start < matches.len() {
+            let from = matches[start].date.naive_local().date();
+            let until = from + Duration::days(self.window - 1);
+            let end = start
+                + matches[start..]
+                    .iter()
+                    .take_while(|m| m.date.naive_local().date() <= until)
+                    .count();
+            if start >= self.min_training.max(1) {
+                let mut fitted = model();
+                tester.train(fitted.as_mut(), &entries[..start], &matches[..start]);
+                let (stats, step) = tester.test(fitted.as_ref(), &entries[start..end], &matches[start..end]);
+                let brier: f64 = step
+                    .iter()
+                    .map(|p| brier(p.probabilities(), p.expected_scores()))
+                    .sum();
+                steps.push(Step {
+                    from,
+                    until,
+                    trained: start,
+                    tested: end - start,
+                    results: stats[0].positive,
+                    winners: stats[1].positive,
+                    brier: brier / (end - start) as f64,
+                });
+                predictions.0.extend(step.0);
+            }
+            start = end;
+        }
+        BacktestReport { steps, predictions }
+    }
+}
+
+impl BacktestReport {
+    /// Returns the scores of all predictions of the backtest.
+    pub fn scores(&self) -> Scores {
+        Scores::from(&self.predictions)
+    }
+}
+
+/**
+    Formats ```BacktestReport``` into a Markdown Table, including header. The last column is the
+    winner rate of all steps so far.
+**/
+impl Markdown for BacktestReport {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|From|Until|Trained|Tested|Result|Winner|Brier|Winner to Date|\n");
+        s.push_str("|:-|:-|-:|-:|-:|-:|-:|-:|\n");
+        let (mut tested, mut winners) = (0, 0);
+        for step in &self.steps {
+            tested += step.tested;
+            winners += step.winners;
+            s.push_str(&format!(
+                "|{}|{}|{}|{}|{}|{}|{:.4}|{:.1}%|\n",
+                step.from,
+                step.until,
+                step.trained,
+                step.tested,
+                step.results,
+                step.winners,
+                step.brier,
+                winners as f64 * 100f64 / tested as f64
+            ));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{entry, played, Chronological},
+        Guru,
+    };
+
+    #[test]
+    fn steps_only_predict_later_matches() {
+        let matches: Vec<Match> = (0..10).map(|day| played(day, "A", "B", [1, 0])).collect();
+        let entries = vec![entry(); 10];
+        let guru = Guru::new(&matches);
+        let factory: BaseModel = Box::new(|| Box::new(Chronological::default()));
+        let backtest = Backtest {
+            window: 3,
+            min_training: 4,
+        };
+        let report = backtest.run(&guru, &factory, &entries, &matches);
+        // the steps start with the days 0, 3, 6 and 9, the first two have too few training matches
+        let steps: Vec<(usize, usize)> = report.steps.iter().map(|s| (s.trained, s.tested)).collect();
+        assert_eq!(steps, vec![(6, 3), (9, 1)]);
+        assert_eq!(report.steps[0].until - report.steps[0].from, Duration::days(2));
+        assert_eq!((report.steps[0].results, report.steps[0].winners), (3, 3));
+        assert_eq!(report.scores().tested, 4);
+        assert_eq!(report.to_table().lines().count(), 2 + 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one day")]
+    fn a_step_covers_at_least_one_day() {
+        let factory: BaseModel = Box::new(|| Box::new(Chronological::default()));
+        let backtest = Backtest {
+            window: 0,
+            min_training: 1,
+        };
+        backtest.run(&Guru::new(&[]), &factory, &[], &[]);
+    }
+}
@@ -0,0 +1,543 @@
+use crate::{
+    models::{DataEntry, Match, Outcome},
+    Markdown, Predictions, Predictor,
+};
+use serde::{Deserialize, Serialize};
+
+// probabilities are clamped to this value before their logit or logarithm is taken
+const EPSILON: f64 = 1e-6;
+
+/// A forecast of a match with result: the home win, draw and away win probabilities and the result.
+pub type Forecast = ([f64; 3], [u8; 2]);
+
+/// Returns the forecasts of the predictions, all of them must be predictions of matches with result.
+pub fn forecasts(predictions: &Predictions) -> Vec<Forecast> {
+    predictions.iter().map(|p| (p.probabilities(), p.expected_scores())).collect()
+}
+
+/**
+    A bin of a reliability diagram: the forecasts whose probability lies in ```[lower, upper)```.
+
+    * ```predicted```: The mean probability of the forecasts in the bin.
+    * ```observed```: The share of the forecasts in the bin that came true.
+**/
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Bin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+    pub predicted: f64,
+    pub observed: f64,
+}
+
+/**
+    A reliability diagram, the forecasts binned by probability. A model is calibrated if the
+    observed frequency of each bin is close to its mean probability: of all home wins predicted
+    with 70%, about 70% should end in a home win.
+
+    **Example**:
+
+    ```text
+    let reliability = Reliability::new(&forecasts(&predictions), None, 10);
+    println!("{}", reliability.to_table());
+    println!("ECE: {:.4}", reliability.ece());
+    ```
+**/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Reliability(pub Vec<Bin>);
+
+impl Reliability {
+    /**
+        Bins the probabilities of the outcome, or of all three outcomes for ```None```, into
+        ```bins``` bins of equal width.
+    **/
+    pub fn new(forecasts: &[Forecast], outcome: Option<Outcome>, bins: usize) -> Self {
+        if bins == 0 {
+            panic!("a reliability diagram needs at least one bin");
+        }
+        let width = 1f64 / bins as f64;
+        let mut diagram: Vec<Bin> = (0..bins)
+            .map(|b| Bin {
+                lower: b as f64 * width,
+                upper: (b + 1) as f64 * width,
+                ..Bin::default()
+            })
+            .collect();
+        for (probabilities, result) in forecasts {
+            let actual = Outcome::from(*result).index();
+            let indices = match outcome {
+                Some(outcome) => vec![outcome.index()],
+                None => vec![0, 1, 2],
+            };
+            for i in indices {
+                let bin = &mut diagram[((probabilities[i] / width) as usize).min(bins - 1)];
+                bin.count += 1;
+                bin.predicted += probabilities[i];
+                bin.observed += (i == actual) as usize as f64;
+            }
+        }
+        for bin in diagram.iter_mut().filter(|b| b.count > 0) {
+            bin.predicted /= bin.count as f64;
+            bin.observed /= bin.count as f64;
+        }
+        Reliability(diagram)
+    }
+
+    /// Returns the expected calibration error, the mean gap between probability and frequency weighted by the bin sizes.
+    pub fn ece(&self) -> f64 {
+        let total: usize = self.0.iter().map(|b| b.count).sum();
+        if total == 0 {
+            return 0f64;
+        }
+        self.0
+            .iter()
+            .map(|b| b.count as f64 * (b.predicted - b.observed).abs())
+            .sum::<f64>()
+            / total as f64
+    }
+}
+
+/**
+    The methods to calibrate the outcome probabilities of a model. After each method the three
+    probabilities are normalized to add up to 1.
+
+    * ```Platt```: Platt scaling, a logistic regression on the logit of each probability
+      (one outcome against the others).
+    * ```Isotonic```: An isotonic regression of each probability, the pool adjacent violators
+      algorithm fits a non decreasing step function that is interpolated linearly. Forecasts with
+      the same probability share a step.
+    * ```Temperature```: All logarithms of the probabilities are divided by a single temperature.
+      A temperature above 1 softens overconfident forecasts, below 1 sharpens them.
+**/
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Method {
+    Platt,
+    Isotonic,
+    Temperature,
+}
+
+/**
+    A calibration map fitted to held-out forecasts of a model.
+
+    **Example**:
+
+    ```text
+    let calibrator = Calibrator::fit(Method::Temperature, &forecasts(&held_out));
+    let calibrated = calibrator.apply(model.predict_outcome(&entry, &m));
+    ```
+
+    **Note**:
+
+    * Fitted to few forecasts, Isotonic overfits easily. Temperature has a single parameter
+      and is the safest choice for small data sets.
+**/
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Calibrator {
+    pub method: Method,
+    temperature: f64,
+    // slope and intercept of each outcome
+    platt: Vec<(f64, f64)>,
+    // points (probability, frequency) of the step function of each outcome
+    isotonic: Vec<Vec<(f64, f64)>>,
+}
+
+fn logit(p: f64) -> f64 {
+    let p = p.clamp(EPSILON, 1f64 - EPSILON);
+    (p / (1f64 - p)).ln()
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1f64 / (1f64 + (-x).exp())
+}
+
+// normalizes the probabilities to add up to 1, the original ones are kept if all are 0
+fn normalized(calibrated: [f64; 3], original: [f64; 3]) -> [f64; 3] {
+    let total: f64 = calibrated.iter().sum();
+    if total <= 0f64 || !total.is_finite() {
+        return original;
+    }
+    [calibrated[0] / total, calibrated[1] / total, calibrated[2] / total]
+}
+
+// softens (temperature > 1) or sharpens the probabilities
+fn scaled(probabilities: [f64; 3], temperature: f64) -> [f64; 3] {
+    let mut scaled = [0f64; 3];
+    for (s, p) in scaled.iter_mut().zip(&probabilities) {
+        *s = (p.max(EPSILON).ln() / temperature).exp();
+    }
+    normalized(scaled, probabilities)
+}
+
+// the temperature with the lowest log-loss, by a golden section search on the logarithm of the temperature
+fn fit_temperature(forecasts: &[Forecast]) -> f64 {
+    let loss = |log_t: f64| -> f64 {
+        forecasts
+            .iter()
+            .map(|(p, r)| -scaled(*p, log_t.exp())[Outcome::from(*r).index()].max(EPSILON).ln())
+            .sum()
+    };
+    let ratio = (5f64.sqrt() - 1f64) / 2f64;
+    let (mut a, mut b) = (-3f64, 3f64);
+    for _ in 0..100 {
+        let (c, d) = (b - ratio * (b - a), a + ratio * (b - a));
+        if loss(c) < loss(d) {
+            b = d;
+        } else {
+            a = c;
+        }
+    }
+    ((a + b) / 2f64).exp()
+}
+
+// slope and intercept of a logistic regression on the logit, fitted by Newton's method with Platt's smoothed targets
+fn fit_platt(points: &[(f64, bool)]) -> (f64, f64) {
+    let positives = points.iter().filter(|(_, o)| *o).count() as f64;
+    let negatives = points.len() as f64 - positives;
+    let (high, low) = ((positives + 1f64) / (positives + 2f64), 1f64 / (negatives + 2f64));
+    let (mut a, mut b) = (1f64, 0f64);
+    for _ in 0..50 {
+        let (mut ga, mut gb, mut haa, mut hab, mut hbb) = (0f64, 0f64, 1e-9, 0f64, 1e-9);
+        for (p, o) in points {
+            let x = logit(*p);
+            let q = sigmoid(a * x + b);
+            let t = if *o { high } else { low };
+            let w = q * (1f64 - q);
+            ga += (q - t) * x;
+            gb += q - t;
+            haa += w * x * x;
+            hab += w * x;
+            hbb += w;
+        }
+        let det = haa * hbb - hab * hab;
+        if det.abs() < 1e-12 {
+            break;
+        }
+        let (da, db) = ((hbb * ga - hab * gb) / det, (haa * gb - hab * ga) / det);
+        a -= da;
+        b -= db;
+        if da.abs() + db.abs() < 1e-9 {
+            break;
+        }
+    }
+    (a, b)
+}
+
+// the pool adjacent violators algorithm, returns the mean probability and the frequency of each block
+fn fit_isotonic(points: &[(f64, bool)]) -> Vec<(f64, f64)> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    // sum of probabilities, sum of outcomes, size and highest probability of each block
+    let mut blocks: Vec<(f64, f64, f64, f64)> = vec![];
+    for (p, o) in sorted {
+        let o = o as usize as f64;
+        match blocks.last_mut() {
+            // forecasts with the same probability always share a block
+            Some(last) if last.3 == p => *last = (last.0 + p, last.1 + o, last.2 + 1f64, p),
+            _ => blocks.push((p, o, 1f64, p)),
+        }
+        while blocks.len() > 1 {
+            let (last, previous) = (blocks[blocks.len() - 1], blocks[blocks.len() - 2]);
+            if previous.1 / previous.2 < last.1 / last.2 {
+                break;
+            }
+            blocks.pop();
+            let merged = (previous.0 + last.0, previous.1 + last.1, previous.2 + last.2, last.3);
+            *blocks.last_mut().unwrap() = merged;
+        }
+    }
+    blocks.iter().map(|(p, o, n, _)| (p / n, o / n)).collect()
+}
+
+// the step function at a probability, linear between the points, constant beyond them
+fn interpolate(points: &[(f64, f64)], p: f64) -> f64 {
+    match points.iter().position(|(x, _)| *x >= p) {
+        None => points.last().map(|(_, y)| *y).unwrap_or(p),
+        Some(0) => points[0].1,
+        Some(i) => {
+            let ((x0, y0), (x1, y1)) = (points[i - 1], points[i]);
+            y0 + (y1 - y0) * (p - x0) / (x1 - x0)
+        }
+    }
+}
+
+impl Calibrator {
+    /// Returns a calibrator that keeps the probabilities.
+    pub fn identity(method: Method) -> Self {
+        Calibrator {
+            method,
+            temperature: 1f64,
+            platt: vec![(1f64, 0f64); 3],
+            isotonic: vec![],
+        }
+    }
+
+    /// Fits the calibration map to held-out forecasts.
+    pub fn fit(method: Method, forecasts: &[Forecast]) -> Self {
+        let mut calibrator = Calibrator::identity(method);
+        if forecasts.is_empty() {
+            return calibrator;
+        }
+        // the probability of each outcome and whether it came true
+        let points = |o: usize| -> Vec<(f64, bool)> {
+            forecasts.iter().map(|(p, r)| (p[o], Outcome::from(*r).index() == o)).collect()
+        };
+        match method {
+            Method::Temperature => calibrator.temperature = fit_temperature(forecasts),
+            Method::Platt => calibrator.platt = (0..3).map(|o| fit_platt(&points(o))).collect(),
+            Method::Isotonic => calibrator.isotonic = (0..3).map(|o| fit_isotonic(&points(o))).collect(),
+        }
+        calibrator
+    }
+
+    /**
+        Fits the model to the first ```1 - validation``` share of the training set and the calibration
+        map to its forecasts of the rest. The model is left fitted to the first share only.
+    **/
+    pub fn held_out<P: Predictor + ?Sized>(
+        method: Method,
+        model: &mut P,
+        validation: f64,
+        training_set: &[DataEntry],
+        matches: &[Match],
+    ) -> Self {
+        let split = ((training_set.len() as f64) * (1f64 - validation)).round() as usize;
+        if split == 0 || split >= training_set.len() {
+            return Calibrator::identity(method);
+        }
+        model.fit(&training_set[..split], &matches[..split]);
+        let held_out: Vec<Forecast> = training_set[split..]
+            .iter()
+            .zip(&matches[split..])
+            .filter_map(|(e, m)| m.result.map(|r| (model.predict_outcome(e, m), r)))
+            .collect();
+        Calibrator::fit(method, &held_out)
+    }
+
+    /// Returns the fitted temperature, 1 unless the method is ```Method::Temperature```.
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// Returns the calibrated probabilities.
+    pub fn apply(&self, probabilities: [f64; 3]) -> [f64; 3] {
+        match self.method {
+            Method::Temperature => scaled(probabilities, self.temperature),
+            Method::Platt => {
+                let mut calibrated = [0f64; 3];
+                for (o, (a, b)) in self.platt.iter().enumerate().take(3) {
+                    calibrated[o] = sigmoid(a * logit(probabilities[o]) + b);
+                }
+                normalized(calibrated, probabilities)
+            }
+            Method::Isotonic => {
+                if self.isotonic.len() < 3 {
+                    return probabilities;
+                }
+                let mut calibrated = [0f64; 3];
+                for (o, points) in self.isotonic.iter().enumerate().take(3) {
+                    calibrated[o] = interpolate(points, probabilities[o]);
+                }
+                normalized(calibrated, probabilities)
+            }
+        }
+    }
+}
+
+/**
+    A model with calibrated outcome probabilities, the predicted goals are the model's.
+
+    The model is fitted to the first ```1 - validation``` share of the training set and the
+    calibrator to its forecasts of the held-out rest, then the model is fitted to the whole
+    training set. The calibrator is stored with the model, all later predictions are calibrated.
+    The wrapped model keeps its type, a ```Calibrated<Elo>``` still gives access to the ratings.
+
+    **Example**:
+
+    ```text
+    let mut calibrated = Calibrated::new(Box::new(Elo::default()), Method::Platt);
+    guru.train(&mut calibrated, &training_set, &matches);
+    save_calibrator(calibrated.calibrator())?;
+    ```
+**/
+pub struct Calibrated<P: Predictor + ?Sized = dyn Predictor> {
+    pub model: Box<P>,
+    /// The share of the training set the calibrator is fitted to.
+    pub validation: f64,
+    calibrator: Calibrator,
+}
+
+impl<P: Predictor + ?Sized> Calibrated<P> {
+    pub fn new(model: Box<P>, method: Method) -> Self {
+        Calibrated::with_calibrator(model, Calibrator::identity(method))
+    }
+
+    /// Wraps a fitted model with a fitted (or loaded) calibrator.
+    pub fn with_calibrator(model: Box<P>, calibrator: Calibrator) -> Self {
+        Calibrated {
+            model,
+            validation: 0.2,
+            calibrator,
+        }
+    }
+
+    /// Returns the calibrator.
+    pub fn calibrator(&self) -> &Calibrator {
+        &self.calibrator
+    }
+}
+
+impl<P: Predictor + ?Sized> Predictor for Calibrated<P> {
+    fn fit(&mut self, training_set: &[DataEntry], matches: &[Match]) {
+        self.calibrator = Calibrator::held_out(
+            self.calibrator.method,
+            self.model.as_mut(),
+            self.validation,
+            training_set,
+            matches,
+        );
+        self.model.fit(training_set, matches);
+    }
+
+    fn predict_scores(&self, entry: &DataEntry, m: &Match) -> [f64; 2] {
+        self.model.predict_scores(entry, m)
+    }
+
+    fn predict_outcome(&self, entry: &DataEntry, m: &Match) -> [f64; 3] {
+        self.calibrator.apply(self.model.predict_outcome(entry, m))
+    }
+
+    fn name(&self) -> String {
+        let method = match self.calibrator.method {
+            Method::Platt => "Platt",
+            Method::Isotonic => "Isotonic",
+            Method::Temperature => "Temperature",
+        };
+        format!("{} ({})", self.model.name(), method)
+    }
+}
+
+/// Formats ```Reliability``` into a Markdown Table, including header. Empty bins are left out.
+impl Markdown for Reliability {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Bin|Forecasts|Predicted|Observed|Diagram|\n");
+        s.push_str("|:-|-:|-:|-:|:-|\n");
+        for bin in self.0.iter().filter(|b| b.count > 0) {
+            // the observed frequency as a bar, the predicted probability marked by '|'
+            let observed = (bin.observed * 20f64).round() as usize;
+            let mut bar: Vec<char> = (0..20).map(|i| if i < observed { '#' } else { '.' }).collect();
+            bar[((bin.predicted * 20f64) as usize).min(19)] = '|';
+            s.push_str(&format!(
+                "|{:.1}-{:.1}|{}|{:.3}|{:.3}|`{}`|\n",
+                bin.lower,
+                bin.upper,
+                bin.count,
+                bin.predicted,
+                bin.observed,
+                bar.into_iter().collect::<String>()
+            ));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{entry, played};
+
+    // overconfident: predicts a home win with 90%, half of the matches end in an away win
+    fn overconfident() -> Vec<Forecast> {
+        (0..20).map(|i| ([0.9, 0.05, 0.05], if i % 2 == 0 { [1, 0] } else { [0, 1] })).collect()
+    }
+
+    struct Overconfident;
+
+    impl Predictor for Overconfident {
+        fn fit(&mut self, _training_set: &[DataEntry], _matches: &[Match]) {}
+
+        fn predict_scores(&self, _entry: &DataEntry, _m: &Match) -> [f64; 2] {
+            [2f64, 0f64]
+        }
+
+        fn predict_outcome(&self, _entry: &DataEntry, _m: &Match) -> [f64; 3] {
+            [0.9, 0.05, 0.05]
+        }
+
+        fn name(&self) -> String {
+            String::from("Overconfident")
+        }
+    }
+
+    #[test]
+    fn reliability_bins_the_probabilities() {
+        let forecasts = vec![
+            ([0.75, 0.2, 0.05], [1, 0]),
+            ([0.75, 0.2, 0.05], [2, 1]),
+            ([0.75, 0.2, 0.05], [0, 0]),
+            ([0.75, 0.2, 0.05], [3, 0]),
+        ];
+        let home = Reliability::new(&forecasts, Some(Outcome::Home), 10);
+        assert_eq!(home.0.len(), 10);
+        assert_eq!(home.0[7].count, 4);
+        assert!((home.0[7].observed - 0.75).abs() < 1e-12);
+        assert!(home.ece().abs() < 1e-12);
+        let all = Reliability::new(&forecasts, None, 10);
+        assert_eq!(all.0.iter().map(|b| b.count).sum::<usize>(), 12);
+        // draws: 0.2 predicted, 0.25 observed; away wins: 0.05 predicted, never observed
+        assert!((all.ece() - (4f64 * 0.05 + 4f64 * 0.05) / 12f64).abs() < 1e-12);
+        assert_eq!(all.to_table().lines().count(), 2 + 3);
+    }
+
+    #[test]
+    fn pool_adjacent_violators_merge_decreasing_blocks() {
+        let points = [(0.1, false), (0.2, true), (0.3, false), (0.4, true)];
+        let steps = fit_isotonic(&points);
+        assert_eq!(steps.len(), 3);
+        assert!((steps[1].0 - 0.25).abs() < 1e-12 && (steps[1].1 - 0.5).abs() < 1e-12);
+        assert!((interpolate(&steps, 0.175) - 0.25).abs() < 1e-12);
+        assert_eq!(interpolate(&steps, 0.05), 0f64);
+        assert_eq!(interpolate(&steps, 0.9), 1f64);
+    }
+
+    #[test]
+    fn every_method_softens_overconfident_forecasts() {
+        for method in &[Method::Temperature, Method::Platt, Method::Isotonic] {
+            let calibrator = Calibrator::fit(*method, &overconfident());
+            let calibrated = calibrator.apply([0.9, 0.05, 0.05]);
+            assert!(calibrated[0] < 0.8, "{:?}: {:?}", method, calibrated);
+            assert!((calibrated.iter().sum::<f64>() - 1f64).abs() < 1e-12);
+        }
+        assert!(Calibrator::fit(Method::Temperature, &overconfident()).temperature() > 1f64);
+    }
+
+    #[test]
+    fn tied_probabilities_share_a_step() {
+        let steps = fit_isotonic(&[(0.05, false), (0.05, true), (0.05, true), (0.05, false)]);
+        assert_eq!(steps, vec![(0.05, 0.5)]);
+    }
+
+    #[test]
+    fn the_identity_keeps_the_probabilities_and_survives_json() {
+        let identity = Calibrator::identity(Method::Platt);
+        let p = identity.apply([0.5, 0.3, 0.2]);
+        assert!(p.iter().zip(&[0.5, 0.3, 0.2]).all(|(a, b)| (a - b).abs() < 1e-9));
+        let fitted = Calibrator::fit(Method::Platt, &overconfident());
+        let loaded: Calibrator = serde_json::from_str(&serde_json::to_string(&fitted).unwrap()).unwrap();
+        let (a, b) = (loaded.apply([0.6, 0.3, 0.1]), fitted.apply([0.6, 0.3, 0.1]));
+        assert!(a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-12));
+    }
+
+    #[test]
+    fn calibrated_models_keep_their_goals() {
+        let results = [[1, 0], [0, 1]];
+        let matches: Vec<Match> = (0..20).map(|day| played(day, "A", "B", results[day as usize % 2])).collect();
+        let entries = vec![entry(); 20];
+        let mut calibrated = Calibrated::new(Box::new(Overconfident), Method::Temperature);
+        calibrated.fit(&entries, &matches);
+        assert!(calibrated.calibrator().temperature() > 1f64);
+        assert_eq!(calibrated.predict_scores(&entries[0], &matches[0]), [2f64, 0f64]);
+        assert!(calibrated.predict_outcome(&entries[0], &matches[0])[0] < 0.9);
+        assert_eq!(calibrated.name(), "Overconfident (Temperature)");
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{models::Outcome, Markdown, Predictions};
+use std::collections::{BTreeMap, BTreeSet};
+
+const OUTCOMES: [&str; 3] = ["Home", "Draw", "Away"];
+
+/**
+    The confusion matrix of the predicted winners (or draws): ```counts[actual][predicted]```,
+    indexed home win, draw, away win. The predicted outcome follows from the predicted result.
+
+    **Example**:
+
+    ```text
+    let confusion = Confusion::from(&predictions);
+    println!("{}", confusion.to_table());
+    println!("draw recall: {:.1}%", confusion.recall(Outcome::Draw));
+    ```
+**/
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Confusion {
+    pub counts: [[usize; 3]; 3],
+}
+
+/**
+    The counts of each pair of actual and predicted result. Shown as a heat-table, the rows are
+    the actual results and the columns the predicted ones.
+**/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScorelineConfusion(pub BTreeMap<([u8; 2], [u8; 2]), usize>);
+
+// a rate in percent, 0 without any count
+fn percent(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0f64;
+    }
+    count as f64 * 100f64 / total as f64
+}
+
+impl Confusion {
+    /// Returns the number of tested matches.
+    pub fn tested(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    /// Returns the share (in percent) of the predictions of the outcome that were correct.
+    pub fn precision(&self, outcome: Outcome) -> f64 {
+        let o = outcome.index();
+        percent(self.counts[o][o], self.counts.iter().map(|row| row[o]).sum())
+    }
+
+    /// Returns the share (in percent) of the matches with the outcome that were predicted correctly.
+    pub fn recall(&self, outcome: Outcome) -> f64 {
+        let o = outcome.index();
+        percent(self.counts[o][o], self.counts[o].iter().sum())
+    }
+}
+
+/// Counts the predictions, all of them must be predictions of matches with result.
+impl From<&Predictions> for Confusion {
+    fn from(predictions: &Predictions) -> Self {
+        let mut confusion = Confusion::default();
+        for p in predictions.iter() {
+            let actual = Outcome::from(p.expected_scores()).index();
+            let predicted = Outcome::from(p.predicted_scores()).index();
+            confusion.counts[actual][predicted] += 1;
+        }
+        confusion
+    }
+}
+
+/// Counts the predictions, all of them must be predictions of matches with result.
+impl From<&Predictions> for ScorelineConfusion {
+    fn from(predictions: &Predictions) -> Self {
+        let mut confusion = BTreeMap::new();
+        for p in predictions.iter() {
+            *confusion.entry((p.expected_scores(), p.predicted_scores())).or_default() += 1;
+        }
+        ScorelineConfusion(confusion)
+    }
+}
+
+/**
+    Formats ```Confusion``` into two Markdown Tables, including headers: the matrix (actual outcomes
+    in the rows) and the precision and recall of each outcome.
+**/
+impl Markdown for Confusion {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Actual \\ Predicted|Home|Draw|Away|\n");
+        s.push_str("|:-|-:|-:|-:|\n");
+        for (name, row) in OUTCOMES.iter().zip(&self.counts) {
+            s.push_str(&format!("|{}|{}|{}|{}|\n", name, row[0], row[1], row[2]));
+        }
+        s.push_str("\n|Outcome|Predicted|Actual|Precision|Recall|\n");
+        s.push_str("|:-|-:|-:|-:|-:|\n");
+        for (o, outcome) in [Outcome::Home, Outcome::Draw, Outcome::Away].iter().enumerate() {
+            s.push_str(&format!(
+                "|{}|{}|{}|{:.1}%|{:.1}%|\n",
+                OUTCOMES[o],
+                self.counts.iter().map(|row| row[o]).sum::<usize>(),
+                self.counts[o].iter().sum::<usize>(),
+                self.precision(*outcome),
+                self.recall(*outcome)
+            ));
+        }
+        s
+    }
+}
+
+/**
+    Formats ```ScorelineConfusion``` into a Markdown Table, including header. Only scorelines that
+    occur are shown, a cell shades from ```░``` to ```█``` with its count.
+**/
+impl Markdown for ScorelineConfusion {
+    fn to_table(&self) -> String {
+        let actual: BTreeSet<[u8; 2]> = self.0.keys().map(|(a, _)| *a).collect();
+        let predicted: BTreeSet<[u8; 2]> = self.0.keys().map(|(_, p)| *p).collect();
+        let max = self.0.values().cloned().max().unwrap_or(0);
+        let mut s = String::new();
+        s.push_str("|Actual \\ Predicted|");
+        for p in &predicted {
+            s.push_str(&format!("{} : {}|", p[0], p[1]));
+        }
+        s.push_str("\n|:-|");
+        s.push_str(&"-:|".repeat(predicted.len()));
+        s.push('\n');
+        for a in &actual {
+            s.push_str(&format!("|{} : {}|", a[0], a[1]));
+            for p in &predicted {
+                match self.0.get(&(*a, *p)) {
+                    Some(count) => {
+                        let shade = ['░', '▒', '▓', '█'][((count * 4 - 1) / max).min(3)];
+                        s.push_str(&format!("{} {}|", shade, count));
+                    }
+                    None => s.push('|'),
+                }
+            }
+            s.push('\n');
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{prediction, predictions};
+
+    fn tested() -> Predictions {
+        let p = [0.4, 0.3, 0.3];
+        predictions(vec![
+            prediction(0, [2, 1], [2f64, 1f64], p),
+            prediction(1, [1, 0], [2f64, 1f64], p),
+            prediction(2, [1, 1], [2f64, 1f64], p),
+            prediction(3, [0, 1], [1f64, 1f64], p),
+            prediction(4, [0, 0], [0f64, 0f64], p),
+        ])
+    }
+
+    #[test]
+    fn counts_actual_against_predicted_outcomes() {
+        let confusion = Confusion::from(&tested());
+        assert_eq!(confusion.counts, [[2, 0, 0], [1, 1, 0], [0, 1, 0]]);
+        assert_eq!(confusion.tested(), 5);
+        assert!((confusion.precision(Outcome::Home) - 200f64 / 3f64).abs() < 1e-12);
+        assert_eq!(confusion.recall(Outcome::Draw), 50f64);
+        // no away win was predicted
+        assert_eq!(confusion.precision(Outcome::Away), 0f64);
+        assert_eq!(confusion.to_table().lines().count(), 2 + 3 + 1 + 2 + 3);
+    }
+
+    #[test]
+    fn counts_pairs_of_scorelines() {
+        let confusion = ScorelineConfusion::from(&tested());
+        assert_eq!(confusion.0[&([2, 1], [2, 1])], 1);
+        assert_eq!(confusion.0.len(), 5);
+        let table = confusion.to_table();
+        // 5 actual results, 3 predicted results
+        assert_eq!(table.lines().count(), 2 + 5);
+        assert!(table.lines().next().unwrap().ends_with("0 : 0|1 : 1|2 : 1|"));
+    }
+}
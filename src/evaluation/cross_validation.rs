@@ -0,0 +1,287 @@
+use crate::{
+    ensemble::stacking::BaseModel,
+    evaluation::metrics::Scores,
+    models::{DataEntry, Match, Outcome},
+    Markdown, Predictions, Testing, Training,
+};
+use rand::{seq::SliceRandom, thread_rng};
+
+/**
+    How the matches are assigned to the folds (see ```docs/guide/cross_validation.md```).
+
+    * ```Random```: The matches are shuffled and dealt to the folds.
+    * ```Stratified```: The home wins, draws and away wins are shuffled and dealt to the folds
+      separately, each fold holds about the same shares of outcomes as the data set.
+    * ```TimeBlocked```: Each fold is a block of consecutive matches, in the order of the data set.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Folding {
+    Random,
+    Stratified,
+    TimeBlocked,
+}
+
+/**
+    k-fold cross validation: each fold is tested once by a new model fitted to all other folds.
+
+    **Example**:
+
+    ```text
+    let cv = CrossValidation { k: 5, folding: Folding::Stratified };
+    let factory: BaseModel = Box::new(|| Box::new(Elo::default()));
+    let report = cv.run(&guru, &factory, &entries, &matches);
+    println!("{}", report.to_table());
+    ```
+
+    **Note**:
+
+    * Models are fitted to results after the matches they predict, even with
+      ```Folding::TimeBlocked``` (except for the last fold). See ```Backtest``` for an evaluation
+      that only looks back.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrossValidation {
+    pub k: usize,
+    pub folding: Folding,
+}
+
+/// The results of a fold: the number of training matches, the exact result and winner rates in percent and the scores.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fold {
+    pub trained: usize,
+    pub result: f64,
+    pub winner: f64,
+    pub scores: Scores,
+}
+
+/// The results of each fold of a cross validation and the predictions of all folds, fold by fold.
+#[derive(Debug)]
+pub struct CrossValidationReport {
+    pub folds: Vec<Fold>,
+    pub predictions: Predictions,
+}
+
+impl Default for CrossValidation {
+    fn default() -> Self {
+        CrossValidation {
+            k: 5,
+            folding: Folding::Random,
+        }
+    }
+}
+
+/// Returns the indices of the matches of each fold.
+pub fn folds(matches: &[Match], k: usize, folding: Folding) -> Vec<Vec<usize>> {
+    if k < 2 || k > matches.len() {
+        panic!("cross validation requires 2 <= k <= number of matches");
+    }
+    let mut rng = thread_rng();
+    let mut folds = vec![vec![]; k];
+    match folding {
+        Folding::TimeBlocked => {
+            for i in 0..matches.len() {
+                folds[i * k / matches.len()].push(i);
+            }
+        }
+        Folding::Random | Folding::Stratified => {
+            let mut groups: Vec<Vec<usize>> = if folding == Folding::Random {
+                vec![(0..matches.len()).collect()]
+            } else {
+                let mut outcomes = vec![vec![]; 3];
+                for (i, m) in matches.iter().enumerate() {
+                    outcomes[m.result.map(|r| Outcome::from(r).index()).unwrap_or(0)].push(i);
+                }
+                outcomes
+            };
+            // dealing continues across the groups, the folds differ in size by one at most
+            let mut next = 0;
+            for group in groups.iter_mut() {
+                group.shuffle(&mut rng);
+                for &i in group.iter() {
+                    folds[next % k].push(i);
+                    next += 1;
+                }
+            }
+            for fold in folds.iter_mut() {
+                fold.sort_unstable();
+            }
+        }
+    }
+    folds
+}
+
+impl CrossValidation {
+    /**
+        Runs all k rounds, ```model``` creates a new, unfitted model for each fold. ```entries```
+        and ```matches``` must have the same order, all matches must have a result.
+    **/
+    pub fn run<T: Testing + Training>(
+        &self,
+        tester: &T,
+        model: &BaseModel,
+        entries: &[DataEntry],
+        matches: &[Match],
+    ) -> CrossValidationReport {
+        let folds = folds(matches, self.k, self.folding);
+        let mut report = vec![];
+        let mut pooled = Predictions(vec![]);
+        for (f, fold) in folds.iter().enumerate() {
+            let training: Vec<usize> = folds
+                .iter()
+                .enumerate()
+                .filter(|(other, _)| *other != f)
+                .flat_map(|(_, indices)| indices.iter().cloned())
+                .collect();
+            let select = |indices: &[usize]| -> (Vec<DataEntry>, Vec<Match>) {
+                indices.iter().map(|&i| (entries[i].clone(), matches[i].clone())).unzip()
+            };
+            let (training_set, training_matches) = select(&training);
+            let (test_set, test_matches) = select(fold);
+            let mut fitted = model();
+            tester.train(fitted.as_mut(), &training_set, &training_matches);
+            let (stats, predictions) = tester.test(fitted.as_ref(), &test_set, &test_matches);
+            report.push(Fold {
+                trained: training.len(),
+                result: stats[0].correct(),
+                winner: stats[1].correct(),
+                scores: Scores::from(&predictions),
+            });
+            pooled.0.extend(predictions.0);
+        }
+        CrossValidationReport {
+            folds: report,
+            predictions: pooled,
+        }
+    }
+}
+
+// the metrics of a fold: result and winner rates, Brier, RPS, log-loss, MAE and RMSE
+fn metrics(fold: &Fold) -> [f64; 7] {
+    let s = &fold.scores;
+    [fold.result, fold.winner, s.brier, s.rps, s.log_loss, s.mae, s.rmse]
+}
+
+impl CrossValidationReport {
+    /// Returns the mean of each metric over the folds, in the order of the table columns.
+    pub fn mean(&self) -> [f64; 7] {
+        let mut mean = [0f64; 7];
+        for fold in &self.folds {
+            for (m, v) in mean.iter_mut().zip(&metrics(fold)) {
+                *m += v / self.folds.len() as f64;
+            }
+        }
+        mean
+    }
+
+    /// Returns the (sample) standard deviation of each metric over the folds, in the order of the table columns.
+    pub fn std_dev(&self) -> [f64; 7] {
+        let mean = self.mean();
+        let mut variance = [0f64; 7];
+        if self.folds.len() < 2 {
+            return variance;
+        }
+        for fold in &self.folds {
+            for ((v, x), m) in variance.iter_mut().zip(&metrics(fold)).zip(&mean) {
+                *v += (x - m).powi(2) / (self.folds.len() - 1) as f64;
+            }
+        }
+        for v in variance.iter_mut() {
+            *v = v.sqrt();
+        }
+        variance
+    }
+}
+
+// a row of the table, rates in percent
+fn row(name: &str, tested: String, trained: String, m: [f64; 7]) -> String {
+    format!(
+        "|{}|{}|{}|{:.1}%|{:.1}%|{:.4}|{:.4}|{:.4}|{:.3}|{:.3}|\n",
+        name, tested, trained, m[0], m[1], m[2], m[3], m[4], m[5], m[6]
+    )
+}
+
+/// Formats ```CrossValidationReport``` into a Markdown Table, including header. The last rows are the mean and the standard deviation.
+impl Markdown for CrossValidationReport {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Fold|Tested|Trained|Result|Winner|Brier|RPS|Log-Loss|MAE Goals|RMSE Goals|\n");
+        s.push_str("|:-|-:|-:|-:|-:|-:|-:|-:|-:|-:|\n");
+        for (f, fold) in self.folds.iter().enumerate() {
+            let name = format!("{}", f + 1);
+            s.push_str(&row(&name, fold.scores.tested.to_string(), fold.trained.to_string(), metrics(fold)));
+        }
+        s.push_str(&row("Mean", String::new(), String::new(), self.mean()));
+        s.push_str(&row("Std. Dev.", String::new(), String::new(), self.std_dev()));
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        baselines::AlwaysHome,
+        testing::{entry, played},
+        Guru,
+    };
+
+    // 6 home wins, 3 draws and 3 away wins
+    fn matches() -> Vec<Match> {
+        let results = [
+            [1, 0], [1, 1], [2, 0], [0, 1], [3, 1], [2, 2],
+            [1, 0], [0, 2], [2, 1], [0, 0], [4, 0], [1, 3],
+        ];
+        results.iter().enumerate().map(|(day, r)| played(day as i64, "A", "B", *r)).collect()
+    }
+
+    #[test]
+    fn time_blocked_folds_are_consecutive() {
+        let folds = folds(&matches()[..10], 3, Folding::TimeBlocked);
+        assert_eq!(folds, vec![vec![0, 1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]]);
+    }
+
+    #[test]
+    fn random_folds_split_all_matches() {
+        let mut all: Vec<usize> = folds(&matches(), 5, Folding::Random).into_iter().flatten().collect();
+        all.sort_unstable();
+        assert_eq!(all, (0..12).collect::<Vec<usize>>());
+        assert!(folds(&matches(), 5, Folding::Random).iter().all(|f| f.len() == 2 || f.len() == 3));
+    }
+
+    #[test]
+    fn stratified_folds_keep_the_shares_of_outcomes() {
+        let matches = matches();
+        for fold in folds(&matches, 3, Folding::Stratified) {
+            let mut outcomes = [0; 3];
+            for i in fold {
+                outcomes[Outcome::from(matches[i].result.unwrap()).index()] += 1;
+            }
+            assert_eq!(outcomes, [2, 1, 1]);
+        }
+    }
+
+    #[test]
+    fn every_fold_is_tested_once() {
+        let matches = matches();
+        let entries = vec![entry(); 12];
+        let factory: BaseModel = Box::new(|| Box::new(AlwaysHome));
+        let cv = CrossValidation {
+            k: 3,
+            folding: Folding::Stratified,
+        };
+        let report = cv.run(&Guru::new(&matches), &factory, &entries, &matches);
+        assert_eq!(report.folds.len(), 3);
+        assert!(report.folds.iter().all(|f| f.trained == 8 && f.scores.tested == 4));
+        assert_eq!(report.predictions.iter().count(), 12);
+        // every fold has two home wins out of four matches
+        assert_eq!(report.mean()[1], 50f64);
+        assert_eq!(report.std_dev()[1], 0f64);
+        assert_eq!(report.to_table().lines().count(), 2 + 3 + 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "2 <= k <= number of matches")]
+    fn k_is_at_most_the_number_of_matches() {
+        folds(&matches()[..2], 3, Folding::Random);
+    }
+}
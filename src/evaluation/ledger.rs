@@ -0,0 +1,286 @@
+use crate::{
+    evaluation::metrics::Scores,
+    models::{deserialize_from_str, serialize_to_str, Match, Outcome},
+    Markdown, Prediction, Predictions,
+};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/**
+    A prediction of a future match as it was made: the model that made it, when it was made,
+    the match and the predicted goals, result and outcome probabilities.
+**/
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct LedgerEntry {
+    pub model: String,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(serialize_with = "serialize_to_str")]
+    pub predicted_at: DateTime<FixedOffset>,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(serialize_with = "serialize_to_str")]
+    pub date: DateTime<FixedOffset>,
+    pub home: String,
+    pub away: String,
+    pub predicted_goals: [f64; 2],
+    pub predicted_scores: [u8; 2],
+    pub probabilities: [f64; 3],
+}
+
+/**
+    The ledger of all predictions of future matches, one JSON object per line. Predictions are
+    only ever appended, never changed.
+
+    **Example**:
+
+    ```text
+    let (_, predictions) = guru.test(&model, &prediction_set, &prediction_matches);
+    append_ledger("guru.ledger", &Ledger::new(&model.name(), now, &predictions))?;
+    let record = load_ledger("guru.ledger")?.reconcile(&sorted);
+    println!("{}", record.to_table());
+    ```
+**/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ledger(pub Vec<LedgerEntry>);
+
+/**
+    The ledger reconciled with the results of the data set.
+
+    * ```settled```: The entries of matches with result and their result, in chronological order.
+    * ```pending```: The entries of matches without result yet.
+    * ```late```: The number of entries made after kick-off.
+
+    **Note**:
+
+    * A match counts once per model, with the last prediction made before kick-off. Predictions
+      made after kick-off are left out, they may know the result.
+**/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackRecord {
+    pub settled: Vec<(LedgerEntry, [u8; 2])>,
+    pub pending: Vec<LedgerEntry>,
+    pub late: usize,
+}
+
+impl LedgerEntry {
+    pub fn new(model: &str, predicted_at: DateTime<FixedOffset>, prediction: &Prediction) -> Self {
+        LedgerEntry {
+            model: String::from(model),
+            predicted_at,
+            date: prediction.date(),
+            home: prediction.teams().0.clone(),
+            away: prediction.teams().1.clone(),
+            predicted_goals: prediction.predicted_goals(),
+            predicted_scores: prediction.predicted_scores(),
+            probabilities: prediction.probabilities(),
+        }
+    }
+
+    // whether the entry predicts the match
+    fn predicts(&self, m: &Match) -> bool {
+        self.home == m.home && self.away == m.away && self.date.naive_local().date() == m.date.naive_local().date()
+    }
+}
+
+impl Ledger {
+    /// Creates the entries of the predictions of a model.
+    pub fn new(model: &str, predicted_at: DateTime<FixedOffset>, predictions: &Predictions) -> Self {
+        Ledger(predictions.iter().map(|p| LedgerEntry::new(model, predicted_at, p)).collect())
+    }
+
+    /// Reads a ledger from JSON lines, empty lines are skipped. The error names the first line that isn't an entry.
+    pub fn from_lines(lines: &str) -> Result<Self, String> {
+        lines
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("ledger line {}: {}", i + 1, e)))
+            .collect::<Result<Vec<LedgerEntry>, String>>()
+            .map(Ledger)
+    }
+
+    /// Writes the ledger as JSON lines.
+    pub fn to_lines(&self) -> String {
+        self.0
+            .iter()
+            .map(|entry| serde_json::to_string(entry).expect("encoding ledger entry failed") + "\n")
+            .collect()
+    }
+
+    /// Matches the entries to the results of the matches.
+    pub fn reconcile(&self, matches: &[Match]) -> TrackRecord {
+        // the last entry before kick-off of each model and match
+        let mut latest: BTreeMap<(String, String, String, String), &LedgerEntry> = BTreeMap::new();
+        let mut late = 0;
+        for entry in &self.0 {
+            if entry.predicted_at >= entry.date {
+                late += 1;
+                continue;
+            }
+            let key = (
+                entry.model.clone(),
+                entry.date.naive_local().date().to_string(),
+                entry.home.clone(),
+                entry.away.clone(),
+            );
+            let later = latest.get(&key).map(|e| e.predicted_at <= entry.predicted_at).unwrap_or(true);
+            if later {
+                latest.insert(key, entry);
+            }
+        }
+        let mut record = TrackRecord {
+            late,
+            ..TrackRecord::default()
+        };
+        for entry in latest.values() {
+            match matches.iter().find(|m| entry.predicts(m)).and_then(|m| m.result) {
+                Some(result) => record.settled.push(((*entry).clone(), result)),
+                None => record.pending.push((*entry).clone()),
+            }
+        }
+        record.settled.sort_by_key(|(e, _)| e.date);
+        record.pending.sort_by_key(|e| e.date);
+        record
+    }
+}
+
+impl TrackRecord {
+    /// Returns the names of the models with settled predictions, in alphabetical order.
+    pub fn models(&self) -> Vec<String> {
+        let mut models: Vec<String> = self.settled.iter().map(|(e, _)| e.model.clone()).collect();
+        models.sort();
+        models.dedup();
+        models
+    }
+
+    /// Returns the settled predictions of a model as predictions, for the metrics of ```evaluation```.
+    pub fn predictions(&self, model: &str) -> Predictions {
+        Predictions(
+            self.settled
+                .iter()
+                .filter(|(e, _)| e.model == model)
+                .map(|(e, result)| Prediction {
+                    date: e.date,
+                    teams: (e.home.clone(), e.away.clone()),
+                    expected_scores: (result[0], result[1]),
+                    predicted_scores: (e.predicted_scores[0], e.predicted_scores[1]),
+                    predicted_goals: e.predicted_goals,
+                    probabilities: e.probabilities,
+                    uncertainty: None,
+                })
+                .collect(),
+        )
+    }
+}
+
+/**
+    Formats ```TrackRecord``` into Markdown Tables, including headers: the settled predictions with
+    the running winner rate of their model, a summary per model and the number of pending predictions.
+**/
+impl Markdown for TrackRecord {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Date|Model|Home|Predicted|Actual|Away|Home Win|Draw|Away Win|Winner|Winner to Date|\n");
+        s.push_str("|:-|:-|-:|:-:|:-:|:-|-:|-:|-:|:-:|-:|\n");
+        let mut running: BTreeMap<&str, [usize; 2]> = BTreeMap::new();
+        for (e, result) in &self.settled {
+            let correct = Outcome::from(e.predicted_scores) == Outcome::from(*result);
+            let counts = running.entry(&e.model).or_insert([0, 0]);
+            counts[0] += correct as usize;
+            counts[1] += 1;
+            s.push_str(&format!(
+                "|{}|{}|{}|{} : {}|{} : {}|{}|{:.2}|{:.2}|{:.2}|{}|{:.1}%|\n",
+                e.date.format("%Y-%m-%d"),
+                e.model,
+                e.home,
+                e.predicted_scores[0],
+                e.predicted_scores[1],
+                result[0],
+                result[1],
+                e.away,
+                e.probabilities[0],
+                e.probabilities[1],
+                e.probabilities[2],
+                if correct { "yes" } else { "no" },
+                counts[0] as f64 * 100f64 / counts[1] as f64
+            ));
+        }
+        s.push_str("\n|Model|Settled|Result|Winner|Brier|RPS|Log-Loss|\n");
+        s.push_str("|:-|-:|-:|-:|-:|-:|-:|\n");
+        for model in self.models() {
+            let predictions = self.predictions(&model);
+            let scores = Scores::from(&predictions);
+            let (mut results, mut winners) = (0, 0);
+            for p in predictions.iter() {
+                results += (p.expected_scores() == p.predicted_scores()) as usize;
+                winners += (Outcome::from(p.expected_scores()) == Outcome::from(p.predicted_scores())) as usize;
+            }
+            s.push_str(&format!(
+                "|{}|{}|{:.1}%|{:.1}%|{:.4}|{:.4}|{:.4}|\n",
+                model,
+                scores.tested,
+                results as f64 * 100f64 / scores.tested as f64,
+                winners as f64 * 100f64 / scores.tested as f64,
+                scores.brier,
+                scores.rps,
+                scores.log_loss
+            ));
+        }
+        s.push_str(&format!(
+            "\nPending: {} predictions without result, {} made after kick-off left out\n",
+            self.pending.len(),
+            self.late
+        ));
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{date, fixture, played, prediction};
+
+    fn entry(model: &str, predicted_at: i64, day: i64, goals: [f64; 2]) -> LedgerEntry {
+        LedgerEntry::new(model, date(predicted_at), &prediction(day, [0, 0], goals, [0.5, 0.3, 0.2]))
+    }
+
+    #[test]
+    fn last_prediction_before_kick_off_counts() {
+        let ledger = Ledger(vec![
+            entry("Elo", -3, 0, [0.0, 1.0]),
+            entry("Elo", -1, 0, [2.0, 0.0]),
+            entry("Elo", 0, 0, [3.0, 0.0]),
+            entry("Poisson", -2, 0, [1.0, 1.0]),
+            entry("Elo", -1, 1, [1.0, 0.0]),
+        ]);
+        let matches = [played(0, "Home", "Away", [2, 0]), fixture(1, "Home", "Away")];
+        let record = ledger.reconcile(&matches);
+        assert_eq!(record.late, 1);
+        assert_eq!(record.settled.len(), 2);
+        assert_eq!(record.settled[0], (entry("Elo", -1, 0, [2.0, 0.0]), [2, 0]));
+        assert_eq!(record.pending, vec![entry("Elo", -1, 1, [1.0, 0.0])]);
+        assert_eq!(record.models(), vec!["Elo", "Poisson"]);
+        let elo = record.predictions("Elo");
+        assert_eq!(elo.iter().count(), 1);
+        assert_eq!(elo.iter().next().unwrap().expected_scores(), [2, 0]);
+        let table = record.to_table();
+        assert!(table.contains("|Elo|1|100.0%|100.0%|"));
+        assert!(table.contains("Pending: 1 predictions without result, 1 made after kick-off"));
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        let ledger = Ledger(vec![entry("Elo", -1, 0, [2.0, 0.0]), entry("Elo", -1, 1, [1.0, 1.0])]);
+        let lines = ledger.to_lines();
+        assert_eq!(lines.lines().count(), 2);
+        assert_eq!(Ledger::from_lines(&format!("\n{}\n", lines)), Ok(ledger));
+    }
+
+    #[test]
+    fn malformed_line_is_named() {
+        let lines = Ledger(vec![entry("Elo", -1, 0, [2.0, 0.0])]).to_lines();
+        let lines = lines + "\n{\"model\": \"Elo\"}\n";
+        let error = Ledger::from_lines(&lines).unwrap_err();
+        assert!(error.starts_with("ledger line 3: "), "{}", error);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{models::Outcome, Markdown, Predictions};
+
+// probabilities are clamped to this value before the log-loss is taken
+const EPSILON: f64 = 1e-15;
+
+/**
+    Scores of the predictions of a model, averaged over the tested matches. The lower, the better.
+
+    * ```brier```: The multi-class Brier score, the squared distance between the home win, draw and
+      away win probabilities and the outcome. Between 0 and 2.
+    * ```rps```: The ranked probability score. Like Brier, but on the cumulative probabilities of the
+      ordered outcomes: a home win predicted as a draw scores better than predicted as an away win.
+      Between 0 and 1.
+    * ```log_loss```: The negative logarithm of the probability of the outcome.
+    * ```mae```, ```rmse```: The mean absolute and root mean squared error of the predicted goals
+      (both teams).
+
+    **Example**:
+
+    ```text
+    probabilities 0.5 / 0.3 / 0.2, the match ends in a draw:
+    brier: 0.5^2 + 0.7^2 + 0.2^2 = 0.78
+    rps: ((0.5 - 0)^2 + (0.8 - 1)^2) / 2 = 0.145
+    log_loss: -ln(0.3) = 1.204
+    ```
+
+    **Rationale**:
+
+    * The share of correct winners rewards a model that is always sure, even if it is wrong
+      as often as a careful one. The scores punish confident wrong forecasts.
+**/
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Scores {
+    pub tested: usize,
+    pub brier: f64,
+    pub rps: f64,
+    pub log_loss: f64,
+    pub mae: f64,
+    pub rmse: f64,
+}
+
+/// The index of the outcome of a result as a one hot vector.
+fn observed(result: [u8; 2]) -> [f64; 3] {
+    let mut observed = [0f64; 3];
+    observed[Outcome::from(result).index()] = 1f64;
+    observed
+}
+
+/// Returns the multi-class Brier score of a single forecast.
+pub fn brier(probabilities: [f64; 3], result: [u8; 2]) -> f64 {
+    probabilities
+        .iter()
+        .zip(&observed(result))
+        .map(|(p, o)| (p - o).powi(2))
+        .sum()
+}
+
+/// Returns the ranked probability score of a single forecast.
+pub fn rps(probabilities: [f64; 3], result: [u8; 2]) -> f64 {
+    let observed = observed(result);
+    let (mut p, mut o, mut score) = (0f64, 0f64, 0f64);
+    for i in 0..2 {
+        p += probabilities[i];
+        o += observed[i];
+        score += (p - o).powi(2);
+    }
+    score / 2f64
+}
+
+/// Returns the log-loss of a single forecast, the probability is clamped to avoid infinite losses.
+pub fn log_loss(probabilities: [f64; 3], result: [u8; 2]) -> f64 {
+    -probabilities[Outcome::from(result).index()].max(EPSILON).ln()
+}
+
+/// Scores the predictions, all of them must be predictions of matches with result.
+impl From<&Predictions> for Scores {
+    fn from(predictions: &Predictions) -> Self {
+        let mut scores = Scores::default();
+        let mut squared = 0f64;
+        for p in predictions.iter() {
+            let (probabilities, result, goals) = (p.probabilities(), p.expected_scores(), p.predicted_goals());
+            scores.tested += 1;
+            scores.brier += brier(probabilities, result);
+            scores.rps += rps(probabilities, result);
+            scores.log_loss += log_loss(probabilities, result);
+            for (g, r) in goals.iter().zip(&result) {
+                scores.mae += (g - f64::from(*r)).abs();
+                squared += (g - f64::from(*r)).powi(2);
+            }
+        }
+        if scores.tested > 0 {
+            let n = scores.tested as f64;
+            scores.brier /= n;
+            scores.rps /= n;
+            scores.log_loss /= n;
+            scores.mae /= 2f64 * n;
+            scores.rmse = (squared / (2f64 * n)).sqrt();
+        }
+        scores
+    }
+}
+
+/// Formats ```Scores``` into a Markdown Table, including header.
+impl Markdown for Scores {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Tested|Brier|RPS|Log-Loss|MAE Goals|RMSE Goals|\n");
+        s.push_str("|-:|-:|-:|-:|-:|-:|\n");
+        s.push_str(&format!(
+            "|{}|{:.4}|{:.4}|{:.4}|{:.3}|{:.3}|\n",
+            self.tested, self.brier, self.rps, self.log_loss, self.mae, self.rmse
+        ));
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{prediction, predictions};
+
+    #[test]
+    fn scores_of_the_example() {
+        let (p, draw) = ([0.5, 0.3, 0.2], [1, 1]);
+        assert!((brier(p, draw) - 0.78).abs() < 1e-12);
+        assert!((rps(p, draw) - 0.145).abs() < 1e-12);
+        assert!((log_loss(p, draw) - 1.204).abs() < 1e-3);
+    }
+
+    #[test]
+    fn rps_punishes_the_more_distant_outcome() {
+        assert!(rps([0f64, 1f64, 0f64], [1, 0]) < rps([0f64, 0f64, 1f64], [1, 0]));
+        assert_eq!(brier([0f64, 1f64, 0f64], [1, 0]), brier([0f64, 0f64, 1f64], [1, 0]));
+        assert_eq!(rps([1f64, 0f64, 0f64], [1, 0]), 0f64);
+    }
+
+    #[test]
+    fn a_certain_wrong_forecast_has_a_finite_log_loss() {
+        assert!((log_loss([1f64, 0f64, 0f64], [0, 1]) + EPSILON.ln()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn scores_are_averaged_over_the_predictions() {
+        let scores = Scores::from(&predictions(vec![
+            prediction(0, [1, 1], [1f64, 1f64], [0.5, 0.3, 0.2]),
+            prediction(1, [2, 0], [1f64, 0f64], [1f64, 0f64, 0f64]),
+        ]));
+        assert_eq!(scores.tested, 2);
+        assert!((scores.brier - 0.39).abs() < 1e-12);
+        assert!((scores.rps - 0.0725).abs() < 1e-12);
+        assert!((scores.mae - 0.25).abs() < 1e-12);
+        assert!((scores.rmse - 0.5).abs() < 1e-12);
+        assert_eq!(Scores::from(&predictions(vec![])), Scores::default());
+        assert_eq!(scores.to_table().lines().count(), 3);
+    }
+}
@@ -0,0 +1,16 @@
+/// Walk-forward backtests, models are refitted to all earlier results before each match day.
+pub mod backtest;
+/// Reliability diagrams, the expected calibration error and post-hoc calibration of outcome probabilities.
+pub mod calibration;
+/// k-fold cross validation with random, stratified or time-blocked folds.
+pub mod cross_validation;
+/// Confusion matrices of the predicted outcomes and scorelines.
+pub mod confusion;
+/// The ledger of predictions of future matches, reconciled with their results into a live track record.
+pub mod ledger;
+/// Probabilistic scores of outcome forecasts (Brier, RPS, log-loss) and errors of the predicted goals.
+pub mod metrics;
+/// Performance of the predictions broken down by league, club, month and balance of the match.
+pub mod segments;
+/// Bootstrap confidence intervals of the metrics and paired significance tests between two models.
+pub mod significance;
@@ -0,0 +1,180 @@
+use crate::{
+    evaluation::metrics::brier,
+    models::{Match, Outcome},
+    Markdown, Prediction, Predictions,
+};
+use std::collections::BTreeMap;
+
+// the lead of the home win over the away win probability of a home-heavy match (and vice versa)
+const HEAVY: f64 = 0.25;
+
+/**
+    The dimensions the predictions are broken down by.
+
+    * ```League```: The league of the match.
+    * ```Club```: Each match counts for the home and the away team.
+    * ```Month```: The month of the match.
+    * ```Balance```: Home-heavy if the model's home win probability leads the away win
+      probability by at least 0.25, away-heavy the other way round, balanced otherwise.
+**/
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Dimension {
+    League,
+    Club,
+    Month,
+    Balance,
+}
+
+/**
+    The performance of the predictions of a segment.
+
+    * ```result```, ```winner```: The exact result and winner rates in percent.
+    * ```draws```: The number of predicted draws.
+    * ```brier```: The mean Brier score of the outcome probabilities.
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub dimension: Dimension,
+    pub name: String,
+    pub tested: usize,
+    pub result: f64,
+    pub winner: f64,
+    pub draws: usize,
+    pub brier: f64,
+}
+
+/**
+    The predictions broken down by league, club, month and balance, sorted by dimension and name.
+
+    **Example**:
+
+    ```text
+    let (_, predictions) = guru.test(&model, &test_set, &test_matches);
+    println!("{}", Segments::new(&predictions, &test_matches).to_table());
+    ```
+**/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Segments(pub Vec<Segment>);
+
+// the segments of a dimension a match belongs to
+fn keys(dimension: Dimension, p: &Prediction, m: &Match) -> Vec<String> {
+    match dimension {
+        Dimension::League => vec![m.league.clone()],
+        Dimension::Club => vec![m.home.clone(), m.away.clone()],
+        Dimension::Month => vec![m.date.format("%Y-%m").to_string()],
+        Dimension::Balance => {
+            let probabilities = p.probabilities();
+            let lead = probabilities[0] - probabilities[2];
+            let balance = if lead >= HEAVY {
+                "Home-heavy"
+            } else if lead <= -HEAVY {
+                "Away-heavy"
+            } else {
+                "Balanced"
+            };
+            vec![String::from(balance)]
+        }
+    }
+}
+
+impl Segments {
+    /**
+        Breaks the predictions down, ```matches``` are the predicted matches in the same order.
+        All of them must have a result.
+    **/
+    pub fn new(predictions: &Predictions, matches: &[Match]) -> Self {
+        let mut segments = vec![];
+        for dimension in &[Dimension::League, Dimension::Club, Dimension::Month, Dimension::Balance] {
+            let mut grouped: BTreeMap<String, Vec<&Prediction>> = BTreeMap::new();
+            for (p, m) in predictions.iter().zip(matches) {
+                for key in keys(*dimension, p, m) {
+                    grouped.entry(key).or_default().push(p);
+                }
+            }
+            for (name, group) in grouped {
+                segments.push(Segment::new(*dimension, name, &group));
+            }
+        }
+        Segments(segments)
+    }
+}
+
+impl Segment {
+    fn new(dimension: Dimension, name: String, predictions: &[&Prediction]) -> Self {
+        let (mut result, mut winner, mut draws, mut score) = (0usize, 0usize, 0usize, 0f64);
+        for p in predictions {
+            let (actual, predicted) = (p.expected_scores(), p.predicted_scores());
+            result += (actual == predicted) as usize;
+            winner += (Outcome::from(actual) == Outcome::from(predicted)) as usize;
+            draws += (Outcome::from(predicted) == Outcome::Draw) as usize;
+            score += brier(p.probabilities(), actual);
+        }
+        let n = predictions.len().max(1) as f64;
+        Segment {
+            dimension,
+            name,
+            tested: predictions.len(),
+            result: result as f64 * 100f64 / n,
+            winner: winner as f64 * 100f64 / n,
+            draws,
+            brier: score / n,
+        }
+    }
+}
+
+/// Formats ```Segments``` into a Markdown Table, including header.
+impl Markdown for Segments {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Segment|Name|Tested|Result|Winner|Predicted Draws|Brier|\n");
+        s.push_str("|:-|:-|-:|-:|-:|-:|-:|\n");
+        for segment in &self.0 {
+            s.push_str(&format!(
+                "|{:?}|{}|{}|{:.1}%|{:.1}%|{}|{:.4}|\n",
+                segment.dimension,
+                segment.name,
+                segment.tested,
+                segment.result,
+                segment.winner,
+                segment.draws,
+                segment.brier
+            ));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{played, prediction, predictions};
+
+    #[test]
+    fn breaks_the_predictions_down_by_each_dimension() {
+        let matches = vec![played(0, "A", "B", [2, 1]), played(40, "B", "C", [1, 1])];
+        let predictions = predictions(vec![
+            prediction(0, [2, 1], [2f64, 1f64], [0.6, 0.3, 0.1]),
+            prediction(40, [1, 1], [1f64, 0f64], [0.4, 0.3, 0.3]),
+        ]);
+        let segments = Segments::new(&predictions, &matches);
+        let names: Vec<(Dimension, &str, usize)> =
+            segments.0.iter().map(|s| (s.dimension, s.name.as_str(), s.tested)).collect();
+        assert_eq!(
+            names,
+            vec![
+                (Dimension::League, "League", 2),
+                (Dimension::Club, "A", 1),
+                (Dimension::Club, "B", 2),
+                (Dimension::Club, "C", 1),
+                (Dimension::Month, "2019-01", 1),
+                (Dimension::Month, "2019-02", 1),
+                (Dimension::Balance, "Balanced", 1),
+                (Dimension::Balance, "Home-heavy", 1),
+            ]
+        );
+        let league = &segments.0[0];
+        assert_eq!((league.result, league.winner, league.draws), (50f64, 50f64, 0));
+        assert!((league.brier - (0.26 + 0.74) / 2f64).abs() < 1e-12);
+        assert_eq!(segments.to_table().lines().count(), 2 + 8);
+    }
+}
@@ -0,0 +1,374 @@
+use crate::{
+    evaluation::metrics::{brier, log_loss, rps},
+    models::Outcome,
+    Markdown, Prediction, Predictions,
+};
+use rand::{thread_rng, Rng};
+
+/// The metrics the bootstrap estimates intervals for, in the order of ```Intervals```.
+const METRICS: [&str; 7] = ["Result", "Winner", "Brier", "RPS", "Log-Loss", "MAE Goals", "RMSE Goals"];
+
+/// An estimate and its confidence interval.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Interval {
+    pub estimate: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/**
+    Bootstrap confidence intervals of the metrics of a test: the result and winner rates (in
+    percent), Brier, RPS, log-loss and the mean absolute and root mean squared error of the goals.
+
+    The matches are resampled with replacement, the interval covers the central ```level``` share
+    of the metric over all samples (percentile bootstrap).
+
+    **Example**:
+
+    ```text
+    let (_, predictions) = guru.test(&model, &test_set, &test_matches);
+    println!("{}", Intervals::bootstrap(&predictions, 1000, 0.95).to_table());
+    ```
+
+    **Rationale**:
+
+    * On a few dozen matches a winner rate of 76% and one of 82% may well come from the same
+      model. The width of the interval shows how much a metric moves by chance.
+**/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Intervals {
+    pub level: f64,
+    pub metrics: Vec<(String, Interval)>,
+}
+
+/**
+    McNemar's test of two models on the same matches: does one predict the winner correctly
+    significantly more often? Only matches where exactly one of the models is right count.
+    The p-value is exact (two-sided binomial test).
+**/
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct McNemar {
+    pub only_first: usize,
+    pub only_second: usize,
+    pub p_value: f64,
+}
+
+/// The losses the Diebold-Mariano test compares.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Loss {
+    Brier,
+    Rps,
+    LogLoss,
+}
+
+/**
+    The Diebold-Mariano test of two models on the same matches: is the mean difference of their
+    losses (first minus second) different from zero? A negative difference favours the first model.
+    The statistic is compared to the standard normal distribution (one step ahead forecasts).
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DieboldMariano {
+    pub loss: Loss,
+    pub mean_difference: f64,
+    pub statistic: f64,
+    pub p_value: f64,
+}
+
+/**
+    Paired comparison of two models tested on the same matches in the same order.
+
+    **Example**:
+
+    ```text
+    let (_, first) = guru.test(&network, &test_set, &test_matches);
+    let (_, second) = guru.test(&elo, &test_set, &test_matches);
+    println!("{}", Comparison::new(("Network", &first), ("Elo", &second)).to_table());
+    ```
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub names: [String; 2],
+    pub mcnemar: McNemar,
+    pub diebold_mariano: Vec<DieboldMariano>,
+}
+
+// the metrics of a single match, means of the resampled values make up the metrics of a sample
+fn values(p: &Prediction) -> [f64; 7] {
+    let (actual, predicted, goals) = (p.expected_scores(), p.predicted_scores(), p.predicted_goals());
+    let errors = [goals[0] - f64::from(actual[0]), goals[1] - f64::from(actual[1])];
+    [
+        (actual == predicted) as usize as f64 * 100f64,
+        (Outcome::from(actual) == Outcome::from(predicted)) as usize as f64 * 100f64,
+        brier(p.probabilities(), actual),
+        rps(p.probabilities(), actual),
+        log_loss(p.probabilities(), actual),
+        (errors[0].abs() + errors[1].abs()) / 2f64,
+        (errors[0].powi(2) + errors[1].powi(2)) / 2f64,
+    ]
+}
+
+// the metrics of the matches at the indices
+fn metrics(values: &[[f64; 7]], indices: impl Iterator<Item = usize>) -> [f64; 7] {
+    let mut sum = [0f64; 7];
+    let mut n = 0f64;
+    for i in indices {
+        for (s, v) in sum.iter_mut().zip(&values[i]) {
+            *s += v;
+        }
+        n += 1f64;
+    }
+    for s in sum.iter_mut() {
+        *s /= n.max(1f64);
+    }
+    // the root of the mean squared error
+    sum[6] = sum[6].sqrt();
+    sum
+}
+
+// the value at the quantile of sorted values
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    sorted[((q * (sorted.len() - 1) as f64).round() as usize).min(sorted.len() - 1)]
+}
+
+impl Intervals {
+    /// Estimates the intervals from ```samples``` bootstrap samples, all predictions must be of matches with result.
+    pub fn bootstrap(predictions: &Predictions, samples: usize, level: f64) -> Self {
+        if samples == 0 || level <= 0f64 || level >= 1f64 {
+            panic!("the bootstrap requires at least one sample and a level between 0 and 1");
+        }
+        let values: Vec<[f64; 7]> = predictions.iter().map(values).collect();
+        let n = values.len();
+        let estimates = metrics(&values, 0..n);
+        if n == 0 {
+            return Intervals::default();
+        }
+        let mut rng = thread_rng();
+        let mut sampled: Vec<Vec<f64>> = (0..7).map(|_| Vec::with_capacity(samples)).collect();
+        for _ in 0..samples {
+            let sample = metrics(&values, (0..n).map(|_| rng.gen_range(0, n)));
+            for (s, m) in sampled.iter_mut().zip(&sample) {
+                s.push(*m);
+            }
+        }
+        let tail = (1f64 - level) / 2f64;
+        let metrics = METRICS
+            .iter()
+            .zip(sampled.iter_mut())
+            .zip(&estimates)
+            .map(|((name, sample), estimate)| {
+                sample.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let interval = Interval {
+                    estimate: *estimate,
+                    lower: quantile(sample, tail),
+                    upper: quantile(sample, 1f64 - tail),
+                };
+                (String::from(*name), interval)
+            })
+            .collect();
+        Intervals { level, metrics }
+    }
+}
+
+// the natural logarithm of n choose k
+fn ln_choose(n: usize, k: usize) -> f64 {
+    (1..=k).map(|i| ((n - k + i) as f64 / i as f64).ln()).sum()
+}
+
+// the standard normal distribution function (Abramowitz and Stegun 7.1.26, error below 1.5e-7)
+fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / 2f64.sqrt();
+    let t = 1f64 / (1f64 + 0.327_591_1 * z);
+    let polynomial = t
+        * (0.254_829_592 + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1f64 - polynomial * (-z * z).exp();
+    if x >= 0f64 {
+        (1f64 + erf) / 2f64
+    } else {
+        (1f64 - erf) / 2f64
+    }
+}
+
+impl McNemar {
+    /// Tests the winner hits of two models, the predictions must be of the same matches in the same order.
+    pub fn new(first: &Predictions, second: &Predictions) -> Self {
+        let hit = |p: &Prediction| Outcome::from(p.expected_scores()) == Outcome::from(p.predicted_scores());
+        let (mut only_first, mut only_second) = (0, 0);
+        for (a, b) in first.iter().zip(second.iter()) {
+            match (hit(a), hit(b)) {
+                (true, false) => only_first += 1,
+                (false, true) => only_second += 1,
+                _ => (),
+            }
+        }
+        let n = only_first + only_second;
+        let k = only_first.min(only_second);
+        let tail: f64 = (0..=k).map(|i| (ln_choose(n, i) - n as f64 * 2f64.ln()).exp()).sum();
+        McNemar {
+            only_first,
+            only_second,
+            p_value: (2f64 * tail).min(1f64),
+        }
+    }
+}
+
+impl DieboldMariano {
+    /// Tests the losses of two models, the predictions must be of the same matches in the same order.
+    pub fn new(first: &Predictions, second: &Predictions, loss: Loss) -> Self {
+        let loss_of = |p: &Prediction| match loss {
+            Loss::Brier => brier(p.probabilities(), p.expected_scores()),
+            Loss::Rps => rps(p.probabilities(), p.expected_scores()),
+            Loss::LogLoss => log_loss(p.probabilities(), p.expected_scores()),
+        };
+        let differences: Vec<f64> = first.iter().zip(second.iter()).map(|(a, b)| loss_of(a) - loss_of(b)).collect();
+        let n = differences.len() as f64;
+        let mean = differences.iter().sum::<f64>() / n.max(1f64);
+        let variance = differences.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / n.max(1f64);
+        let (statistic, p_value) = if variance > 0f64 {
+            let statistic = mean / (variance / n).sqrt();
+            (statistic, 2f64 * (1f64 - normal_cdf(statistic.abs())))
+        } else {
+            // identical losses on every match
+            (0f64, 1f64)
+        };
+        DieboldMariano {
+            loss,
+            mean_difference: mean,
+            statistic,
+            p_value,
+        }
+    }
+}
+
+impl Comparison {
+    /// Compares two named models by their predictions of the same matches in the same order.
+    pub fn new(first: (&str, &Predictions), second: (&str, &Predictions)) -> Self {
+        Comparison {
+            names: [String::from(first.0), String::from(second.0)],
+            mcnemar: McNemar::new(first.1, second.1),
+            diebold_mariano: [Loss::Brier, Loss::Rps, Loss::LogLoss]
+                .iter()
+                .map(|loss| DieboldMariano::new(first.1, second.1, *loss))
+                .collect(),
+        }
+    }
+}
+
+/// Formats ```Intervals``` into a Markdown Table, including header. Rates are in percent.
+impl Markdown for Intervals {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str(&format!("|Metric|Estimate|{:.0}% Interval|\n", self.level * 100f64));
+        s.push_str("|:-|-:|:-|\n");
+        for (name, i) in &self.metrics {
+            s.push_str(&format!("|{}|{:.3}|{:.3} - {:.3}|\n", name, i.estimate, i.lower, i.upper));
+        }
+        s
+    }
+}
+
+/// Formats ```Comparison``` into a Markdown Table, including header.
+impl Markdown for Comparison {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str(&format!("|Test|{} vs {}|Statistic|p-Value|\n", self.names[0], self.names[1]));
+        s.push_str("|:-|:-|-:|-:|\n");
+        s.push_str(&format!(
+            "|McNemar (Winner)|{} : {} only correct||{:.4}|\n",
+            self.mcnemar.only_first, self.mcnemar.only_second, self.mcnemar.p_value
+        ));
+        for dm in &self.diebold_mariano {
+            let loss = match dm.loss {
+                Loss::Brier => "Brier",
+                Loss::Rps => "RPS",
+                Loss::LogLoss => "Log-Loss",
+            };
+            s.push_str(&format!(
+                "|Diebold-Mariano ({})|{:+.4} mean difference|{:.3}|{:.4}|\n",
+                loss, dm.mean_difference, dm.statistic, dm.p_value
+            ));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{prediction, predictions};
+
+    const RESULTS: [[u8; 2]; 4] = [[1, 0], [2, 0], [2, 1], [0, 1]];
+
+    // always predicts a home win
+    fn home() -> Predictions {
+        predictions(RESULTS.iter().map(|r| prediction(0, *r, [1.0, 0.0], [0.6, 0.3, 0.1])).collect())
+    }
+
+    // always predicts an away win
+    fn away() -> Predictions {
+        predictions(RESULTS.iter().map(|r| prediction(0, *r, [0.0, 1.0], [0.1, 0.3, 0.6])).collect())
+    }
+
+    #[test]
+    fn normal_cdf_matches_the_table() {
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-7);
+        assert!((normal_cdf(1.96) - 0.975).abs() < 1e-4);
+        assert!((normal_cdf(-1.0) - 0.158_655).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ln_choose_is_the_binomial_coefficient() {
+        assert!((ln_choose(5, 2) - 10f64.ln()).abs() < 1e-12);
+        assert_eq!(ln_choose(7, 0), 0.0);
+    }
+
+    #[test]
+    fn mcnemar_is_an_exact_binomial_test() {
+        let test = McNemar::new(&home(), &away());
+        assert_eq!((test.only_first, test.only_second), (3, 1));
+        // 2 * (1 + 4) / 16
+        assert!((test.p_value - 0.625).abs() < 1e-12);
+        assert_eq!(McNemar::new(&home(), &home()).p_value, 1.0);
+    }
+
+    #[test]
+    fn diebold_mariano_favours_the_lower_losses() {
+        let test = DieboldMariano::new(&home(), &away(), Loss::Brier);
+        assert!(test.mean_difference < 0.0 && test.statistic < 0.0);
+        assert!(test.p_value > 0.0 && test.p_value < 1.0);
+        let same = DieboldMariano::new(&home(), &home(), Loss::LogLoss);
+        assert_eq!((same.mean_difference, same.statistic, same.p_value), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn bootstrap_of_identical_matches_has_no_width() {
+        let same = (0..5).map(|day| prediction(day, [1, 0], [1.0, 0.0], [0.5, 0.3, 0.2]));
+        let same = predictions(same.collect());
+        let intervals = Intervals::bootstrap(&same, 100, 0.9);
+        assert_eq!(intervals.metrics.len(), 7);
+        for (_, i) in &intervals.metrics {
+            assert!((i.lower - i.estimate).abs() < 1e-12 && (i.upper - i.estimate).abs() < 1e-12);
+        }
+        assert_eq!(intervals.metrics[1].1.estimate, 100.0);
+        assert!(intervals.to_table().contains("|Metric|Estimate|90% Interval|"));
+    }
+
+    #[test]
+    fn bootstrap_interval_contains_the_estimate() {
+        for (_, i) in &Intervals::bootstrap(&home(), 200, 0.95).metrics {
+            assert!(i.lower <= i.estimate && i.estimate <= i.upper);
+        }
+    }
+
+    #[test]
+    fn comparison_runs_every_test() {
+        let comparison = Comparison::new(("Home", &home()), ("Away", &away()));
+        assert_eq!(comparison.diebold_mariano.len(), 3);
+        assert_eq!(comparison.to_table().lines().count(), 2 + 1 + 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "a level between 0 and 1")]
+    fn bootstrap_level_is_below_one() {
+        Intervals::bootstrap(&home(), 100, 1.0);
+    }
+}
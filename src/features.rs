@@ -2,6 +2,7 @@ use chrono::{DateTime, FixedOffset};
 use crate::{
     Stats,
     models::{Match, Scoring},
+    ratings::elo::Elo,
     utils::{normalize}
 };
 use std::{
@@ -39,6 +40,23 @@ impl From<(&[Match], &DateTime<FixedOffset>)> for GameDayFeature {
         GameDayFeature{ data }
     }
 }
+/**
+    Returns the home win, draw and away win probabilities of the Elo ratings before the match.
+    The Elo engine should have processed all matches with result, only matches before the
+    date of the match are taken into account.
+
+    **Example**:
+
+    * The home team is rated 1550, the away team 1500, the home advantage is 100 rating points
+    * The draw rate in the processed matches is 0.25
+    * EloFeature { data: [0.599, 0.209, 0.192] }
+**/
+pub struct EloFeature { pub data: [f64; 3] }
+impl From<(&Elo, &Match)> for EloFeature {
+    fn from(from: (&Elo, &Match)) -> Self {
+        EloFeature { data: from.0.probabilities(from.1) }
+    }
+}
 /**
     Returns goal difference a Club shot at home and away
     Note: Stats must be updated before ```from``` is invoked.
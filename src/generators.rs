@@ -1,6 +1,9 @@
 use crate::{
-    features::{GameDayFeature, GoalDiffFeature, LeagueFeature, MedianScoreFeature, WDLFeature},
+    features::{
+        EloFeature, GameDayFeature, GoalDiffFeature, LeagueFeature, MedianScoreFeature, WDLFeature,
+    },
     models::{Clubs, Match, Scoring},
+    ratings::elo::Elo,
     utils::normalize,
     Stats,
 };
@@ -40,10 +43,41 @@ pub struct DefaultInputGenerator<'a> {
     pub values: (Vec<Match>, &'a Clubs, HashMap<String, Stats>),
 }
 
+/**
+    Appends the ```EloFeature``` to the inputs of another generator.
+
+    **Example**:
+
+    ```text
+    let mut elo = Elo::default();
+    elo.process(&sorted);
+    let mut generator = EloInputGenerator { generator: def_in_gen, elo: &elo };
+    ```
+**/
+#[derive(Clone, Debug)]
+pub struct EloInputGenerator<'a, G: Generator> {
+    pub generator: G,
+    pub elo: &'a Elo,
+}
+
 pub trait Generator {
     fn generate(&mut self, m: &Match) -> Vec<f64>;
 }
 
+impl<G: Generator + ?Sized> Generator for Box<G> {
+    fn generate(&mut self, m: &Match) -> Vec<f64> {
+        (**self).generate(m)
+    }
+}
+
+impl<G: Generator> Generator for EloInputGenerator<'_, G> {
+    fn generate(&mut self, m: &Match) -> Vec<f64> {
+        let mut inputs = self.generator.generate(m);
+        inputs.extend_from_slice(&EloFeature::from((self.elo, m)).data);
+        inputs
+    }
+}
+
 
 impl<'a> DefaultInputGenerator<'a> {
     /**
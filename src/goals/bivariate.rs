@@ -0,0 +1,200 @@
+use crate::{
+    goals::{
+        poisson,
+        poisson::{Poisson, PoissonSettings},
+        skellam, ScoreMatrix,
+    },
+    models::{DataEntry, Match},
+    Predictor,
+};
+
+/**
+    A bivariate Poisson goal model (Karlis and Ntzoufras, 2003). The goals of both teams share
+    a common Poisson distributed component, which makes them positively correlated:
+
+    ```text
+    home goals = X1 + X3, away goals = X2 + X3
+    X1 ~ Poisson(home rate), X2 ~ Poisson(away rate), X3 ~ Poisson(covariance)
+    ```
+
+    **Note**:
+
+    * The expected goals of both teams are fitted by an independent Poisson model. The covariance is
+      fitted afterwards by maximum likelihood and taken from the independent rates,
+      so the expected goals stay the same.
+    * The goal margin ```X1 - X2``` doesn't depend on the covariance and is Skellam distributed,
+      the outcome probabilities are exact (see ```skellam::outcome```).
+**/
+#[derive(Clone, Debug)]
+pub struct BivariatePoisson {
+    marginal: Poisson,
+    covariance: f64,
+}
+
+/**
+    Returns the probability of the result ```home_goals``` : ```away_goals``` under a bivariate
+    Poisson distribution with the rates of the independent components and the covariance.
+**/
+pub fn bivariate_poisson(home_goals: u32, away_goals: u32, home: f64, away: f64, covariance: f64) -> f64 {
+    (0..=home_goals.min(away_goals))
+        .map(|k| {
+            poisson(home_goals - k, home) * poisson(away_goals - k, away) * poisson(k, covariance)
+        })
+        .sum()
+}
+
+impl Default for BivariatePoisson {
+    fn default() -> Self {
+        BivariatePoisson::new(PoissonSettings::default())
+    }
+}
+
+impl BivariatePoisson {
+    /// ```settings.dixon_coles``` is ignored, the covariance replaces the low score correction.
+    pub fn new(settings: PoissonSettings) -> Self {
+        BivariatePoisson {
+            marginal: Poisson::new(PoissonSettings {
+                dixon_coles: false,
+                ..settings
+            }),
+            covariance: 0f64,
+        }
+    }
+
+    /// Fits the expected goals and the covariance to the matches with result.
+    pub fn fit_matches(&mut self, matches: &[Match]) {
+        self.marginal.fit_matches(matches);
+        self.covariance = 0f64;
+        let played: Vec<(&Match, [f64; 2])> = matches
+            .iter()
+            .filter(|m| m.result.is_some())
+            .map(|m| (m, self.marginal.rates(m)))
+            .collect();
+        if played.is_empty() {
+            return;
+        }
+        // the covariance can't be larger than the expected goals of a team
+        let upper = played
+            .iter()
+            .map(|(_, r)| r[0].min(r[1]))
+            .fold(f64::MAX, f64::min)
+            * 0.99;
+        let likelihood = |covariance: f64| -> f64 {
+            played
+                .iter()
+                .map(|(m, r)| {
+                    let result = m.result.unwrap();
+                    bivariate_poisson(
+                        u32::from(result[0]),
+                        u32::from(result[1]),
+                        r[0] - covariance,
+                        r[1] - covariance,
+                        covariance,
+                    )
+                    .ln()
+                })
+                .sum()
+        };
+        let (mut lower, mut upper) = (0f64, upper);
+        let ratio = (5f64.sqrt() - 1f64) / 2f64;
+        for _ in 0..100 {
+            let a = upper - ratio * (upper - lower);
+            let b = lower + ratio * (upper - lower);
+            if likelihood(a) < likelihood(b) {
+                lower = a;
+            } else {
+                upper = b;
+            }
+        }
+        self.covariance = (lower + upper) / 2f64;
+    }
+
+    /// Returns the rates of the home, the away and the common component.
+    pub fn rates(&self, m: &Match) -> [f64; 3] {
+        let [home, away] = self.marginal.rates(m);
+        let covariance = self.covariance.min(home).min(away);
+        [home - covariance, away - covariance, covariance]
+    }
+
+    /// Returns the covariance of the home and the away goals.
+    pub fn covariance(&self) -> f64 {
+        self.covariance
+    }
+
+    /// Returns the probabilities of all scorelines up to ```max_goals``` per team.
+    pub fn score_matrix(&self, m: &Match) -> ScoreMatrix {
+        let [home, away, covariance] = self.rates(m);
+        let max = u32::from(self.marginal.settings.max_goals);
+        ScoreMatrix(
+            (0..=max)
+                .map(|h| (0..=max).map(|a| bivariate_poisson(h, a, home, away, covariance)).collect())
+                .collect(),
+        )
+    }
+}
+
+/**
+    The model ignores the input features. The predicted scores are the expected goals,
+    the outcome probabilities are exact Skellam probabilities.
+**/
+impl Predictor for BivariatePoisson {
+    fn fit(&mut self, _training_set: &[DataEntry], matches: &[Match]) {
+        self.fit_matches(matches);
+    }
+
+    fn predict_scores(&self, _entry: &DataEntry, m: &Match) -> [f64; 2] {
+        self.marginal.rates(m)
+    }
+
+    fn predict_outcome(&self, _entry: &DataEntry, m: &Match) -> [f64; 3] {
+        let [home, away, _] = self.rates(m);
+        skellam::outcome(home, away)
+    }
+
+    fn name(&self) -> String {
+        String::from("Bivariate Poisson")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{entry, played};
+
+    #[test]
+    fn without_covariance_the_scores_are_independent() {
+        let p = bivariate_poisson(2, 1, 1.5, 1.0, 0f64);
+        assert!((p - poisson(2, 1.5) * poisson(1, 1.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn the_covariance_adds_the_common_goals() {
+        let p = bivariate_poisson(1, 1, 1.0, 0.8, 0.2);
+        let expected = poisson(1, 1.0) * poisson(1, 0.8) * poisson(0, 0.2)
+            + poisson(0, 1.0) * poisson(0, 0.8) * poisson(1, 0.2);
+        assert!((p - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn correlated_scores_keep_the_expected_goals() {
+        let matches: Vec<Match> = (0..12)
+            .map(|day| {
+                let goals = (day % 4) as u8;
+                let (home, away) = if day % 2 == 0 { ("A", "B") } else { ("B", "A") };
+                played(day, home, away, [goals, goals])
+            })
+            .collect();
+        let mut model = BivariatePoisson::default();
+        model.fit_matches(&matches);
+        assert!(model.covariance() > 0f64);
+        let next = played(20, "A", "B", [0, 0]);
+        let [home, away, covariance] = model.rates(&next);
+        let goals = model.marginal.rates(&next);
+        assert!((home + covariance - goals[0]).abs() < 1e-12);
+        assert!((away + covariance - goals[1]).abs() < 1e-12);
+        let matrix = model.score_matrix(&next);
+        assert!((matrix.total() - 1f64).abs() < 1e-3);
+        let outcome = model.predict_outcome(&entry(), &next);
+        assert!((outcome.iter().sum::<f64>() - 1f64).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{models::Outcome, Markdown};
+
+/// Bivariate Poisson goal model with a covariance term for correlated scoring.
+pub mod bivariate;
+/// Independent Poisson goal model with the Dixon-Coles low score correction and time decay.
+pub mod poisson;
+/// Exact goal margin and outcome probabilities of two expected goal rates (Skellam distribution).
+pub mod skellam;
+
+/**
+    The probabilities of the scorelines of a single match, up to a maximum number of goals per team.
+    ```0[h][a]``` is the probability of the result h : a.
+
+    **Note**:
+
+    * Scorelines above the maximum are cut off, the probabilities add up to slightly less than 1.
+      ```outcome``` and ```most_likely``` only consider the scorelines in the matrix.
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreMatrix(pub Vec<Vec<f64>>);
+
+/// Returns the probability of ```k``` goals for a Poisson distributed number of goals with the mean ```rate```.
+pub fn poisson(k: u32, rate: f64) -> f64 {
+    if rate <= 0f64 {
+        return if k == 0 { 1f64 } else { 0f64 };
+    }
+    let ln_factorial: f64 = (2..=k).map(|i| f64::from(i).ln()).sum();
+    (f64::from(k) * rate.ln() - rate - ln_factorial).exp()
+}
+
+impl ScoreMatrix {
+    /// Returns the sum of all probabilities in the matrix.
+    pub fn total(&self) -> f64 {
+        self.0.iter().flat_map(|row| row.iter()).sum()
+    }
+
+    /// Returns the probabilities of a home win, a draw and an away win, normalized to add up to 1.
+    pub fn outcome(&self) -> [f64; 3] {
+        let mut outcome = [0f64; 3];
+        for (h, row) in self.0.iter().enumerate() {
+            for (a, p) in row.iter().enumerate() {
+                outcome[Outcome::from([h as u8, a as u8]).index()] += p;
+            }
+        }
+        let total = self.total();
+        if total > 0f64 {
+            for o in outcome.iter_mut() {
+                *o /= total;
+            }
+        }
+        outcome
+    }
+
+    /// Returns the most likely scoreline.
+    pub fn most_likely(&self) -> [u8; 2] {
+        let mut best = (0f64, [0, 0]);
+        for (h, row) in self.0.iter().enumerate() {
+            for (a, &p) in row.iter().enumerate() {
+                if p > best.0 {
+                    best = (p, [h as u8, a as u8]);
+                }
+            }
+        }
+        best.1
+    }
+}
+
+/// Formats ```ScoreMatrix``` into a Markdown Table, home goals in rows, away goals in columns.
+impl Markdown for ScoreMatrix {
+    fn to_table(&self) -> String {
+        let columns = self.0.first().map(|row| row.len()).unwrap_or(0);
+        let mut s = String::from("|Home \\ Away|");
+        for a in 0..columns {
+            s.push_str(&format!("{}|", a));
+        }
+        s.push_str("\n|-:|");
+        s.push_str(&"-:|".repeat(columns));
+        s.push('\n');
+        for (h, row) in self.0.iter().enumerate() {
+            s.push_str(&format!("|{}|", h));
+            for p in row {
+                s.push_str(&format!("{:.3}|", p));
+            }
+            s.push('\n');
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poisson_probabilities() {
+        assert!((poisson(0, 1.5) - (-1.5f64).exp()).abs() < 1e-12);
+        assert!((poisson(2, 1.5) - 1.125 * (-1.5f64).exp()).abs() < 1e-12);
+        assert_eq!(poisson(0, 0f64), 1f64);
+        assert_eq!(poisson(1, 0f64), 0f64);
+    }
+
+    #[test]
+    fn outcome_is_normalized_and_most_likely_is_the_largest_cell() {
+        let matrix = ScoreMatrix(vec![vec![0.2, 0.1], vec![0.3, 0.2]]);
+        assert!((matrix.total() - 0.8).abs() < 1e-12);
+        let outcome = matrix.outcome();
+        assert!((outcome[0] - 0.375).abs() < 1e-12);
+        assert!((outcome[1] - 0.5).abs() < 1e-12);
+        assert!((outcome[2] - 0.125).abs() < 1e-12);
+        assert_eq!(matrix.most_likely(), [1, 0]);
+        assert_eq!(matrix.to_table().lines().count(), 2 + 2);
+    }
+}
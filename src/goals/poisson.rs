@@ -0,0 +1,404 @@
+use crate::{
+    goals::{poisson, ScoreMatrix},
+    models::{DataEntry, Match},
+    Predictor,
+};
+use std::collections::HashMap;
+
+// the fit stops after this number of cycles or if no parameter changes more than CONVERGENCE
+const MAX_CYCLES: u32 = 200;
+const CONVERGENCE: f64 = 1e-6;
+
+/**
+    The settings of a Poisson goal model.
+
+    * ```dixon_coles```: Corrects the probabilities of the low scorelines 0 : 0, 1 : 0, 0 : 1 and 1 : 1
+      by the dependence parameter ```rho``` (Dixon and Coles, 1997).
+    * ```decay```: Weight decay per day. A match played d days before the latest training match
+      has the weight ```exp(-decay * d)```. ```0.0``` weights all matches equally.
+    * ```regularization```: Pulls the attack and defence strengths of clubs with few matches
+      towards the average (L2 penalty on the log strengths).
+    * ```max_goals```: The highest number of goals per team in the score matrix.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PoissonSettings {
+    pub dixon_coles: bool,
+    pub decay: f64,
+    pub regularization: f64,
+    pub max_goals: u8,
+}
+
+/**
+    A goal model. The goals of each team are Poisson distributed, the expected goals are
+    the product of the league average, the home advantage, the attack strength of the team
+    and the defence weakness of the opponent:
+
+    ```text
+    home goals: exp(intercept + home + attack[home team] + defence[away team])
+    away goals: exp(intercept + attack[away team] + defence[home team])
+    ```
+
+    The parameters are fitted by maximum likelihood over the weighted results.
+
+    **Note**:
+
+    * ```rho``` is fitted after the strengths, keeping them fixed. The low score correction
+      barely changes the expected goals, the two step fit saves a joint optimization.
+    * Clubs without a training match get average strengths.
+**/
+#[derive(Clone, Debug)]
+pub struct Poisson {
+    pub settings: PoissonSettings,
+    intercept: f64,
+    home: f64,
+    attack: HashMap<String, f64>,
+    defence: HashMap<String, f64>,
+    rho: f64,
+}
+
+// a parameter of the model, used by the coordinate-wise fit
+enum Parameter<'a> {
+    Intercept,
+    Home,
+    Attack(&'a str),
+    Defence(&'a str),
+}
+
+impl Default for PoissonSettings {
+    fn default() -> Self {
+        PoissonSettings {
+            dixon_coles: true,
+            decay: 0.0019,
+            regularization: 1f64,
+            max_goals: 10,
+        }
+    }
+}
+
+impl Default for Poisson {
+    fn default() -> Self {
+        Poisson::new(PoissonSettings::default())
+    }
+}
+
+impl Poisson {
+    pub fn new(settings: PoissonSettings) -> Self {
+        if settings.decay < 0f64 || settings.regularization < 0f64 {
+            panic!("decay and regularization must not be negative");
+        }
+        Poisson {
+            settings,
+            intercept: 0f64,
+            home: 0f64,
+            attack: HashMap::new(),
+            defence: HashMap::new(),
+            rho: 0f64,
+        }
+    }
+
+    /**
+        Fits the strengths, the home advantage and ```rho``` to the matches with result.
+        Previously fitted parameters are discarded.
+    **/
+    pub fn fit_matches(&mut self, matches: &[Match]) {
+        let played: Vec<&Match> = matches.iter().filter(|m| m.result.is_some()).collect();
+        *self = Poisson::new(self.settings);
+        let latest = match played.iter().map(|m| m.date).max() {
+            Some(latest) => latest,
+            None => return,
+        };
+        let weights: Vec<f64> = played
+            .iter()
+            .map(|m| {
+                let days = latest.signed_duration_since(m.date).num_seconds() as f64 / 86_400f64;
+                (-self.settings.decay * days).exp()
+            })
+            .collect();
+        let mut clubs: Vec<String> = played
+            .iter()
+            .flat_map(|m| vec![m.home.clone(), m.away.clone()])
+            .collect();
+        clubs.sort();
+        clubs.dedup();
+        for club in &clubs {
+            self.attack.insert(club.clone(), 0f64);
+            self.defence.insert(club.clone(), 0f64);
+        }
+        let goals: f64 = played
+            .iter()
+            .map(|m| f64::from(m.result.unwrap()[0]) + f64::from(m.result.unwrap()[1]))
+            .sum();
+        self.intercept = (goals / (2f64 * played.len() as f64)).max(0.1).ln();
+
+        for _ in 0..MAX_CYCLES {
+            let mut change = self.step(Parameter::Intercept, &played, &weights);
+            change = change.max(self.step(Parameter::Home, &played, &weights));
+            for club in &clubs {
+                change = change.max(self.step(Parameter::Attack(club), &played, &weights));
+                change = change.max(self.step(Parameter::Defence(club), &played, &weights));
+            }
+            if change < CONVERGENCE {
+                break;
+            }
+        }
+        if self.settings.dixon_coles {
+            self.fit_rho(&played, &weights);
+        }
+    }
+
+    /// Returns the expected goals of the home and the away team.
+    pub fn rates(&self, m: &Match) -> [f64; 2] {
+        let strength = |map: &HashMap<String, f64>, club: &str| *map.get(club).unwrap_or(&0f64);
+        [
+            (self.intercept + self.home + strength(&self.attack, &m.home) + strength(&self.defence, &m.away)).exp(),
+            (self.intercept + strength(&self.attack, &m.away) + strength(&self.defence, &m.home)).exp(),
+        ]
+    }
+
+    /// Returns the probabilities of all scorelines up to ```max_goals``` per team.
+    pub fn score_matrix(&self, m: &Match) -> ScoreMatrix {
+        let [home, away] = self.rates(m);
+        let max = u32::from(self.settings.max_goals);
+        ScoreMatrix(
+            (0..=max)
+                .map(|h| {
+                    (0..=max)
+                        .map(|a| poisson(h, home) * poisson(a, away) * tau(h, a, home, away, self.rho))
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the attack strength of a club, 1.2 scores 20% more goals than the average.
+    pub fn attack(&self, club: &str) -> f64 {
+        self.attack.get(club).unwrap_or(&0f64).exp()
+    }
+
+    /// Returns the defence weakness of a club, 0.8 concedes 20% less goals than the average.
+    pub fn defence(&self, club: &str) -> f64 {
+        self.defence.get(club).unwrap_or(&0f64).exp()
+    }
+
+    /// Returns the factor the home team's expected goals are multiplied by.
+    pub fn home_advantage(&self) -> f64 {
+        self.home.exp()
+    }
+
+    /// Returns the Dixon-Coles dependence parameter, 0.0 without correction.
+    pub fn rho(&self) -> f64 {
+        self.rho
+    }
+
+    fn value(&mut self, parameter: &Parameter) -> &mut f64 {
+        match parameter {
+            Parameter::Intercept => &mut self.intercept,
+            Parameter::Home => &mut self.home,
+            Parameter::Attack(club) => self.attack.get_mut(*club).unwrap(),
+            Parameter::Defence(club) => self.defence.get_mut(*club).unwrap(),
+        }
+    }
+
+    // a newton step on a single parameter, returns the absolute change
+    fn step(&mut self, parameter: Parameter, played: &[&Match], weights: &[f64]) -> f64 {
+        let (mut gradient, mut curvature) = (0f64, 0f64);
+        for (m, w) in played.iter().zip(weights) {
+            let (in_home, in_away) = match parameter {
+                Parameter::Intercept => (true, true),
+                Parameter::Home => (true, false),
+                Parameter::Attack(club) => (m.home == club, m.away == club),
+                Parameter::Defence(club) => (m.away == club, m.home == club),
+            };
+            if !in_home && !in_away {
+                continue;
+            }
+            let rates = self.rates(m);
+            let result = m.result.unwrap();
+            for (i, &involved) in [in_home, in_away].iter().enumerate() {
+                if involved {
+                    gradient += w * (f64::from(result[i]) - rates[i]);
+                    curvature += w * rates[i];
+                }
+            }
+        }
+        let penalty = match parameter {
+            Parameter::Attack(_) | Parameter::Defence(_) => self.settings.regularization,
+            _ => 0f64,
+        };
+        let value = self.value(&parameter);
+        gradient -= penalty * *value;
+        curvature += penalty;
+        if curvature <= 0f64 {
+            return 0f64;
+        }
+        let change = (gradient / curvature).clamp(-1f64, 1f64);
+        *value += change;
+        change.abs()
+    }
+
+    // maximizes the weighted log likelihood of the low score correction by golden section search
+    fn fit_rho(&mut self, played: &[&Match], weights: &[f64]) {
+        let rates: Vec<[f64; 2]> = played.iter().map(|m| self.rates(m)).collect();
+        // keeps all correction factors positive
+        let (mut lower, mut upper) = (-1f64, 1f64);
+        for r in &rates {
+            lower = lower.max(-1f64 / r[0]).max(-1f64 / r[1]);
+            upper = upper.min(1f64 / (r[0] * r[1]));
+        }
+        let (mut lower, mut upper) = (lower + 1e-6, upper - 1e-6);
+        let likelihood = |rho: f64| -> f64 {
+            played
+                .iter()
+                .zip(rates.iter().zip(weights))
+                .map(|(m, (r, w))| {
+                    let result = m.result.unwrap();
+                    w * tau(u32::from(result[0]), u32::from(result[1]), r[0], r[1], rho).ln()
+                })
+                .sum()
+        };
+        let ratio = (5f64.sqrt() - 1f64) / 2f64;
+        for _ in 0..100 {
+            let a = upper - ratio * (upper - lower);
+            let b = lower + ratio * (upper - lower);
+            if likelihood(a) < likelihood(b) {
+                lower = a;
+            } else {
+                upper = b;
+            }
+        }
+        self.rho = (lower + upper) / 2f64;
+    }
+}
+
+/**
+    The low score correction of Dixon and Coles. A positive ```rho``` makes 0 : 0 and 1 : 1
+    less likely and 1 : 0 and 0 : 1 more likely.
+**/
+pub fn tau(home_goals: u32, away_goals: u32, home: f64, away: f64, rho: f64) -> f64 {
+    let factor = match (home_goals, away_goals) {
+        (0, 0) => 1f64 - home * away * rho,
+        (0, 1) => 1f64 + home * rho,
+        (1, 0) => 1f64 + away * rho,
+        (1, 1) => 1f64 - rho,
+        _ => 1f64,
+    };
+    factor.max(0f64)
+}
+
+/**
+    The model ignores the input features. The predicted scores are the expected goals,
+    the outcome probabilities are summed up from the score matrix.
+**/
+impl Predictor for Poisson {
+    fn fit(&mut self, _training_set: &[DataEntry], matches: &[Match]) {
+        self.fit_matches(matches);
+    }
+
+    fn predict_scores(&self, _entry: &DataEntry, m: &Match) -> [f64; 2] {
+        self.rates(m)
+    }
+
+    fn predict_outcome(&self, _entry: &DataEntry, m: &Match) -> [f64; 3] {
+        self.score_matrix(m).outcome()
+    }
+
+    fn name(&self) -> String {
+        if self.settings.dixon_coles {
+            String::from("Dixon-Coles")
+        } else {
+            String::from("Poisson")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::played;
+
+    fn matches() -> Vec<Match> {
+        vec![
+            played(0, "A", "B", [2, 1]),
+            played(1, "C", "A", [1, 1]),
+            played(2, "B", "C", [3, 0]),
+            played(3, "A", "C", [0, 2]),
+            played(4, "B", "A", [1, 2]),
+            played(5, "C", "B", [2, 2]),
+        ]
+    }
+
+    fn fitted(dixon_coles: bool) -> Poisson {
+        let mut model = Poisson::new(PoissonSettings {
+            dixon_coles,
+            decay: 0f64,
+            ..PoissonSettings::default()
+        });
+        model.fit_matches(&matches());
+        model
+    }
+
+    #[test]
+    fn expected_goals_add_up_to_the_scored_goals() {
+        let model = fitted(false);
+        let (mut home, mut away) = (0f64, 0f64);
+        for m in matches() {
+            let rates = model.rates(&m);
+            home += rates[0];
+            away += rates[1];
+        }
+        assert!((home - 9f64).abs() < 1e-3);
+        assert!((away - 8f64).abs() < 1e-3);
+        assert!(model.home_advantage() > 1f64);
+        assert_eq!(model.rho(), 0f64);
+    }
+
+    #[test]
+    fn clubs_without_a_match_get_average_strengths() {
+        let model = fitted(true);
+        assert_eq!(model.attack("Z"), 1f64);
+        assert_eq!(model.defence("Z"), 1f64);
+        assert_eq!(model.name(), "Dixon-Coles");
+    }
+
+    #[test]
+    fn the_score_matrix_adds_up_to_nearly_one() {
+        let model = fitted(true);
+        let matrix = model.score_matrix(&played(6, "A", "B", [0, 0]));
+        assert_eq!(matrix.0.len(), 11);
+        assert!((matrix.total() - 1f64).abs() < 1e-3);
+        assert!((matrix.outcome().iter().sum::<f64>() - 1f64).abs() < 1e-12);
+    }
+
+    #[test]
+    fn a_positive_rho_moves_probability_from_draws_to_narrow_wins() {
+        assert!(tau(0, 0, 1.5, 1.0, 0.1) < 1f64);
+        assert!(tau(1, 1, 1.5, 1.0, 0.1) < 1f64);
+        assert!(tau(1, 0, 1.5, 1.0, 0.1) > 1f64);
+        assert!(tau(0, 1, 1.5, 1.0, 0.1) > 1f64);
+        assert_eq!(tau(2, 1, 1.5, 1.0, 0.1), 1f64);
+    }
+
+    #[test]
+    fn older_matches_weigh_less_with_decay() {
+        let mut matches = vec![played(0, "A", "B", [4, 0])];
+        matches.extend((1..=3).map(|day| played(day * 300, "A", "B", [0, 2])));
+        let mut decayed = Poisson::default();
+        decayed.fit_matches(&matches);
+        let mut flat = Poisson::new(PoissonSettings {
+            decay: 0f64,
+            ..PoissonSettings::default()
+        });
+        flat.fit_matches(&matches);
+        let next = played(1000, "A", "B", [0, 0]);
+        assert!(decayed.rates(&next)[0] < flat.rates(&next)[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be negative")]
+    fn decay_must_not_be_negative() {
+        Poisson::new(PoissonSettings {
+            decay: -1f64,
+            ..PoissonSettings::default()
+        });
+    }
+}
@@ -0,0 +1,105 @@
+// the sums stop at this number of terms
+const MAX_TERMS: u32 = 1000;
+const PRECISION: f64 = 1e-16;
+
+/**
+    Returns the probability that the home team wins by ```margin``` goals (negative for an away win),
+    if the home team scores ```home``` and the away team ```away``` goals on average.
+    The goals of both teams are independent and Poisson distributed, the expected goals can come from
+    any model, ie. a Poisson model or the outputs of a network.
+
+    **Example**:
+
+    ```text
+    skellam(0, 1.5, 1.0) = 0.2598 (draw)
+    skellam(1, 1.5, 1.0) = 0.2417 (home win by one goal)
+    ```
+**/
+pub fn skellam(margin: i32, home: f64, away: f64) -> f64 {
+    if home < 0f64 || away < 0f64 {
+        panic!("expected goals must not be negative");
+    }
+    if margin < 0 {
+        return skellam(-margin, away, home);
+    }
+    let k = margin as u32;
+    // sum over m of P(home = m + k) * P(away = m), each term is derived from the previous one
+    let mut term = (-home - away).exp();
+    for i in 1..=k {
+        term *= home / f64::from(i);
+    }
+    let mut sum = term;
+    let mode = (home * away).sqrt();
+    for m in 0..MAX_TERMS {
+        term *= home * away / (f64::from(m + 1) * f64::from(m + k + 1));
+        sum += term;
+        if f64::from(m) > mode && term <= sum * PRECISION {
+            break;
+        }
+    }
+    sum
+}
+
+/**
+    Returns the probabilities of the goal margins from ```-max_margin``` to ```max_margin```,
+    paired with the margin.
+**/
+pub fn margins(home: f64, away: f64, max_margin: u32) -> Vec<(i32, f64)> {
+    let max = max_margin as i32;
+    (-max..=max).map(|k| (k, skellam(k, home, away))).collect()
+}
+
+/**
+    Returns the probabilities of a home win, a draw and an away win.
+    Sums up the margins until the remaining probability is negligible.
+**/
+pub fn outcome(home: f64, away: f64) -> [f64; 3] {
+    let draw = skellam(0, home, away);
+    let tail = |goals: f64, other: f64| -> f64 {
+        let mut sum = 0f64;
+        for k in 1..=MAX_TERMS as i32 {
+            let p = skellam(k, goals, other);
+            sum += p;
+            if f64::from(k) > goals && p <= sum * PRECISION {
+                break;
+            }
+        }
+        sum
+    };
+    [tail(home, away), draw, tail(away, home)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::goals::poisson;
+
+    #[test]
+    fn margin_probabilities() {
+        assert!((skellam(0, 1.5, 1.0) - 0.2598).abs() < 1e-4);
+        assert!((skellam(1, 1.5, 1.0) - 0.2417).abs() < 1e-4);
+        assert_eq!(skellam(-1, 1.5, 1.0), skellam(1, 1.0, 1.5));
+    }
+
+    #[test]
+    fn margins_match_the_independent_scorelines() {
+        let direct: f64 = (0..40).map(|a| poisson(a + 2, 1.5) * poisson(a, 1.0)).sum();
+        assert!((skellam(2, 1.5, 1.0) - direct).abs() < 1e-12);
+        let total: f64 = margins(1.5, 1.0, 20).iter().map(|(_, p)| p).sum();
+        assert!((total - 1f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn outcome_adds_up_to_one() {
+        let outcome = outcome(1.5, 1.0);
+        assert!((outcome.iter().sum::<f64>() - 1f64).abs() < 1e-9);
+        assert!(outcome[0] > outcome[2]);
+        assert_eq!(super::outcome(0f64, 0f64), [0f64, 1f64, 0f64]);
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be negative")]
+    fn expected_goals_must_not_be_negative() {
+        skellam(0, -1f64, 1f64);
+    }
+}
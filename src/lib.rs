@@ -3,23 +3,44 @@ extern crate chrono;
 extern crate rand;
 extern crate serde;
 extern crate serde_json;
+/// Naive baseline predictors every model should beat.
+pub mod baselines;
+/// Classifiers that predict the outcome from the input features of a match.
+pub mod classifiers;
+/// Combines the predictions of several models.
+pub mod ensemble;
+/// Scores and analyses the predictions of a model.
+pub mod evaluation;
 /// Contains example implemenations of input features.
 /// All Features impl ```From<T>``` as way to creating that feature
 /// from a data set.
 pub mod features;
+/// Goal models that predict the probabilities of each scoreline.
+pub mod goals;
 /// Generators can be used to create and combine input features.
 pub mod generators;
 /// A collection of football match related models.
 pub mod models;
 /// Contains a single Feedforward Network implementation.
 pub mod neural;
+/// Rating systems that rate the strength of clubs from their results.
+pub mod ratings;
+/// Matches shared by the unit tests.
+#[cfg(test)]
+mod testing;
+/// Searches network architectures and trainer settings by time series cross validation.
+pub mod tune;
 /// A collection of useful helpers.
 pub mod utils;
 
 use chrono::{DateTime, FixedOffset};
 use generators::Generator;
-use models::{Club, Clubs, DataEntry, Match};
-use neural::nn::{HaltCondition, NN};
+use goals::skellam;
+use models::{Club, Clubs, DataEntry, Match, Outcome};
+use neural::{
+    nn::{HaltCondition, NN},
+    uncertainty::{self, Uncertainty, UncertaintyMode},
+};
 use std::{
     collections::{HashMap, HashSet},
     fmt,
@@ -51,13 +72,16 @@ pub struct Stats {
     pub games_played: [u8; 2],
 }
 
-/// The result predicted by a network.
+/// The result predicted by a model.
 #[derive(Debug)]
 pub struct Prediction {
     date: DateTime<FixedOffset>,
     teams: (String, String),
     expected_scores: (u8, u8),
     predicted_scores: (u8, u8),
+    predicted_goals: [f64; 2],
+    probabilities: [f64; 3],
+    uncertainty: Option<Uncertainty>,
 }
 
 /// A wrapper to store a vector of ```Prediction``` structs.
@@ -68,27 +92,36 @@ pub struct Predictions(Vec<Prediction>);
 pub trait Markdown {
     fn to_table(&self) -> String;
 }
-/// Trait to implement the test process of a network.
+/**
+    Trait for any model that predicts football matches. ```Guru``` trains, tests
+    and predicts with any ```Predictor```.
+
+    * ```fit```: Fits the model to a training set. ```matches``` are the matches the entries
+      were created from, in the same order. Models that only need the inputs ignore them.
+    * ```predict_scores```: Returns the expected goals for the home and the away team.
+    * ```predict_outcome```: Returns the probabilities of a home win, a draw and an away win.
+    * ```name```: A short name for reports.
+**/
+pub trait Predictor {
+    fn fit(&mut self, training_set: &[DataEntry], matches: &[Match]);
+    fn predict_scores(&self, entry: &DataEntry, m: &Match) -> [f64; 2];
+    fn predict_outcome(&self, entry: &DataEntry, m: &Match) -> [f64; 3];
+    fn name(&self) -> String;
+}
+/// Trait to implement the test process of a model.
 pub trait Testing {
     /// Any implementation should create two instances of NetworkStats
     /// and invole ```update``` after each test in the test set.
-    fn test(
+    fn test<P: Predictor + ?Sized>(
         &self,
-        net: &mut NN,
-        test_set: &[DataEntry], //&[(Vec<f64>, Vec<f64>)],
+        model: &P,
+        test_set: &[DataEntry],
         matches: &[Match],
     ) -> ([NetworkStats; 2], Predictions);
 }
-/// Trait to implement the training process of a network. 
+/// Trait to implement the training process of a model.
 pub trait Training {
-    fn train(
-        &self,
-        net: &mut NN,
-        training_set: &[DataEntry],
-        momentum: f64,
-        rate: f64,
-        halt_error: f64,
-    );
+    fn train<P: Predictor + ?Sized>(&self, model: &mut P, training_set: &[DataEntry], matches: &[Match]);
 }
 
 impl From<&[Match]> for Clubs {
@@ -127,6 +160,27 @@ impl<'a> Guru<'a> {
     pub fn new(data_set: &'a [Match]) -> Self {
         Guru { data_set }
     }
+
+    /**
+        Estimates the predictive uncertainty for each entry and attaches it to the prediction
+        at the same position. ```predictions``` must be created from ```entries```, ie. by ```test```.
+        ```level``` is the share of the samples covered by the reported interval (ie. 0.9).
+    **/
+    pub fn estimate_uncertainty(
+        &self,
+        nets: &[NN],
+        mode: UncertaintyMode,
+        level: f64,
+        entries: &[DataEntry],
+        predictions: &mut Predictions,
+    ) {
+        let ats = Stats::all_time_highest_score_in_league(&self.data_set);
+        let highest = f64::from(*ats.iter().max().unwrap()).round();
+        for (entry, prediction) in entries.iter().zip(predictions.0.iter_mut()) {
+            let samples = uncertainty::sample(nets, &entry.inputs, mode);
+            prediction.uncertainty = Some(Uncertainty::from_samples(&samples, highest, level));
+        }
+    }
 }
     /*** Returns the goal difference between
     goals shot at home for the home team at home
@@ -159,61 +213,32 @@ impl<'a> Guru<'a> {
     }
 
 impl<'a> Testing for Guru<'a> {
-    // TODO separate Display
-    fn test(
+    fn test<P: Predictor + ?Sized>(
         &self,
-        net: &mut NN,
-        test_set: &[DataEntry], //  &[(Vec<f64>, Vec<f64>)]
+        model: &P,
+        test_set: &[DataEntry],
         matches: &[Match],
     ) -> ([NetworkStats; 2], Predictions) {
-        let ats = Stats::all_time_highest_score_in_league(&self.data_set);
-        let highest = ats.iter().max().unwrap();
-        // TODO: move to caller
         let mut res_stats = NetworkStats::default();
         let mut win_stats = NetworkStats::default();
-        let test_data: Vec<(Vec<f64>, Vec<f64>)> = test_set
-            .iter()
-            .map(|e| (e.inputs.clone(), e.outputs.clone()))
-            .collect();
         let mut predictions = Predictions(Vec::new());
-        for i in 0..test_data.len() {
-            let res = net.run(&test_data[i].0);
-            let phr = (res[0] * f64::from(*highest).round()) as u8; // denormalized home result
-            let par = (res[1] * f64::from(*highest).round()) as u8; // denormalized away result
-                                                                    // assuming test else prediction
-                                                                    // TODO: move to caller
-            if matches[i].result.is_some() {
-                // Create a prediction and add it to the Predictions vector.
-                let p = Prediction {
-                    date: matches[i].date,
-                    teams: (matches[i].home.clone(), matches[i].away.clone()),
-                    expected_scores: (matches[i].result.unwrap()[0], matches[i].result.unwrap()[1]),
-                    predicted_scores: (phr, par),
-                };
-                predictions.0.push(p);
-                // result stats
-                if matches[i].result.unwrap() == [phr, par] {
-                    res_stats.update(true);
-                } else {
-                    res_stats.update(false);
-                }
-                // winner stats
-                if matches[i].result.unwrap()[0] > matches[i].result.unwrap()[1] && phr > par
-                    || matches[i].result.unwrap()[0] < matches[i].result.unwrap()[1] && phr < par
-                    || matches[i].result.unwrap()[0] == matches[i].result.unwrap()[1] && phr == par
-                {
-                    win_stats.update(true);
-                } else {
-                    win_stats.update(false);
-                }
-            } else {
-                let p = Prediction {
-                    date: matches[i].date,
-                    teams: (matches[i].home.clone(), matches[i].away.clone()),
-                    expected_scores: (0, 0),
-                    predicted_scores: (phr, par),
-                };
-                predictions.0.push(p);
+        for (entry, m) in test_set.iter().zip(matches.iter()) {
+            let scores = model.predict_scores(entry, m);
+            let predicted = [scores[0] as u8, scores[1] as u8];
+            // assuming test else prediction
+            let expected = m.result.unwrap_or([0, 0]);
+            predictions.0.push(Prediction {
+                date: m.date,
+                teams: (m.home.clone(), m.away.clone()),
+                expected_scores: (expected[0], expected[1]),
+                predicted_scores: (predicted[0], predicted[1]),
+                predicted_goals: scores,
+                probabilities: model.predict_outcome(entry, m),
+                uncertainty: None,
+            });
+            if let Some(result) = m.result {
+                res_stats.update(result == predicted);
+                win_stats.update(Outcome::from(result) == Outcome::from(predicted));
             }
         }
         ([res_stats, win_stats], predictions)
@@ -221,28 +246,59 @@ impl<'a> Testing for Guru<'a> {
 }
 
 impl<'a> Training for Guru<'a> {
-    fn train(
-        &self,
-        net: &mut NN,
-        training_set: &[DataEntry],
-        momentum: f64,
-        rate: f64,
-        halt_error: f64,
-    ) {
-        if momentum > 1.0 || rate > 1.0 {
-            panic!("invoking train(): Values for momentum and rate must be <= 1.0")
+    fn train<P: Predictor + ?Sized>(&self, model: &mut P, training_set: &[DataEntry], matches: &[Match]) {
+        model.fit(training_set, matches);
+    }
+}
+
+/**
+    A network predicts the normalized goals of both teams.
+    The outputs are denormalized by ```PredictorSettings::scale```, the training uses the
+    rate, momentum and error of the settings (see ```NN::set_predictor_settings```).
+
+    **Note**:
+
+    * The outcome probabilities treat the denormalized outputs as expected goals of independent
+      Poisson distributed scores (see ```goals::skellam::outcome```).
+**/
+impl Predictor for NN {
+    fn fit(&mut self, training_set: &[DataEntry], _matches: &[Match]) {
+        let settings = self.predictor_settings();
+        if settings.momentum > 1.0 || settings.rate > 1.0 {
+            panic!("invoking fit(): Values for momentum and rate must be <= 1.0")
         }
-        // impl Into for DataEntry
-        let test_data: Vec<(Vec<f64>, Vec<f64>)> = training_set
+        let examples: Vec<(Vec<f64>, Vec<f64>)> = training_set
             .iter()
             .map(|e| (e.inputs.clone(), e.outputs.clone()))
             .collect();
-        net.train(&test_data)
-            .halt_condition(HaltCondition::MSE(halt_error))
-            .log_interval(Some(1000))
-            .momentum(momentum)
-            .rate(rate)
-            .go();
+        let mut trainer = self.train(&examples);
+        trainer.log_interval(Some(1000)).rate(settings.rate);
+        if settings.error > 0f64 {
+            trainer.halt_condition(HaltCondition::MSE(settings.error));
+        }
+        if settings.momentum > 0f64 {
+            trainer.momentum(settings.momentum);
+        }
+        trainer.go();
+    }
+
+    fn predict_scores(&self, entry: &DataEntry, _m: &Match) -> [f64; 2] {
+        let scale = self.predictor_settings().scale;
+        let outputs = self.run(&entry.inputs);
+        [outputs[0] * scale, outputs[1] * scale]
+    }
+
+    fn predict_outcome(&self, entry: &DataEntry, m: &Match) -> [f64; 3] {
+        let scores = self.predict_scores(entry, m);
+        skellam::outcome(scores[0].max(0f64), scores[1].max(0f64))
+    }
+
+    fn name(&self) -> String {
+        let hidden: Vec<String> = self.layers[..self.layers.len() - 1]
+            .iter()
+            .map(|layer| layer.len().to_string())
+            .collect();
+        format!("Network {}", hidden.join("-"))
     }
 }
 
@@ -255,6 +311,13 @@ impl NetworkStats {
             negative,
         }
     }
+    /// Returns the share of positive tests in percent, zero if nothing was tested.
+    pub fn correct(&self) -> f64 {
+        if self.tested == 0 {
+            return 0f64;
+        }
+        self.positive as f64 * 100f64 / self.tested as f64
+    }
     pub fn update(&mut self, positive: bool) {
         self.tested += 1;
         if positive {
@@ -287,6 +350,57 @@ tested: {}, positive: {}, negative: {}, correct: {}%",
     }
 }
 
+impl Predictions {
+    /// Returns an iterator over the predictions, in the order of the tested entries.
+    pub fn iter(&self) -> std::slice::Iter<'_, Prediction> {
+        self.0.iter()
+    }
+}
+
+impl Prediction {
+    /// Returns the date of the match.
+    pub fn date(&self) -> DateTime<FixedOffset> {
+        self.date
+    }
+
+    /// Returns the home and the away team.
+    pub fn teams(&self) -> &(String, String) {
+        &self.teams
+    }
+
+    /// Returns the result of the match, ```0 : 0``` for a match without result.
+    pub fn expected_scores(&self) -> [u8; 2] {
+        [self.expected_scores.0, self.expected_scores.1]
+    }
+
+    /// Returns the predicted result, the predicted goals rounded down.
+    pub fn predicted_scores(&self) -> [u8; 2] {
+        [self.predicted_scores.0, self.predicted_scores.1]
+    }
+
+    /// Returns the probabilities of a home win, a draw and an away win given by the model.
+    pub fn probabilities(&self) -> [f64; 3] {
+        self.probabilities
+    }
+
+    /// Returns the expected goals of the home and the away team given by the model.
+    pub fn predicted_goals(&self) -> [f64; 2] {
+        self.predicted_goals
+    }
+
+    /**
+        Returns the probabilities of the goal margins from ```-max_margin``` to ```max_margin```,
+        derived from the expected goals by the Skellam distribution.
+    **/
+    pub fn margins(&self, max_margin: u32) -> Vec<(i32, f64)> {
+        skellam::margins(
+            self.predicted_goals[0].max(0f64),
+            self.predicted_goals[1].max(0f64),
+            max_margin,
+        )
+    }
+}
+
 // A prediction Displays as a single row of a markdown table.
 impl fmt::Display for Prediction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -300,16 +414,49 @@ impl fmt::Display for Prediction {
             "Expected: {} : {}",
             self.expected_scores.0, self.expected_scores.1
         )?;
+        if let Some(u) = &self.uncertainty {
+            writeln!(
+                f,
+                "Mean: {:.2} : {:.2}, Std. dev.: {:.2} : {:.2}, H/D/A: {:.2} / {:.2} / {:.2}{}",
+                u.mean[0],
+                u.mean[1],
+                u.std_dev[0],
+                u.std_dev[1],
+                u.outcome[0],
+                u.outcome[1],
+                u.outcome[2],
+                if u.is_coin_flip() { " (coin flip)" } else { "" }
+            )?;
+        }
         fmt::Result::Ok(())
     }
 }
 impl Markdown for Prediction {
     /// Formats ```Prediction``` into a Markdown Table Row
+    /// Appends the uncertainty columns, if an ```Uncertainty``` was estimated.
     fn to_table(&self) -> String {
-        format!(
+        let mut row = format!(
             "|{}|{} : {}|{}|",
             self.teams.0, self.predicted_scores.0, self.predicted_scores.1, self.teams.1
-        )
+        );
+        if let Some(u) = &self.uncertainty {
+            row.push_str(&format!(
+                "{:.2} : {:.2}|{:.2} : {:.2}|[{:.2}, {:.2}] : [{:.2}, {:.2}]|{:.2} / {:.2} / {:.2}|{}|",
+                u.mean[0],
+                u.mean[1],
+                u.std_dev[0],
+                u.std_dev[1],
+                u.interval[0].0,
+                u.interval[0].1,
+                u.interval[1].0,
+                u.interval[1].1,
+                u.outcome[0],
+                u.outcome[1],
+                u.outcome[2],
+                if u.is_coin_flip() { "coin flip" } else { "" }
+            ));
+        }
+        row
     }
 }
 
@@ -327,8 +474,19 @@ impl fmt::Display for Predictions {
 impl Markdown for Predictions {
     fn to_table(&self) -> String {
         let mut s = String::new();
-        s.push_str("|Home|Predicted result|Away|\n");
-        s.push_str("|-:|:-:|:-|\n");
+        match self.0.iter().find_map(|p| p.uncertainty.as_ref()) {
+            Some(u) => {
+                s.push_str(&format!(
+                    "|Home|Predicted result|Away|Mean|Std. dev.|{:.0}% interval|H / D / A|Note|\n",
+                    u.level * 100f64
+                ));
+                s.push_str("|-:|:-:|:-|:-:|:-:|:-:|:-:|:-|\n");
+            }
+            None => {
+                s.push_str("|Home|Predicted result|Away|\n");
+                s.push_str("|-:|:-:|:-|\n");
+            }
+        }
         for elem in self.0.iter() {
             s.push_str(&elem.to_table());
             s.push_str("\n");
@@ -427,3 +585,75 @@ impl Default for Stats {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neural::nn::PredictorSettings;
+    use testing::{fixture, played};
+
+    // always predicts a 2:1 home win
+    struct HomeWin;
+
+    impl Predictor for HomeWin {
+        fn fit(&mut self, _training_set: &[DataEntry], _matches: &[Match]) {}
+
+        fn predict_scores(&self, _entry: &DataEntry, _m: &Match) -> [f64; 2] {
+            [2.4, 1.2]
+        }
+
+        fn predict_outcome(&self, _entry: &DataEntry, _m: &Match) -> [f64; 3] {
+            [0.6, 0.25, 0.15]
+        }
+
+        fn name(&self) -> String {
+            String::from("Home Win")
+        }
+    }
+
+    fn entry() -> DataEntry {
+        DataEntry {
+            inputs: vec![0.5, 0.5],
+            outputs: vec![],
+        }
+    }
+
+    #[test]
+    fn tests_count_results_and_winners_of_played_matches() {
+        let matches = vec![
+            played(0, "A", "B", [2, 1]),
+            played(1, "B", "C", [3, 0]),
+            played(2, "C", "A", [0, 0]),
+            fixture(3, "A", "C"),
+        ];
+        let entries = vec![entry(); 4];
+        let guru = Guru::new(&matches);
+        let ([results, winners], predictions) = guru.test(&HomeWin, &entries, &matches);
+        assert_eq!((results.tested, results.positive), (3, 1));
+        assert_eq!((winners.tested, winners.positive), (3, 2));
+        assert_eq!(predictions.0.len(), 4);
+        assert_eq!(predictions.0[3].predicted_scores, (2, 1));
+        assert_eq!(predictions.0[3].probabilities, [0.6, 0.25, 0.15]);
+    }
+
+    #[test]
+    fn networks_scale_their_outputs_into_goals() {
+        let mut net = NN::new(&[2, 3, 2]);
+        let m = fixture(0, "A", "B");
+        let unscaled = net.predict_scores(&entry(), &m);
+        net.set_predictor_settings(PredictorSettings {
+            scale: 4f64,
+            ..PredictorSettings::default()
+        });
+        let scaled = net.predict_scores(&entry(), &m);
+        assert!((scaled[0] - 4f64 * unscaled[0]).abs() < 1e-12);
+        assert!((net.predict_outcome(&entry(), &m).iter().sum::<f64>() - 1f64).abs() < 1e-6);
+        assert_eq!(net.name(), "Network 3");
+    }
+
+    #[test]
+    fn nothing_tested_is_zero_percent_correct() {
+        assert_eq!(NetworkStats::default().correct(), 0f64);
+        assert_eq!(NetworkStats::new(4, 3, 1).correct(), 75f64);
+    }
+}
@@ -22,7 +22,7 @@ pub struct Clubs {
 /**
 One item of a training, test or prediction set, that consists of a set of input features and output features.
 **/
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct DataEntry {
     pub inputs: Vec<f64>,
     pub outputs: Vec<f64>,
@@ -53,6 +53,16 @@ pub struct Sets<'a> {
     pub data: Vec<Vec<Match>>
 }
 /**
+The outcome of a match seen from the home team.
+The index of an outcome is the position of its probability in H/D/A triples ```[f64; 3]```.
+**/
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Outcome {
+    Home,
+    Draw,
+    Away,
+}
+/**
 Can be used to mark either the scoring of the home team or the away team.
 **/
 #[derive(Debug, Eq, Hash, PartialEq)]
@@ -99,13 +109,34 @@ impl Match {
     }
 }
 
+impl Outcome {
+    /// Returns 0 for a home win, 1 for a draw and 2 for an away win.
+    pub fn index(self) -> usize {
+        match self {
+            Outcome::Home => 0,
+            Outcome::Draw => 1,
+            Outcome::Away => 2,
+        }
+    }
+}
+
+impl From<[u8; 2]> for Outcome {
+    fn from(result: [u8; 2]) -> Self {
+        match result[0].cmp(&result[1]) {
+            std::cmp::Ordering::Greater => Outcome::Home,
+            std::cmp::Ordering::Equal => Outcome::Draw,
+            std::cmp::Ordering::Less => Outcome::Away,
+        }
+    }
+}
+
 impl <'a>Sets<'a> {
     pub fn new(original: Option<&'a[Match]>, data: Vec<Vec<Match>>) -> Self {
         Sets { original, data }
     }
 }
 
-fn deserialize_from_str<'de, D>(deserializer: D) -> Result<DateTime<FixedOffset>, D::Error>
+pub(crate) fn deserialize_from_str<'de, D>(deserializer: D) -> Result<DateTime<FixedOffset>, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -114,7 +145,7 @@ where
         .map_err(de::Error::custom)
 }
 
-fn serialize_to_str<S: Serializer>(
+pub(crate) fn serialize_to_str<S: Serializer>(
     date: &DateTime<FixedOffset>,
     serializer: S,
 ) -> Result<S::Ok, S::Error> {
@@ -0,0 +1,181 @@
+use crate::{
+    models::{DataEntry, Outcome},
+    neural::nn::NN,
+};
+use std::fmt;
+
+/**
+    Describes how closely a student network tracks its teacher ensemble on a set of entries.
+
+    * ```mse```: Mean squared error between the raw student and teacher outputs.
+    * ```goal_mae```: Mean absolute difference in goals for the home and the away team.
+    * ```outcome_agreement```: Share of the entries, where student and teacher predict the same
+      outcome (home win, draw, away win).
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fidelity {
+    pub entries: usize,
+    pub mse: f64,
+    pub goal_mae: [f64; 2],
+    pub outcome_agreement: f64,
+}
+
+/**
+    Returns the soft targets of a teacher ensemble for a single input:
+    The average of the outputs of all teacher networks.
+**/
+pub fn soft_targets(teachers: &[NN], inputs: &[f64]) -> Vec<f64> {
+    if teachers.is_empty() {
+        panic!("at least one teacher network is required");
+    }
+    let mut targets = teachers[0].run(inputs);
+    for teacher in &teachers[1..] {
+        for (t, o) in targets.iter_mut().zip(teacher.run(inputs)) {
+            *t += o;
+        }
+    }
+    for t in targets.iter_mut() {
+        *t /= teachers.len() as f64;
+    }
+    targets
+}
+
+/**
+    Creates the training set for a student network.
+
+    * Labeled entries (ie. the training set) get the soft targets blended with their actual result:
+      ```alpha * soft + (1 - alpha) * actual```
+    * Unlabeled entries (ie. matches without result) get the soft targets only.
+
+    **Rationale**:
+
+    The averaged outputs of the ensemble carry more information than the actual result alone.
+    A match that ended 1 : 0, but was a coin flip for the ensemble, teaches the student to be
+    less certain. Fixtures without a result add inputs the student would not see otherwise.
+**/
+pub fn distill_set(
+    teachers: &[NN],
+    labeled: &[DataEntry],
+    unlabeled: &[DataEntry],
+    alpha: f64,
+) -> Vec<DataEntry> {
+    if !(0f64..=1f64).contains(&alpha) {
+        panic!("alpha must be in the range [0, 1]");
+    }
+    let mut set: Vec<DataEntry> = labeled
+        .iter()
+        .map(|e| {
+            let soft = soft_targets(teachers, &e.inputs);
+            let outputs = soft
+                .iter()
+                .zip(e.outputs.iter())
+                .map(|(s, a)| alpha * s + (1f64 - alpha) * a)
+                .collect();
+            DataEntry {
+                inputs: e.inputs.clone(),
+                outputs,
+            }
+        })
+        .collect();
+    set.extend(unlabeled.iter().map(|e| DataEntry {
+        inputs: e.inputs.clone(),
+        outputs: soft_targets(teachers, &e.inputs),
+    }));
+    set
+}
+
+impl Fidelity {
+    /**
+        Compares the student to the teacher ensemble on the inputs of ```entries```.
+        ```scale``` denormalizes the outputs into goals, as in ```Guru::test```.
+    **/
+    pub fn measure(teachers: &[NN], student: &NN, entries: &[DataEntry], scale: f64) -> Self {
+        let mut mse = 0f64;
+        let mut goal_mae = [0f64; 2];
+        let mut agreements = 0u32;
+        for e in entries {
+            let teacher = soft_targets(teachers, &e.inputs);
+            let student = student.run(&e.inputs);
+            for i in 0..=1 {
+                mse += (teacher[i] - student[i]).powi(2) / 2f64;
+                goal_mae[i] += ((teacher[i] - student[i]) * scale).abs();
+            }
+            let t = [(teacher[0] * scale) as u8, (teacher[1] * scale) as u8];
+            let s = [(student[0] * scale) as u8, (student[1] * scale) as u8];
+            if Outcome::from(t) == Outcome::from(s) {
+                agreements += 1;
+            }
+        }
+        let n = if entries.is_empty() { 1f64 } else { entries.len() as f64 };
+        Fidelity {
+            entries: entries.len(),
+            mse: mse / n,
+            goal_mae: [goal_mae[0] / n, goal_mae[1] / n],
+            outcome_agreement: f64::from(agreements) / n,
+        }
+    }
+}
+
+impl fmt::Display for Fidelity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "entries: {}, mse: {:.5}, goal mae: {:.2} : {:.2}, outcome agreement: {:.0}%",
+            self.entries,
+            self.mse,
+            self.goal_mae[0],
+            self.goal_mae[1],
+            self.outcome_agreement * 100f64
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(inputs: Vec<f64>, outputs: Vec<f64>) -> DataEntry {
+        DataEntry { inputs, outputs }
+    }
+
+    #[test]
+    fn soft_targets_are_the_mean_of_the_teachers() {
+        let teachers = vec![NN::new(&[2, 3, 2]), NN::new(&[2, 3, 2]), NN::new(&[2, 4, 2])];
+        let inputs = [0.3, 0.7];
+        let targets = soft_targets(&teachers, &inputs);
+        for (i, t) in targets.iter().enumerate() {
+            let mean = teachers.iter().map(|net| net.run(&inputs)[i]).sum::<f64>() / 3f64;
+            assert!((t - mean).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn labeled_entries_blend_soft_and_actual_targets() {
+        let teachers = vec![NN::new(&[2, 3, 2])];
+        let labeled = vec![entry(vec![0.1, 0.9], vec![1f64, 0f64])];
+        let unlabeled = vec![entry(vec![0.5, 0.5], vec![])];
+        let set = distill_set(&teachers, &labeled, &unlabeled, 0.25);
+        assert_eq!(set.len(), 2);
+        let soft = soft_targets(&teachers, &labeled[0].inputs);
+        assert!((set[0].outputs[0] - (0.25 * soft[0] + 0.75)).abs() < 1e-12);
+        assert!((set[0].outputs[1] - 0.25 * soft[1]).abs() < 1e-12);
+        assert_eq!(set[1].outputs, soft_targets(&teachers, &unlabeled[0].inputs));
+    }
+
+    #[test]
+    #[should_panic]
+    fn alpha_above_one_panics() {
+        distill_set(&[NN::new(&[2, 3, 2])], &[], &[], 1.5);
+    }
+
+    #[test]
+    fn a_single_teacher_is_tracked_perfectly_by_itself() {
+        let teacher = NN::new(&[2, 3, 2]);
+        let entries = vec![entry(vec![0.2, 0.4], vec![]), entry(vec![0.9, 0.1], vec![])];
+        let fidelity = Fidelity::measure(std::slice::from_ref(&teacher), &teacher, &entries, 10f64);
+        assert_eq!(fidelity.entries, 2);
+        assert!(fidelity.mse.abs() < 1e-12);
+        assert!(fidelity.goal_mae.iter().all(|e| e.abs() < 1e-12));
+        assert!((fidelity.outcome_agreement - 1f64).abs() < 1e-12);
+    }
+}
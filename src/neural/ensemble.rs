@@ -0,0 +1,360 @@
+use crate::{
+    goals::skellam,
+    models::{DataEntry, Match, Outcome},
+    neural::nn::NN,
+    Predictor,
+};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    thread_rng, Rng,
+};
+use rustc_serialize::json;
+
+/**
+    How the members of a ```NetworkEnsemble``` are trained.
+
+    * ```Bagging```: Each member is trained on a bootstrap sample of the training set
+      (drawn with replacement, as large as the training set). All members weigh the same.
+    * ```Boosting```: AdaBoost.R2 (Drucker, 1997). Each member is trained on a sample drawn by the
+      weights of the examples. Examples the member predicts badly weigh more for the next member,
+      better members weigh more in the ensemble.
+**/
+#[derive(Debug, Copy, Clone, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum Aggregation {
+    Bagging,
+    Boosting,
+}
+
+/**
+    The out-of-bag estimate of a bagged ensemble. Each training example is predicted by the members
+    that were trained without it.
+
+    * ```error```: The mean squared error of the (normalized) outputs per example.
+    * ```winners```: The share of correctly predicted winners (or draws) in percent.
+    * ```covered```: The number of examples left out by at least one member.
+**/
+#[derive(Debug, Copy, Clone, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct OutOfBag {
+    pub error: f64,
+    pub winners: f64,
+    pub covered: usize,
+}
+
+/**
+    An ensemble of networks, trained by bagging or boosting. The predicted goals are the mean of the
+    members (bagging) or their weighted median (boosting, as AdaBoost.R2 combines its members), the
+    outcome probabilities follow from them as for a single network.
+
+    **Example**:
+
+    ```text
+    let members = (0..5).map(|_| new_network()).collect();
+    let mut ensemble = NetworkEnsemble::new(Aggregation::Bagging, members);
+    guru.train(&mut ensemble, &training_set, &matches);
+    println!("{:?}", ensemble.out_of_bag());
+    ```
+
+    **Note**:
+
+    * Boosting stops adding members as soon as a member's weighted loss reaches 0.5,
+      the ensemble may hold fewer members than it was created with.
+**/
+#[derive(Debug, Clone, RustcDecodable, RustcEncodable)]
+pub struct NetworkEnsemble {
+    pub aggregation: Aggregation,
+    pub members: Vec<NN>,
+    weights: Vec<f64>,
+    out_of_bag: Option<OutOfBag>,
+}
+
+impl NetworkEnsemble {
+    /// Creates an ensemble of untrained networks, their predictor settings are used for training.
+    pub fn new(aggregation: Aggregation, members: Vec<NN>) -> Self {
+        if members.is_empty() {
+            panic!("an ensemble needs at least one network");
+        }
+        let weights = vec![1f64; members.len()];
+        NetworkEnsemble {
+            aggregation,
+            members,
+            weights,
+            out_of_bag: None,
+        }
+    }
+
+    /// Returns the weight of each member.
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    /// Returns the out-of-bag estimate, ```None``` unless the ensemble was trained by bagging.
+    pub fn out_of_bag(&self) -> Option<OutOfBag> {
+        self.out_of_bag
+    }
+
+    /// Encodes the ensemble as a JSON string.
+    pub fn to_json(&self) -> String {
+        json::encode(self).expect("encoding JSON failed")
+    }
+
+    /// Builds an ensemble from a JSON string.
+    pub fn from_json(encoded: &str) -> NetworkEnsemble {
+        let ensemble: NetworkEnsemble = json::decode(encoded).expect("decoding JSON failed");
+        ensemble
+    }
+
+    // trains each member on a bootstrap sample, the left out examples estimate the error
+    fn bag(&mut self, training_set: &[DataEntry], matches: &[Match]) {
+        let n = training_set.len();
+        let mut rng = thread_rng();
+        let mut left_out: Vec<Vec<usize>> = vec![vec![]; n];
+        for (m, member) in self.members.iter_mut().enumerate() {
+            println!("Training Bagged Network {} of {}...", m + 1, self.weights.len());
+            let sample: Vec<usize> = (0..n).map(|_| rng.gen_range(0, n)).collect();
+            let mut drawn = vec![false; n];
+            for &i in &sample {
+                drawn[i] = true;
+            }
+            for (i, _) in drawn.iter().enumerate().filter(|(_, d)| !**d) {
+                left_out[i].push(m);
+            }
+            fit_sample(member, training_set, matches, &sample);
+        }
+        let (mut error, mut correct, mut covered) = (0f64, 0usize, 0usize);
+        for (i, members) in left_out.iter().enumerate().filter(|(_, m)| !m.is_empty()) {
+            let entry = &training_set[i];
+            let outputs: Vec<f64> = (0..entry.outputs.len())
+                .map(|o| members.iter().map(|&m| self.members[m].run(&entry.inputs)[o]).sum::<f64>() / members.len() as f64)
+                .collect();
+            error += outputs.iter().zip(&entry.outputs).map(|(p, t)| (p - t).powi(2)).sum::<f64>()
+                / outputs.len().max(1) as f64;
+            if let Some(result) = matches.get(i).and_then(|m| m.result) {
+                let scale = self.members[members[0]].predictor_settings().scale;
+                let predicted = [(outputs[0] * scale) as u8, (outputs[1] * scale) as u8];
+                if Outcome::from(predicted) == Outcome::from(result) {
+                    correct += 1;
+                }
+            }
+            covered += 1;
+        }
+        self.out_of_bag = if covered > 0 {
+            Some(OutOfBag {
+                error: error / covered as f64,
+                winners: correct as f64 * 100f64 / covered as f64,
+                covered,
+            })
+        } else {
+            None
+        };
+    }
+
+    // AdaBoost.R2 with a linear loss
+    fn boost(&mut self, training_set: &[DataEntry], matches: &[Match]) {
+        let n = training_set.len();
+        let mut rng = thread_rng();
+        let mut example_weights = vec![1f64 / n as f64; n];
+        let size = self.members.len();
+        let mut members = vec![];
+        let mut weights = vec![];
+        for (m, mut member) in self.members.drain(..).enumerate() {
+            println!("Training Boosted Network {} of {}...", m + 1, size);
+            let distribution = WeightedIndex::new(&example_weights).unwrap();
+            let sample: Vec<usize> = (0..n).map(|_| distribution.sample(&mut rng)).collect();
+            fit_sample(&mut member, training_set, matches, &sample);
+            let errors: Vec<f64> = training_set
+                .iter()
+                .map(|e| member.run(&e.inputs).iter().zip(&e.outputs).map(|(p, t)| (p - t).abs()).sum())
+                .collect();
+            let max = errors.iter().cloned().fold(0f64, f64::max);
+            let losses: Vec<f64> = errors.iter().map(|e| if max > 0f64 { e / max } else { 0f64 }).collect();
+            let loss: f64 = losses.iter().zip(&example_weights).map(|(l, w)| l * w).sum();
+            if loss >= 0.5 && !members.is_empty() {
+                println!("Boosting stopped, the weighted loss of network {} is {:.3}", m + 1, loss);
+                break;
+            }
+            let beta = (loss / (1f64 - loss)).max(1e-10);
+            for (w, l) in example_weights.iter_mut().zip(&losses) {
+                *w *= beta.powf(1f64 - l);
+            }
+            let total: f64 = example_weights.iter().sum();
+            for w in example_weights.iter_mut() {
+                *w /= total;
+            }
+            members.push(member);
+            weights.push((1f64 / beta).ln().max(1e-10));
+        }
+        self.members = members;
+        self.weights = weights;
+        self.out_of_bag = None;
+    }
+}
+
+// the smallest value whose weight and the weights of all smaller values reach half of the total weight
+fn weighted_median(mut values: Vec<(f64, f64)>) -> f64 {
+    values.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let half = values.iter().map(|(_, w)| w).sum::<f64>() / 2f64;
+    let mut cumulative = 0f64;
+    for (v, w) in &values {
+        cumulative += w;
+        if cumulative >= half {
+            return *v;
+        }
+    }
+    values.last().map(|(v, _)| *v).unwrap_or(0f64)
+}
+
+// fits a network to the examples at the sample indices
+fn fit_sample(member: &mut NN, training_set: &[DataEntry], matches: &[Match], sample: &[usize]) {
+    let entries: Vec<DataEntry> = sample.iter().map(|&i| training_set[i].clone()).collect();
+    let sampled: Vec<Match> = sample.iter().filter_map(|&i| matches.get(i).cloned()).collect();
+    member.fit(&entries, &sampled);
+}
+
+/**
+    Trains the members by bagging or boosting, ```matches``` are only used for the out-of-bag winners.
+**/
+impl Predictor for NetworkEnsemble {
+    fn fit(&mut self, training_set: &[DataEntry], matches: &[Match]) {
+        if training_set.is_empty() {
+            return;
+        }
+        match self.aggregation {
+            Aggregation::Bagging => self.bag(training_set, matches),
+            Aggregation::Boosting => self.boost(training_set, matches),
+        }
+    }
+
+    fn predict_scores(&self, entry: &DataEntry, m: &Match) -> [f64; 2] {
+        let predictions: Vec<[f64; 2]> =
+            self.members.iter().map(|member| member.predict_scores(entry, m)).collect();
+        let mut scores = [0f64; 2];
+        for (o, score) in scores.iter_mut().enumerate() {
+            let values: Vec<(f64, f64)> = predictions.iter().zip(&self.weights).map(|(p, w)| (p[o], *w)).collect();
+            *score = match self.aggregation {
+                Aggregation::Bagging => {
+                    let total: f64 = self.weights.iter().sum();
+                    values.iter().map(|(v, w)| v * w / total).sum()
+                }
+                Aggregation::Boosting => weighted_median(values),
+            };
+        }
+        scores
+    }
+
+    fn predict_outcome(&self, entry: &DataEntry, m: &Match) -> [f64; 3] {
+        let scores = self.predict_scores(entry, m);
+        skellam::outcome(scores[0].max(0f64), scores[1].max(0f64))
+    }
+
+    fn name(&self) -> String {
+        let aggregation = match self.aggregation {
+            Aggregation::Bagging => "Bagged",
+            Aggregation::Boosting => "Boosted",
+        };
+        format!("{} Networks ({})", aggregation, self.members.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, played};
+
+    fn networks(n: usize) -> Vec<NN> {
+        (0..n).map(|_| NN::new(&[2, 3, 2])).collect()
+    }
+
+    // the inputs decide the result: home wins, draws and away wins
+    fn training() -> (Vec<DataEntry>, Vec<Match>) {
+        let results = [[2, 0], [1, 1], [0, 2], [3, 1], [0, 0], [1, 3], [2, 1], [1, 2]];
+        let entries = results
+            .iter()
+            .map(|r| DataEntry {
+                inputs: vec![f64::from(r[0]) / 3f64, f64::from(r[1]) / 3f64],
+                outputs: vec![f64::from(r[0]) / 10f64, f64::from(r[1]) / 10f64],
+            })
+            .collect();
+        let matches = results
+            .iter()
+            .enumerate()
+            .map(|(day, r)| played(day as i64, "A", "B", *r))
+            .collect();
+        (entries, matches)
+    }
+
+    fn entry() -> DataEntry {
+        DataEntry {
+            inputs: vec![0.5, 0.2],
+            outputs: vec![],
+        }
+    }
+
+    #[test]
+    fn bagging_averages_the_members() {
+        let members = networks(3);
+        let m = fixture(0, "A", "B");
+        let expected: Vec<[f64; 2]> = members.iter().map(|n| n.predict_scores(&entry(), &m)).collect();
+        let ensemble = NetworkEnsemble::new(Aggregation::Bagging, members);
+        let scores = ensemble.predict_scores(&entry(), &m);
+        for o in 0..2 {
+            let mean = expected.iter().map(|s| s[o]).sum::<f64>() / 3f64;
+            assert!((scores[o] - mean).abs() < 1e-12);
+        }
+        assert!((ensemble.predict_outcome(&entry(), &m).iter().sum::<f64>() - 1f64).abs() < 1e-6);
+        assert_eq!(ensemble.name(), "Bagged Networks (3)");
+    }
+
+    #[test]
+    fn boosting_takes_the_median_of_the_members() {
+        let members = networks(3);
+        let m = fixture(0, "A", "B");
+        let mut home: Vec<f64> = members.iter().map(|n| n.predict_scores(&entry(), &m)[0]).collect();
+        home.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let ensemble = NetworkEnsemble::new(Aggregation::Boosting, members);
+        assert_eq!(ensemble.predict_scores(&entry(), &m)[0], home[1]);
+        assert_eq!(ensemble.name(), "Boosted Networks (3)");
+    }
+
+    #[test]
+    fn bagging_estimates_the_out_of_bag_error() {
+        let (entries, matches) = training();
+        let mut ensemble = NetworkEnsemble::new(Aggregation::Bagging, networks(5));
+        assert_eq!(ensemble.out_of_bag(), None);
+        ensemble.fit(&entries, &matches);
+        let estimate = ensemble.out_of_bag().unwrap();
+        assert!(estimate.covered > 0 && estimate.covered <= entries.len());
+        assert!(estimate.error >= 0f64);
+        assert!(estimate.winners >= 0f64 && estimate.winners <= 100f64);
+        assert_eq!(ensemble.weights(), &[1f64; 5]);
+    }
+
+    #[test]
+    fn boosting_weighs_the_members_it_keeps() {
+        let (entries, matches) = training();
+        let mut ensemble = NetworkEnsemble::new(Aggregation::Boosting, networks(3));
+        ensemble.fit(&entries, &matches);
+        assert!(!ensemble.members.is_empty() && ensemble.members.len() <= 3);
+        assert_eq!(ensemble.weights().len(), ensemble.members.len());
+        assert!(ensemble.weights().iter().all(|w| *w > 0f64));
+        assert_eq!(ensemble.out_of_bag(), None);
+        let loaded = NetworkEnsemble::from_json(&ensemble.to_json());
+        let m = fixture(0, "A", "B");
+        let (a, b) = (loaded.predict_scores(&entry(), &m), ensemble.predict_scores(&entry(), &m));
+        assert!((a[0] - b[0]).abs() < 1e-9 && (a[1] - b[1]).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one network")]
+    fn an_ensemble_has_members() {
+        NetworkEnsemble::new(Aggregation::Bagging, vec![]);
+    }
+
+    #[test]
+    fn weighted_median_reaches_half_of_the_weight() {
+        assert_eq!(weighted_median(vec![(3f64, 1f64), (1f64, 1f64), (2f64, 1f64)]), 2f64);
+        // a heavy member outweighs the others
+        assert_eq!(weighted_median(vec![(1f64, 1f64), (2f64, 1f64), (9f64, 3f64)]), 9f64);
+        // an outlier doesn't move the median
+        assert_eq!(weighted_median(vec![(1f64, 1f64), (1.2, 1f64), (50f64, 1f64)]), 1.2);
+    }
+}
@@ -1 +1,9 @@
+/// Distills an ensemble of networks into a single student network.
+pub mod distill;
+/// Bagging and boosting ensembles of networks, saved as a single file.
+pub mod ensemble;
 pub mod nn;
+/// Prunes unimportant connections and hidden nodes and compacts the network.
+pub mod prune;
+/// Estimates the predictive uncertainty of networks by Monte Carlo dropout or deep ensembles.
+pub mod uncertainty;
@@ -62,17 +62,23 @@ on October 11, 2019 - Latest commit 26aff11 on May 8, 2015
 //! }
 //! ```
 
-use rand::Rng;
+use rand::{seq::SliceRandom, Rng};
 use rustc_serialize::json;
 use std::iter::{Enumerate, Zip};
 use std::slice;
 use std::time::{Duration, Instant};
 use HaltCondition::{Epochs, Timer, MSE};
-use LearningMode::Incremental;
+use LearningMode::{Incremental, MiniBatch};
 
 const DEFAULT_LEARNING_RATE: f64 = 0.3f64;
 const DEFAULT_MOMENTUM: f64 = 0f64;
 const DEFAULT_EPOCHS: u32 = 1000;
+const DEFAULT_BATCH_SIZE: usize = 8;
+const NORM_EPSILON: f64 = 1e-5;
+const NORM_MOMENTUM: f64 = 0.1;
+// networks with normalization layers train for this number of epochs by default,
+// and stop at it if they don't reach the target error
+const NORM_EPOCHS: u32 = 20_000;
 
 /// Specifies when to stop training the network
 #[derive(Debug, Copy, Clone)]
@@ -90,6 +96,67 @@ pub enum HaltCondition {
 pub enum LearningMode {
     /// train the network Incrementally (updates weights after each example)
     Incremental,
+    /// train the network in mini-batches of the given size (updates weights after each batch
+    /// with the averaged weight updates). The examples are shuffled in each epoch.
+    /// Networks with normalization layers are always trained in mini-batches, `Incremental`
+    /// then means batches of one example (layer normalization only)
+    /// or of eight examples (batch normalization).
+    MiniBatch(u32),
+}
+
+/// Specifies the kind of a normalization layer
+#[derive(Debug, Copy, Clone, PartialEq, RustcDecodable, RustcEncodable)]
+pub enum NormKind {
+    /// [Batch normalization](https://en.wikipedia.org/wiki/Batch_normalization):
+    /// normalizes each node by the mean and variance over a mini-batch while training.
+    /// Running averages of these statistics are used when the network is run.
+    Batch,
+    /// Layer normalization: normalizes the nodes of a layer by their mean and variance
+    /// within a single example, the same way while training and running the network.
+    Layer,
+}
+
+/// A normalization layer. Normalizes the values it receives and scales and shifts them
+/// by the learned parameters `gamma` and `beta`, one per node.
+#[derive(Debug, Clone, RustcDecodable, RustcEncodable)]
+pub struct Norm {
+    pub kind: NormKind,
+    pub gamma: Vec<f64>,
+    pub beta: Vec<f64>,
+    pub running_mean: Vec<f64>,
+    pub running_var: Vec<f64>,
+}
+
+/// Settings used when the network is fitted and run as a `Predictor`.
+#[derive(Debug, Copy, Clone, PartialEq, RustcDecodable, RustcEncodable)]
+pub struct PredictorSettings {
+    /// the learning rate used when training
+    pub rate: f64,
+    /// the momentum used when training, `0.0` trains without momentum
+    pub momentum: f64,
+    /// training halts at this mean squared error, `0.0` trains for the default number of epochs
+    pub error: f64,
+    /// the number of goals an output of `1.0` stands for
+    pub scale: f64,
+}
+
+impl Default for PredictorSettings {
+    fn default() -> Self {
+        PredictorSettings {
+            rate: DEFAULT_LEARNING_RATE,
+            momentum: DEFAULT_MOMENTUM,
+            error: 0f64,
+            scale: 1f64,
+        }
+    }
+}
+
+// intermediate values of a normalization layer in a mini-batch, required for backpropagation
+struct NormCache {
+    normalized: Vec<Vec<f64>>,
+    inv_std: Vec<f64>,
+    mean: Vec<f64>,
+    var: Vec<f64>,
 }
 
 /// Used to specify options that dictate how a network will be trained
@@ -101,6 +168,7 @@ pub struct Trainer<'a, 'b> {
     log_interval: Option<u32>,
     halt_condition: HaltCondition,
     learning_mode: LearningMode,
+    mask: Option<&'b [Vec<Vec<bool>>]>,
     nn: &'a mut NN,
 }
 
@@ -166,6 +234,14 @@ impl<'a, 'b> Trainer<'a, 'b> {
         self
     }
 
+    /// Restricts the training to the weights marked `true` in `mask`, which has
+    /// the same layout as `layers`. Weights marked `false` keep their value, ie. pruned
+    /// weights stay zero while the network is fine-tuned.
+    pub fn mask(&mut self, mask: &'b [Vec<Vec<bool>>]) -> &mut Trainer<'a, 'b> {
+        self.mask = Some(mask);
+        self
+    }
+
     /// When `go` is called, the network will begin training based on the
     /// options specified. If `go` does not get called, the network will not
     /// get trained!
@@ -176,6 +252,8 @@ impl<'a, 'b> Trainer<'a, 'b> {
             self.momentum,
             self.log_interval,
             self.halt_condition,
+            self.learning_mode,
+            self.mask,
         )
     }
 }
@@ -185,6 +263,11 @@ impl<'a, 'b> Trainer<'a, 'b> {
 pub struct NN {
     pub layers: Vec<Vec<Vec<f64>>>,
     num_inputs: u32,
+    // normalization layers in front of each layer, `None` if the network has none
+    // (an `Option` keeps networks saved without normalization layers decodable)
+    norms: Option<Vec<Option<Norm>>>,
+    // settings of the network as a `Predictor`, `None` for the defaults
+    predictor: Option<PredictorSettings>,
 }
 
 impl NN {
@@ -232,7 +315,50 @@ impl NN {
         NN {
             layers,
             num_inputs: first_layer_size,
+            norms: None,
+            predictor: None,
+        }
+    }
+
+    /// Places a normalization layer in front of the layer at `position` in `layers`.
+    /// The position is counted like the `layers_sizes` in `new`: `0` normalizes the inputs,
+    /// `1` the outputs of the first hidden layer, and so on. The outputs of the
+    /// output layer can't be normalized.
+    pub fn add_norm(&mut self, position: usize, kind: NormKind) {
+        let layer_count = self.layers.len();
+        if position >= layer_count {
+            panic!("a normalization layer must be placed in front of a hidden or the output layer");
+        }
+        let size = if position == 0 {
+            self.num_inputs as usize
+        } else {
+            self.layers[position - 1].len()
+        };
+        let norms = self.norms.get_or_insert_with(|| vec![None; layer_count]);
+        norms[position] = Some(Norm::new(kind, size));
+    }
+
+    /// Returns the normalization layer in front of the layer at `position`, if any.
+    pub fn norm(&self, position: usize) -> Option<&Norm> {
+        self.norms.as_ref().and_then(|norms| norms[position].as_ref())
+    }
+
+    /// Returns true, if the network has at least one normalization layer.
+    pub fn has_norms(&self) -> bool {
+        (0..self.layers.len()).any(|position| self.norm(position).is_some())
+    }
+
+    /// Sets the trainer settings and the output scale used by the network as a `Predictor`.
+    pub fn set_predictor_settings(&mut self, settings: PredictorSettings) {
+        if settings.scale <= 0f64 {
+            panic!("the output scale must be a positive number");
         }
+        self.predictor = Some(settings);
+    }
+
+    /// Returns the settings used by the network as a `Predictor`.
+    pub fn predictor_settings(&self) -> PredictorSettings {
+        self.predictor.unwrap_or_default()
     }
 
     /// Runs the network on an input and returns a vector of the results.
@@ -246,18 +372,56 @@ impl NN {
         self.do_run(inputs).pop().unwrap()
     }
 
+    /// Runs the network like `run`, but drops each hidden node with the probability `rate`
+    /// and scales the remaining hidden activations by `1 / (1 - rate)` (inverted dropout).
+    /// The output layer is never dropped. Repeated calls yield a distribution of outputs
+    /// that is used for Monte Carlo dropout.
+    pub fn run_dropout<R: Rng>(&self, inputs: &[f64], rate: f64, rng: &mut R) -> Vec<f64> {
+        if inputs.len() as u32 != self.num_inputs {
+            panic!("input has a different length than the network's input layer");
+        }
+        if !(0f64..1f64).contains(&rate) {
+            panic!("the dropout rate must be in the range [0, 1)");
+        }
+        let output_layer = self.layers.len() - 1;
+        let mut values = inputs.to_vec();
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            if let Some(norm) = self.norm(layer_index) {
+                values = norm.run(&values);
+            }
+            let mut layer_results = Vec::new();
+            for node in layer.iter() {
+                let result = sigmoid(modified_dotprod(node, &values));
+                if layer_index == output_layer {
+                    layer_results.push(result);
+                } else if rng.gen::<f64>() < rate {
+                    layer_results.push(0f64);
+                } else {
+                    layer_results.push(result / (1f64 - rate));
+                }
+            }
+            values = layer_results;
+        }
+        values
+    }
+
     /// Takes in vector of examples and returns a `Trainer` struct that is used
     /// to specify options that dictate how the training should proceed.
     /// No actual training will occur until the `go()` method on the
     /// `Trainer` struct is called.
+    /// Networks with normalization layers train for 20,000 epochs by default, as their mini-batches
+    /// make fewer updates per epoch. With an `MSE` halt condition they stop after 20,000 epochs,
+    /// the error is measured with the running statistics after each epoch.
     pub fn train<'b>(&'b mut self, examples: &'b [(Vec<f64>, Vec<f64>)]) -> Trainer {
+        let epochs = if self.has_norms() { NORM_EPOCHS } else { DEFAULT_EPOCHS };
         Trainer {
             examples,
             rate: DEFAULT_LEARNING_RATE,
             momentum: DEFAULT_MOMENTUM,
             log_interval: None,
-            halt_condition: Epochs(DEFAULT_EPOCHS),
+            halt_condition: Epochs(epochs),
             learning_mode: Incremental,
+            mask: None,
             nn: self,
         }
     }
@@ -273,6 +437,7 @@ impl NN {
         network
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn train_details(
         &mut self,
         examples: &[(Vec<f64>, Vec<f64>)],
@@ -280,6 +445,8 @@ impl NN {
         momentum: f64,
         log_interval: Option<u32>,
         halt_condition: HaltCondition,
+        learning_mode: LearningMode,
+        mask: Option<&[Vec<Vec<bool>>]>,
     ) -> f64 {
         // check that input and output sizes are correct
         let input_layer_size = self.num_inputs;
@@ -293,7 +460,26 @@ impl NN {
             }
         }
 
-        self.train_incremental(examples, rate, momentum, log_interval, halt_condition)
+        let has_batch_norm = (0..self.layers.len())
+            .any(|position| self.norm(position).map(|n| n.kind) == Some(NormKind::Batch));
+        let batch_size = match learning_mode {
+            MiniBatch(size) => Some(size as usize),
+            Incremental if has_batch_norm => Some(DEFAULT_BATCH_SIZE),
+            Incremental if self.has_norms() => Some(1),
+            Incremental => None,
+        };
+        match batch_size {
+            Some(size) => {
+                if size < 1 {
+                    panic!("a mini-batch must contain at least one example");
+                }
+                if has_batch_norm && size < 2 {
+                    panic!("batch normalization requires mini-batches of at least two examples");
+                }
+                self.train_mini_batch(examples, rate, momentum, log_interval, halt_condition, mask, size)
+            }
+            None => self.train_incremental(examples, rate, momentum, log_interval, halt_condition, mask),
+        }
     }
 
     fn train_incremental(
@@ -303,6 +489,7 @@ impl NN {
         momentum: f64,
         log_interval: Option<u32>,
         halt_condition: HaltCondition,
+        mask: Option<&[Vec<Vec<bool>>]>,
     ) -> f64 {
         let mut prev_deltas = self.make_weights_tracker(0.0f64);
         let mut epochs = 0u32;
@@ -310,33 +497,10 @@ impl NN {
         let start_time = Instant::now();
 
         loop {
-            if epochs > 0 {
-                // log error rate if necessary
-                match log_interval {
-                    Some(interval) if epochs % interval == 0 => {
-                        println!("error rate: {}", training_error_rate);
-                    }
-                    _ => (),
-                }
-
-                // check if we've met the halt condition yet
-                match halt_condition {
-                    Epochs(epochs_halt) => {
-                        if epochs == epochs_halt {
-                            break;
-                        }
-                    }
-                    MSE(target_error) => {
-                        if training_error_rate <= target_error {
-                            break;
-                        }
-                    }
-                    Timer(duration) => {
-                        if start_time.elapsed() >= duration {
-                            break;
-                        }
-                    }
-                }
+            if epochs > 0
+                && halted(epochs, training_error_rate, log_interval, halt_condition, start_time)
+            {
+                break;
             }
 
             training_error_rate = 0f64;
@@ -345,7 +509,85 @@ impl NN {
                 let results = self.do_run(&inputs);
                 let weight_updates = self.calculate_weight_updates(&results, &targets);
                 training_error_rate += calculate_error(&results, &targets);
-                self.update_weights(&weight_updates, &mut prev_deltas, rate, momentum)
+                self.update_weights(&weight_updates, &mut prev_deltas, rate, momentum, mask)
+            }
+
+            epochs += 1;
+        }
+
+        training_error_rate
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn train_mini_batch(
+        &mut self,
+        examples: &[(Vec<f64>, Vec<f64>)],
+        rate: f64,
+        momentum: f64,
+        log_interval: Option<u32>,
+        halt_condition: HaltCondition,
+        mask: Option<&[Vec<Vec<bool>>]>,
+        batch_size: usize,
+    ) -> f64 {
+        let mut rng = rand::thread_rng();
+        let mut prev_deltas = self.make_weights_tracker(0.0f64);
+        let mut prev_norm_deltas: Vec<Option<(Vec<f64>, Vec<f64>)>> = (0..self.layers.len())
+            .map(|position| {
+                self.norm(position)
+                    .map(|n| (vec![0f64; n.gamma.len()], vec![0f64; n.beta.len()]))
+            })
+            .collect();
+        let mut order: Vec<usize> = (0..examples.len()).collect();
+        let mut epochs = 0u32;
+        let mut training_error_rate = 0f64;
+        let start_time = Instant::now();
+
+        loop {
+            if epochs > 0
+                && halted(epochs, training_error_rate, log_interval, halt_condition, start_time)
+            {
+                break;
+            }
+            if let MSE(target_error) = halt_condition {
+                if self.has_norms() && epochs == NORM_EPOCHS {
+                    println!(
+                        "training stopped after {} epochs, the error rate {} didn't reach {}",
+                        epochs, training_error_rate, target_error
+                    );
+                    break;
+                }
+            }
+
+            training_error_rate = 0f64;
+            order.shuffle(&mut rng);
+
+            for batch in order.chunks(batch_size) {
+                let batch: Vec<&(Vec<f64>, Vec<f64>)> = batch.iter().map(|&i| &examples[i]).collect();
+                let (error, weight_updates, norm_updates, caches) = self.calculate_batch_updates(&batch);
+                training_error_rate += error;
+                self.update_weights(&weight_updates, &mut prev_deltas, rate, momentum, mask);
+                if let Some(norms) = self.norms.as_mut() {
+                    for (position, norm) in norms.iter_mut().enumerate() {
+                        if let (Some(norm), Some((gamma_updates, beta_updates)), Some((prev_gamma, prev_beta))) = (
+                            norm.as_mut(),
+                            &norm_updates[position],
+                            prev_norm_deltas[position].as_mut(),
+                        ) {
+                            norm.update(gamma_updates, beta_updates, prev_gamma, prev_beta, rate, momentum);
+                            if let Some(cache) = &caches[position] {
+                                norm.update_running(cache);
+                            }
+                        }
+                    }
+                }
+            }
+            // the error of each batch depends on the statistics of the batch, the network is
+            // judged the way it is run: with the running statistics
+            if self.has_norms() {
+                training_error_rate = examples
+                    .iter()
+                    .map(|(inputs, targets)| calculate_error(&self.do_run(inputs), targets))
+                    .sum();
             }
 
             epochs += 1;
@@ -354,19 +596,133 @@ impl NN {
         training_error_rate
     }
 
-    fn do_run(&self, inputs: &[f64]) -> Vec<Vec<f64>> {
+    pub(crate) fn do_run(&self, inputs: &[f64]) -> Vec<Vec<f64>> {
         let mut results = Vec::new();
         results.push(inputs.to_vec());
         for (layer_index, layer) in self.layers.iter().enumerate() {
+            let normalized;
+            let layer_inputs = match self.norm(layer_index) {
+                Some(norm) => {
+                    normalized = norm.run(&results[layer_index]);
+                    &normalized
+                }
+                None => &results[layer_index],
+            };
             let mut layer_results = Vec::new();
             for node in layer.iter() {
-                layer_results.push(sigmoid(modified_dotprod(&node, &results[layer_index])))
+                layer_results.push(sigmoid(modified_dotprod(&node, layer_inputs)))
             }
             results.push(layer_results);
         }
         results
     }
 
+    // runs a mini-batch through the network in training mode and calculates the averaged
+    // weight updates, the updates of the normalization layers and the summed error
+    #[allow(clippy::type_complexity)]
+    fn calculate_batch_updates(
+        &self,
+        batch: &[&(Vec<f64>, Vec<f64>)],
+    ) -> (
+        f64,
+        Vec<Vec<Vec<f64>>>,
+        Vec<Option<(Vec<f64>, Vec<f64>)>>,
+        Vec<Option<NormCache>>,
+    ) {
+        let size = batch.len() as f64;
+        // outputs of each layer, starting with the inputs
+        let mut activations: Vec<Vec<Vec<f64>>> = vec![batch.iter().map(|e| e.0.clone()).collect()];
+        // values each layer receives, after normalization
+        let mut layer_inputs: Vec<Vec<Vec<f64>>> = Vec::new();
+        let mut caches: Vec<Option<NormCache>> = Vec::new();
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            let (values, cache) = match self.norm(layer_index) {
+                Some(norm) => {
+                    let cache = norm.train(&activations[layer_index]);
+                    (norm.scale(&cache.normalized), Some(cache))
+                }
+                None => (activations[layer_index].clone(), None),
+            };
+            let outputs = values
+                .iter()
+                .map(|v| layer.iter().map(|node| sigmoid(modified_dotprod(node, v))).collect())
+                .collect();
+            layer_inputs.push(values);
+            caches.push(cache);
+            activations.push(outputs);
+        }
+
+        let outputs = &activations[self.layers.len()];
+        let mut error = 0f64;
+        let mut node_errors: Vec<Vec<f64>> = Vec::new();
+        for (results, example) in outputs.iter().zip(batch.iter()) {
+            error += calculate_error(slice::from_ref(results), &example.1);
+            node_errors.push(
+                results
+                    .iter()
+                    .zip(example.1.iter())
+                    .map(|(&result, &target)| result * (1f64 - result) * (target - result))
+                    .collect(),
+            );
+        }
+
+        let mut weight_updates = self.make_weights_tracker(0f64);
+        let mut norm_updates: Vec<Option<(Vec<f64>, Vec<f64>)>> = vec![None; self.layers.len()];
+        for layer_index in (0..self.layers.len()).rev() {
+            let layer = &self.layers[layer_index];
+            for (b, values) in layer_inputs[layer_index].iter().enumerate() {
+                for (node_index, updates) in weight_updates[layer_index].iter_mut().enumerate() {
+                    let node_error = node_errors[b][node_index];
+                    updates[0] += node_error / size;
+                    for (value_index, value) in values.iter().enumerate() {
+                        updates[value_index + 1] += node_error * value / size;
+                    }
+                }
+            }
+            if layer_index == 0 && self.norm(0).is_none() {
+                break;
+            }
+            // errors of the values this layer received
+            let mut value_errors: Vec<Vec<f64>> = node_errors
+                .iter()
+                .map(|errors| {
+                    (0..layer_inputs[layer_index][0].len())
+                        .map(|value_index| {
+                            layer
+                                .iter()
+                                .zip(errors.iter())
+                                .map(|(node, error)| node[value_index + 1] * error)
+                                .sum()
+                        })
+                        .collect()
+                })
+                .collect();
+            if let (Some(norm), Some(cache)) = (self.norm(layer_index), &caches[layer_index]) {
+                let (errors, gamma_updates, beta_updates) = norm.backpropagate(cache, &value_errors);
+                value_errors = errors;
+                norm_updates[layer_index] = Some((
+                    gamma_updates.iter().map(|u| u / size).collect(),
+                    beta_updates.iter().map(|u| u / size).collect(),
+                ));
+            }
+            if layer_index == 0 {
+                break;
+            }
+            node_errors = value_errors
+                .iter()
+                .zip(activations[layer_index].iter())
+                .map(|(errors, results)| {
+                    errors
+                        .iter()
+                        .zip(results.iter())
+                        .map(|(error, result)| result * (1f64 - result) * error)
+                        .collect()
+                })
+                .collect();
+        }
+        (error, weight_updates, norm_updates, caches)
+    }
+
     // updates all weights in the network
     fn update_weights(
         &mut self,
@@ -374,6 +730,7 @@ impl NN {
         prev_deltas: &mut Vec<Vec<Vec<f64>>>,
         rate: f64,
         momentum: f64,
+        mask: Option<&[Vec<Vec<bool>>]>,
     ) {
         for layer_index in 0..self.layers.len() {
             let layer = &mut self.layers[layer_index];
@@ -382,6 +739,11 @@ impl NN {
                 let node = &mut layer[node_index];
                 let node_weight_updates = &layer_weight_updates[node_index];
                 for weight_index in 0..node.len() {
+                    if let Some(mask) = mask {
+                        if !mask[layer_index][node_index][weight_index] {
+                            continue;
+                        }
+                    }
                     let weight_update = node_weight_updates[weight_index];
                     let prev_delta = prev_deltas[layer_index][node_index][weight_index];
                     let delta = (rate * weight_update) + (momentum * prev_delta);
@@ -393,7 +755,7 @@ impl NN {
     }
 
     // calculates all weight updates by backpropagation
-    fn calculate_weight_updates(
+    pub(crate) fn calculate_weight_updates(
         &self,
         results: &[Vec<f64>],
         targets: &[f64],
@@ -471,6 +833,206 @@ impl NN {
     }
 }
 
+impl Norm {
+    fn new(kind: NormKind, size: usize) -> Norm {
+        Norm {
+            kind,
+            gamma: vec![1f64; size],
+            beta: vec![0f64; size],
+            running_mean: vec![0f64; size],
+            running_var: vec![1f64; size],
+        }
+    }
+
+    /// Normalizes the values of a single example, the way the network is run.
+    pub fn run(&self, values: &[f64]) -> Vec<f64> {
+        match self.kind {
+            NormKind::Batch => values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let normalized = (v - self.running_mean[i]) / (self.running_var[i] + NORM_EPSILON).sqrt();
+                    self.gamma[i] * normalized + self.beta[i]
+                })
+                .collect(),
+            NormKind::Layer => {
+                let (mean, var) = moments(values);
+                let inv_std = 1f64 / (var + NORM_EPSILON).sqrt();
+                let normalized: Vec<f64> = values.iter().map(|v| (v - mean) * inv_std).collect();
+                self.scale(&[normalized]).pop().unwrap()
+            }
+        }
+    }
+
+    // normalizes a mini-batch with the statistics of the batch (batch normalization)
+    // or of each example (layer normalization)
+    fn train(&self, batch: &[Vec<f64>]) -> NormCache {
+        match self.kind {
+            NormKind::Batch => {
+                let size = self.gamma.len();
+                let columns: Vec<(f64, f64)> = (0..size)
+                    .map(|i| moments(&batch.iter().map(|v| v[i]).collect::<Vec<f64>>()))
+                    .collect();
+                let mean: Vec<f64> = columns.iter().map(|c| c.0).collect();
+                let var: Vec<f64> = columns.iter().map(|c| c.1).collect();
+                let inv_std: Vec<f64> = var.iter().map(|v| 1f64 / (v + NORM_EPSILON).sqrt()).collect();
+                let normalized = batch
+                    .iter()
+                    .map(|v| (0..size).map(|i| (v[i] - mean[i]) * inv_std[i]).collect())
+                    .collect();
+                NormCache {
+                    normalized,
+                    inv_std,
+                    mean,
+                    var,
+                }
+            }
+            NormKind::Layer => {
+                let rows: Vec<(f64, f64)> = batch.iter().map(|v| moments(v)).collect();
+                let inv_std: Vec<f64> = rows.iter().map(|r| 1f64 / (r.1 + NORM_EPSILON).sqrt()).collect();
+                let normalized = batch
+                    .iter()
+                    .zip(rows.iter().zip(inv_std.iter()))
+                    .map(|(v, (r, s))| v.iter().map(|x| (x - r.0) * s).collect())
+                    .collect();
+                NormCache {
+                    normalized,
+                    inv_std,
+                    mean: rows.iter().map(|r| r.0).collect(),
+                    var: rows.iter().map(|r| r.1).collect(),
+                }
+            }
+        }
+    }
+
+    // applies gamma and beta to normalized values
+    fn scale(&self, normalized: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        normalized
+            .iter()
+            .map(|v| {
+                v.iter()
+                    .enumerate()
+                    .map(|(i, x)| self.gamma[i] * x + self.beta[i])
+                    .collect()
+            })
+            .collect()
+    }
+
+    // propagates the errors of the scaled outputs back to the inputs of the normalization,
+    // returns the input errors and the (summed) updates for gamma and beta
+    fn backpropagate(
+        &self,
+        cache: &NormCache,
+        errors: &[Vec<f64>],
+    ) -> (Vec<Vec<f64>>, Vec<f64>, Vec<f64>) {
+        let size = self.gamma.len();
+        let mut gamma_updates = vec![0f64; size];
+        let mut beta_updates = vec![0f64; size];
+        for (e, x) in errors.iter().zip(cache.normalized.iter()) {
+            for i in 0..size {
+                gamma_updates[i] += e[i] * x[i];
+                beta_updates[i] += e[i];
+            }
+        }
+        let normalized_errors: Vec<Vec<f64>> = errors
+            .iter()
+            .map(|e| e.iter().zip(self.gamma.iter()).map(|(e, g)| e * g).collect())
+            .collect();
+        let mut input_errors = vec![vec![0f64; size]; errors.len()];
+        match self.kind {
+            NormKind::Batch => {
+                let n = errors.len() as f64;
+                for i in 0..size {
+                    let sum: f64 = normalized_errors.iter().map(|e| e[i]).sum();
+                    let dot: f64 = normalized_errors
+                        .iter()
+                        .zip(cache.normalized.iter())
+                        .map(|(e, x)| e[i] * x[i])
+                        .sum();
+                    for (b, e) in normalized_errors.iter().enumerate() {
+                        input_errors[b][i] = cache.inv_std[i] / n
+                            * (n * e[i] - sum - cache.normalized[b][i] * dot);
+                    }
+                }
+            }
+            NormKind::Layer => {
+                let n = size as f64;
+                for (b, e) in normalized_errors.iter().enumerate() {
+                    let sum: f64 = e.iter().sum();
+                    let dot: f64 = e.iter().zip(cache.normalized[b].iter()).map(|(e, x)| e * x).sum();
+                    for i in 0..size {
+                        input_errors[b][i] = cache.inv_std[b] / n
+                            * (n * e[i] - sum - cache.normalized[b][i] * dot);
+                    }
+                }
+            }
+        }
+        (input_errors, gamma_updates, beta_updates)
+    }
+
+    fn update(
+        &mut self,
+        gamma_updates: &[f64],
+        beta_updates: &[f64],
+        prev_gamma: &mut [f64],
+        prev_beta: &mut [f64],
+        rate: f64,
+        momentum: f64,
+    ) {
+        for i in 0..self.gamma.len() {
+            let delta = rate * gamma_updates[i] + momentum * prev_gamma[i];
+            self.gamma[i] += delta;
+            prev_gamma[i] = delta;
+            let delta = rate * beta_updates[i] + momentum * prev_beta[i];
+            self.beta[i] += delta;
+            prev_beta[i] = delta;
+        }
+    }
+
+    // moves the running statistics towards the statistics of a mini-batch
+    fn update_running(&mut self, cache: &NormCache) {
+        if self.kind != NormKind::Batch {
+            return;
+        }
+        for i in 0..self.gamma.len() {
+            self.running_mean[i] = (1f64 - NORM_MOMENTUM) * self.running_mean[i] + NORM_MOMENTUM * cache.mean[i];
+            self.running_var[i] = (1f64 - NORM_MOMENTUM) * self.running_var[i] + NORM_MOMENTUM * cache.var[i];
+        }
+    }
+}
+
+// checks the halt condition and logs the error rate if necessary
+fn halted(
+    epochs: u32,
+    training_error_rate: f64,
+    log_interval: Option<u32>,
+    halt_condition: HaltCondition,
+    start_time: Instant,
+) -> bool {
+    // log error rate if necessary
+    match log_interval {
+        Some(interval) if epochs % interval == 0 => {
+            println!("error rate: {}", training_error_rate);
+        }
+        _ => (),
+    }
+
+    // check if we've met the halt condition yet
+    match halt_condition {
+        Epochs(epochs_halt) => epochs == epochs_halt,
+        MSE(target_error) => training_error_rate <= target_error,
+        Timer(duration) => start_time.elapsed() >= duration,
+    }
+}
+
+// mean and (biased) variance of a set of values
+fn moments(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, var)
+}
+
 fn modified_dotprod(node: &[f64], values: &[f64]) -> f64 {
     let mut it = node.iter();
     let mut total = *it.next().unwrap(); // start with the threshold weight
@@ -480,7 +1042,7 @@ fn modified_dotprod(node: &[f64], values: &[f64]) -> f64 {
     total
 }
 
-fn sigmoid(y: f64) -> f64 {
+pub(crate) fn sigmoid(y: f64) -> f64 {
     1f64 / (1f64 + (-y).exp())
 }
 
@@ -502,3 +1064,151 @@ fn calculate_error(results: &[Vec<f64>], targets: &[f64]) -> f64 {
     }
     total / (last_results.len() as f64)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn examples() -> Vec<(Vec<f64>, Vec<f64>)> {
+        vec![
+            (vec![0f64, 1f64], vec![0.2]),
+            (vec![1f64, 0f64], vec![0.8]),
+            (vec![0.5, 0.5], vec![0.5]),
+            (vec![1f64, 1f64], vec![0.4]),
+        ]
+    }
+
+    // the loss the batch updates descend: half the squared error, averaged over the batch
+    fn batch_loss(net: &NN, batch: &[&(Vec<f64>, Vec<f64>)]) -> f64 {
+        let outputs = net.layers[net.layers.len() - 1].len() as f64;
+        net.calculate_batch_updates(batch).0 * outputs / (2f64 * batch.len() as f64)
+    }
+
+    // the central difference of the loss when a parameter is changed by ```change```
+    fn gradient(net: &NN, batch: &[&(Vec<f64>, Vec<f64>)], change: impl Fn(&mut NN, f64)) -> f64 {
+        let h = 1e-6;
+        let mut plus = net.clone();
+        change(&mut plus, h);
+        let mut minus = net.clone();
+        change(&mut minus, -h);
+        (batch_loss(&plus, batch) - batch_loss(&minus, batch)) / (2f64 * h)
+    }
+
+    fn norm_mut(net: &mut NN, position: usize) -> &mut Norm {
+        net.norms.as_mut().unwrap()[position].as_mut().unwrap()
+    }
+
+    // compares the updates of a mini-batch with finite differences of the loss
+    fn check_gradients(norms: &[(usize, NormKind)]) {
+        let mut net = NN::new(&[2, 3, 2]);
+        for &(position, kind) in norms {
+            net.add_norm(position, kind);
+        }
+        // gamma and beta away from their initial values
+        for norm in net.norms.as_mut().unwrap().iter_mut().flatten() {
+            for (i, (gamma, beta)) in norm.gamma.iter_mut().zip(norm.beta.iter_mut()).enumerate() {
+                *gamma = 0.8 + 0.3 * i as f64;
+                *beta = 0.1 - 0.2 * i as f64;
+            }
+        }
+        let examples = [
+            (vec![0f64, 1f64], vec![0.2, 0.6]),
+            (vec![1f64, 0f64], vec![0.8, 0.1]),
+            (vec![0.5, 0.7], vec![0.5, 0.3]),
+            (vec![0.9, 0.2], vec![0.4, 0.9]),
+        ];
+        let batch: Vec<&(Vec<f64>, Vec<f64>)> = examples.iter().collect();
+        let (_, weight_updates, norm_updates, _) = net.calculate_batch_updates(&batch);
+        // the updates point against the gradient
+        for (l, layer) in weight_updates.iter().enumerate() {
+            for (n, node) in layer.iter().enumerate() {
+                for (w, update) in node.iter().enumerate() {
+                    let numeric = gradient(&net, &batch, |net, h| net.layers[l][n][w] += h);
+                    assert!((update + numeric).abs() < 1e-7, "weight {}/{}/{}", l, n, w);
+                }
+            }
+        }
+        for &(position, _) in norms {
+            let (gamma_updates, beta_updates) = norm_updates[position].as_ref().unwrap();
+            for i in 0..gamma_updates.len() {
+                let numeric = gradient(&net, &batch, |net, h| norm_mut(net, position).gamma[i] += h);
+                assert!((gamma_updates[i] + numeric).abs() < 1e-7, "gamma {}/{}", position, i);
+                let numeric = gradient(&net, &batch, |net, h| norm_mut(net, position).beta[i] += h);
+                assert!((beta_updates[i] + numeric).abs() < 1e-7, "beta {}/{}", position, i);
+            }
+        }
+    }
+
+    #[test]
+    fn batch_norm_updates_follow_the_gradient() {
+        check_gradients(&[(0, NormKind::Batch), (1, NormKind::Batch)]);
+    }
+
+    #[test]
+    fn layer_norm_updates_follow_the_gradient() {
+        check_gradients(&[(1, NormKind::Layer)]);
+    }
+
+    #[test]
+    fn mixed_norm_updates_follow_the_gradient() {
+        check_gradients(&[(0, NormKind::Batch), (1, NormKind::Layer)]);
+    }
+
+    #[test]
+    fn norms_are_sized_by_the_layer_they_normalize() {
+        let mut net = NN::new(&[2, 3, 1]);
+        assert!(!net.has_norms());
+        net.add_norm(0, NormKind::Batch);
+        net.add_norm(1, NormKind::Layer);
+        assert!(net.has_norms());
+        assert_eq!(net.norm(0).unwrap().gamma.len(), 2);
+        assert_eq!(net.norm(1).unwrap().kind, NormKind::Layer);
+        assert_eq!(net.norm(1).unwrap().beta.len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "in front of a hidden or the output layer")]
+    fn the_outputs_cant_be_normalized() {
+        NN::new(&[2, 3, 1]).add_norm(2, NormKind::Layer);
+    }
+
+    #[test]
+    fn layer_norm_normalizes_each_example() {
+        let norm = Norm::new(NormKind::Layer, 3);
+        let (mean, var) = moments(&norm.run(&[1f64, 2f64, 6f64]));
+        assert!(mean.abs() < 1e-9);
+        assert!((var - 1f64).abs() < 1e-4);
+    }
+
+    #[test]
+    fn batch_norm_runs_with_the_running_statistics() {
+        let mut norm = Norm::new(NormKind::Batch, 1);
+        norm.running_mean = vec![2f64];
+        norm.running_var = vec![4f64];
+        norm.gamma = vec![3f64];
+        norm.beta = vec![1f64];
+        assert!((norm.run(&[4f64])[0] - 4f64).abs() < 1e-4);
+    }
+
+    #[test]
+    fn trains_with_norms_and_survives_a_json_round_trip() {
+        let mut net = NN::new(&[2, 3, 1]);
+        net.add_norm(0, NormKind::Batch);
+        net.add_norm(1, NormKind::Layer);
+        net.train(&examples())
+            .halt_condition(Epochs(20))
+            .learning_mode(MiniBatch(2))
+            .go();
+        let loaded = NN::from_json(&net.to_json());
+        assert!((loaded.run(&[0.3, 0.6])[0] - net.run(&[0.3, 0.6])[0]).abs() < 1e-12);
+        assert_ne!(net.norm(0).unwrap().running_mean, vec![0f64; 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "mini-batches of at least two examples")]
+    fn batch_norm_needs_two_examples_per_mini_batch() {
+        let mut net = NN::new(&[2, 3, 1]);
+        net.add_norm(1, NormKind::Batch);
+        net.train(&examples()).halt_condition(Epochs(1)).learning_mode(MiniBatch(1)).go();
+    }
+}
@@ -0,0 +1,423 @@
+use crate::{
+    neural::nn::{sigmoid, HaltCondition, NN},
+    Markdown, NetworkStats,
+};
+
+/**
+    Selects how the importance of a connection or a hidden node is measured.
+
+    * ```Magnitude```: The absolute value of a weight. A hidden node is as important
+      as the sum of its absolute outgoing weights.
+    * ```Sensitivity```: The change of the error caused by a weight (first order estimate
+      ```|w * dE/dw|```, summed over the examples). A hidden node is as important as the increase
+      of the mean squared error, if it is removed from the network.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Criterion {
+    Magnitude,
+    Sensitivity,
+}
+
+/**
+    One step of a pruning run. Describes size and accuracy of the compacted network.
+
+    * ```pruned```: Share of the connections of the original network that were removed.
+    * ```parameters```: Remaining weights, including the thresholds.
+    * ```hidden```: Number of nodes in each hidden layer.
+    * ```result```, ```winner```: Correct results and winners on the test set in %.
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct PruneStep {
+    pub pruned: f64,
+    pub parameters: usize,
+    pub hidden: Vec<usize>,
+    pub training_mse: f64,
+    pub test_mse: f64,
+    pub result: usize,
+    pub winner: usize,
+}
+
+/// The steps of a pruning run, starting with the unpruned network.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PruneReport(pub Vec<PruneStep>);
+
+/**
+    Returns a mask with the layout of ```net.layers```. Weights that are in use are marked
+    ```true```, pruned weights (exactly zero) are marked ```false```. Thresholds are always in use.
+    Can be passed to ```Trainer::mask``` to fine-tune the network without reviving pruned weights.
+**/
+pub fn mask(net: &NN) -> Vec<Vec<Vec<bool>>> {
+    net.layers
+        .iter()
+        .map(|layer| {
+            layer
+                .iter()
+                .map(|node| {
+                    node.iter()
+                        .enumerate()
+                        .map(|(i, &w)| i == 0 || w != 0f64)
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the number of connections (weights without thresholds) that are in use.
+pub fn connections(net: &NN) -> usize {
+    net.layers
+        .iter()
+        .flat_map(|layer| layer.iter())
+        .map(|node| node[1..].iter().filter(|&&w| w != 0f64).count())
+        .sum()
+}
+
+/// Returns the number of weights in use, including the thresholds.
+pub fn parameters(net: &NN) -> usize {
+    connections(net) + net.layers.iter().map(|layer| layer.len()).sum::<usize>()
+}
+
+/// Returns the mean squared error of the network on the examples.
+pub fn mse(net: &NN, examples: &[(Vec<f64>, Vec<f64>)]) -> f64 {
+    if examples.is_empty() {
+        return 0f64;
+    }
+    let total: f64 = examples
+        .iter()
+        .map(|(inputs, targets)| {
+            let results = net.run(inputs);
+            results
+                .iter()
+                .zip(targets.iter())
+                .map(|(r, t)| (t - r).powi(2))
+                .sum::<f64>()
+                / results.len() as f64
+        })
+        .sum();
+    total / examples.len() as f64
+}
+
+/**
+    Prunes ```count``` connections that are still in use and have the lowest importance.
+    Pruned weights are set to zero. Returns the number of pruned connections.
+**/
+pub fn prune_connections(
+    net: &mut NN,
+    examples: &[(Vec<f64>, Vec<f64>)],
+    criterion: Criterion,
+    count: usize,
+) -> usize {
+    assert_prunable(net);
+    let scores = connection_scores(net, examples, criterion);
+    let mut candidates: Vec<(f64, usize, usize, usize)> = vec![];
+    for (l, layer) in net.layers.iter().enumerate() {
+        for (n, node) in layer.iter().enumerate() {
+            for (w, &weight) in node.iter().enumerate().skip(1) {
+                if weight != 0f64 {
+                    candidates.push((scores[l][n][w], l, n, w));
+                }
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let count = count.min(candidates.len());
+    for &(_, l, n, w) in candidates.iter().take(count) {
+        net.layers[l][n][w] = 0f64;
+    }
+    count
+}
+
+/**
+    Prunes ```count``` hidden nodes with the lowest importance by setting their outgoing weights
+    to zero. Each hidden layer keeps at least one node. ```compact``` removes the pruned nodes.
+    Returns the number of pruned nodes.
+**/
+pub fn prune_units(
+    net: &mut NN,
+    examples: &[(Vec<f64>, Vec<f64>)],
+    criterion: Criterion,
+    count: usize,
+) -> usize {
+    assert_prunable(net);
+    let base = mse(net, examples);
+    let mut candidates: Vec<(f64, usize, usize)> = vec![];
+    for l in 0..net.layers.len() - 1 {
+        for j in 0..net.layers[l].len() {
+            if is_dead(net, l, j) {
+                continue;
+            }
+            let score = match criterion {
+                Criterion::Magnitude => net.layers[l + 1].iter().map(|node| node[j + 1].abs()).sum(),
+                Criterion::Sensitivity => {
+                    let mut ablated = net.clone();
+                    silence(&mut ablated, l, j);
+                    mse(&ablated, examples) - base
+                }
+            };
+            candidates.push((score, l, j));
+        }
+    }
+    candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let mut pruned = 0;
+    for &(_, l, j) in candidates.iter() {
+        if pruned == count {
+            break;
+        }
+        let alive = (0..net.layers[l].len()).filter(|&k| !is_dead(net, l, k)).count();
+        if alive > 1 {
+            silence(net, l, j);
+            pruned += 1;
+        }
+    }
+    pruned
+}
+
+/**
+    Fine-tunes a pruned network for a number of epochs. Pruned weights stay zero.
+**/
+pub fn fine_tune(
+    net: &mut NN,
+    examples: &[(Vec<f64>, Vec<f64>)],
+    epochs: u32,
+    rate: f64,
+    momentum: f64,
+) {
+    let mask = mask(net);
+    net.train(examples)
+        .halt_condition(HaltCondition::Epochs(epochs))
+        .rate(rate)
+        .momentum(momentum)
+        .mask(&mask)
+        .go();
+}
+
+/**
+    Physically removes dead hidden nodes from ```net.layers```. Returns the number of removed nodes.
+    The outputs of the network do not change.
+
+    * A node without outgoing connections has no effect and is removed.
+    * A node without incoming connections has a constant output. The constant is added to the
+      thresholds of the next layer, then the node is removed.
+
+    Each hidden layer keeps at least one node. Removing a node may cause nodes in the previous
+    layer to become dead, the network is compacted until no dead node is left.
+
+    **Note**:
+
+    * Networks with normalization layers can't be pruned or compacted. A normalization layer
+      couples the nodes of a layer, removing one node changes the outputs of the others.
+**/
+pub fn compact(net: &mut NN) -> usize {
+    assert_prunable(net);
+    let mut removed = 0;
+    loop {
+        let mut changed = false;
+        for l in 0..net.layers.len() - 1 {
+            let mut j = 0;
+            while j < net.layers[l].len() && net.layers[l].len() > 1 {
+                let no_outputs = net.layers[l + 1].iter().all(|node| node[j + 1] == 0f64);
+                let no_inputs = net.layers[l][j][1..].iter().all(|&w| w == 0f64);
+                if no_outputs || no_inputs {
+                    if !no_outputs {
+                        let constant = sigmoid(net.layers[l][j][0]);
+                        for node in net.layers[l + 1].iter_mut() {
+                            node[0] += node[j + 1] * constant;
+                        }
+                    }
+                    net.layers[l].remove(j);
+                    for node in net.layers[l + 1].iter_mut() {
+                        node.remove(j + 1);
+                    }
+                    removed += 1;
+                    changed = true;
+                } else {
+                    j += 1;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    removed
+}
+
+impl PruneStep {
+    /**
+        Measures the network after a pruning step.
+        ```original``` is the number of connections of the unpruned network,
+        ```stats``` are the result and winner stats of ```Guru::test``` on the test set.
+    **/
+    pub fn measure(
+        net: &NN,
+        original: usize,
+        training: &[(Vec<f64>, Vec<f64>)],
+        test: &[(Vec<f64>, Vec<f64>)],
+        stats: &[NetworkStats; 2],
+    ) -> Self {
+        let percent = |s: &NetworkStats| (s.positive * 100).checked_div(s.tested).unwrap_or(0);
+        PruneStep {
+            pruned: 1f64 - connections(net) as f64 / original as f64,
+            parameters: parameters(net),
+            hidden: net.layers[..net.layers.len() - 1]
+                .iter()
+                .map(|layer| layer.len())
+                .collect(),
+            training_mse: mse(net, training),
+            test_mse: mse(net, test),
+            result: percent(&stats[0]),
+            winner: percent(&stats[1]),
+        }
+    }
+}
+
+/// Formats ```PruneReport``` into a Markdown Table, including header.
+impl Markdown for PruneReport {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Step|Pruned|Parameters|Hidden layers|Training MSE|Test MSE|Result|Winner|\n");
+        s.push_str("|-:|-:|-:|:-:|-:|-:|-:|-:|\n");
+        for (i, step) in self.0.iter().enumerate() {
+            let hidden: Vec<String> = step.hidden.iter().map(|h| h.to_string()).collect();
+            s.push_str(&format!(
+                "|{}|{:.0}%|{}|{}|{:.5}|{:.5}|{}%|{}%|\n",
+                i,
+                step.pruned * 100f64,
+                step.parameters,
+                hidden.join("-"),
+                step.training_mse,
+                step.test_mse,
+                step.result,
+                step.winner
+            ));
+        }
+        s
+    }
+}
+
+fn connection_scores(
+    net: &NN,
+    examples: &[(Vec<f64>, Vec<f64>)],
+    criterion: Criterion,
+) -> Vec<Vec<Vec<f64>>> {
+    let mut scores: Vec<Vec<Vec<f64>>> = net
+        .layers
+        .iter()
+        .map(|layer| {
+            layer
+                .iter()
+                .map(|node| match criterion {
+                    Criterion::Magnitude => node.iter().map(|w| w.abs()).collect(),
+                    Criterion::Sensitivity => vec![0f64; node.len()],
+                })
+                .collect()
+        })
+        .collect();
+    if criterion == Criterion::Sensitivity {
+        for (inputs, targets) in examples {
+            let results = net.do_run(inputs);
+            let updates = net.calculate_weight_updates(&results, targets);
+            for (l, layer) in net.layers.iter().enumerate() {
+                for (n, node) in layer.iter().enumerate() {
+                    for (w, weight) in node.iter().enumerate() {
+                        scores[l][n][w] += (weight * updates[l][n][w]).abs();
+                    }
+                }
+            }
+        }
+    }
+    scores
+}
+
+fn assert_prunable(net: &NN) {
+    if net.has_norms() {
+        panic!("networks with normalization layers can't be pruned");
+    }
+}
+
+// a hidden node is dead, if none of the next layer's nodes uses its output
+fn is_dead(net: &NN, layer: usize, node: usize) -> bool {
+    net.layers[layer + 1]
+        .iter()
+        .all(|next| next[node + 1] == 0f64)
+}
+
+// removes all outgoing connections of a hidden node
+fn silence(net: &mut NN, layer: usize, node: usize) {
+    for next in net.layers[layer + 1].iter_mut() {
+        next[node + 1] = 0f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neural::nn::NormKind;
+
+    fn examples() -> Vec<(Vec<f64>, Vec<f64>)> {
+        vec![
+            (vec![0f64, 1f64], vec![0.2, 0.1]),
+            (vec![1f64, 0f64], vec![0.1, 0.3]),
+            (vec![0.5, 0.5], vec![0.2, 0.2]),
+        ]
+    }
+
+    #[test]
+    fn counts_connections_and_parameters() {
+        let net = NN::new(&[2, 3, 2]);
+        assert_eq!(connections(&net), 2 * 3 + 3 * 2);
+        assert_eq!(parameters(&net), 12 + 3 + 2);
+        assert!(mask(&net).iter().flatten().flatten().all(|&used| used));
+    }
+
+    #[test]
+    fn prunes_the_smallest_weights_by_magnitude() {
+        let mut net = NN::new(&[2, 3, 2]);
+        let mut weights: Vec<f64> = net.layers.iter().flatten().flat_map(|node| node[1..].to_vec()).collect();
+        weights.sort_by(|a, b| a.abs().partial_cmp(&b.abs()).unwrap());
+        assert_eq!(prune_connections(&mut net, &examples(), Criterion::Magnitude, 4), 4);
+        assert_eq!(connections(&net), 8);
+        let kept: Vec<f64> = net.layers.iter().flatten().flat_map(|node| node[1..].to_vec()).collect();
+        for w in &weights[..4] {
+            assert!(!kept.contains(w));
+        }
+        // pruned weights are masked, thresholds are not
+        let mask = mask(&net);
+        assert_eq!(mask.iter().flatten().flatten().filter(|&&used| !used).count(), 4);
+    }
+
+    #[test]
+    fn pruned_units_keep_one_node_per_layer_and_compact_keeps_the_outputs() {
+        let mut net = NN::new(&[2, 4, 2]);
+        assert_eq!(prune_units(&mut net, &examples(), Criterion::Sensitivity, 10), 3);
+        let before: Vec<Vec<f64>> = examples().iter().map(|(inputs, _)| net.run(inputs)).collect();
+        assert_eq!(compact(&mut net), 3);
+        assert_eq!(net.layers[0].len(), 1);
+        for ((inputs, _), outputs) in examples().iter().zip(&before) {
+            for (a, b) in net.run(inputs).iter().zip(outputs) {
+                assert!((a - b).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn compact_folds_nodes_without_inputs_into_the_thresholds() {
+        let mut net = NN::new(&[2, 3, 2]);
+        for w in net.layers[0][1][1..].iter_mut() {
+            *w = 0f64;
+        }
+        let before = net.run(&[0.3, 0.6]);
+        assert_eq!(compact(&mut net), 1);
+        for (a, b) in net.run(&[0.3, 0.6]).iter().zip(&before) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "networks with normalization layers can't be pruned")]
+    fn networks_with_norms_cannot_be_pruned() {
+        let mut net = NN::new(&[2, 3, 2]);
+        net.add_norm(1, NormKind::Layer);
+        prune_connections(&mut net, &examples(), Criterion::Magnitude, 1);
+    }
+}
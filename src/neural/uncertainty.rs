@@ -0,0 +1,192 @@
+use crate::{models::Outcome, neural::nn::NN};
+use rand::thread_rng;
+
+/// Two outcomes whose probabilities differ by less than this margin make a prediction a coin flip.
+pub const COIN_FLIP_MARGIN: f64 = 0.1;
+
+/**
+    Selects how the spread of a prediction is estimated.
+
+    * ```MonteCarloDropout```: Runs ```passes``` stochastic forward passes through a single network.
+      Each pass drops hidden nodes with the probability ```rate``` (see ```NN::run_dropout```).
+    * ```DeepEnsemble```: Runs one forward pass through each member of a set of independently
+      trained networks with the same layout.
+
+    **Note**:
+
+    * The network is trained without dropout. Monte Carlo dropout therefore measures how sensitive
+      a prediction is to losing hidden nodes. A deep ensemble also captures the spread between different
+      training runs and is the better estimate, if the time to train several networks is available.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UncertaintyMode {
+    MonteCarloDropout { passes: usize, rate: f64 },
+    DeepEnsemble,
+}
+
+/**
+    The predictive uncertainty of a single fixture, derived from a set of sampled network outputs.
+    All goal values are denormalized, index 0 is the home team, index 1 the away team.
+
+    * ```mean```, ```std_dev```: Mean and standard deviation of the sampled goals.
+    * ```interval```: The central interval that holds ```level``` of the sampled goals.
+    * ```outcome```: Share of the samples that are a home win, draw or away win.
+
+    **Example**:
+
+    * 100 passes, the home goals are mostly between 1.1 and 2.3, the away goals between 0.8 and 1.9
+    * 48 passes end in a home win, 12 in a draw and 40 in an away win
+    * outcome: [0.48, 0.12, 0.40]. The prediction is a coin flip between a home and an away win.
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Uncertainty {
+    pub samples: usize,
+    pub level: f64,
+    pub mean: [f64; 2],
+    pub std_dev: [f64; 2],
+    pub interval: [(f64, f64); 2],
+    pub outcome: [f64; 3],
+}
+
+impl Uncertainty {
+    /**
+        Summarizes the sampled network outputs. ```samples``` are raw (normalized) outputs,
+        ```scale``` is the factor used to denormalize them into goals.
+        The outcome of a sample is derived from the goals the same way ```Guru::test```
+        derives the predicted scores.
+    **/
+    pub fn from_samples(samples: &[Vec<f64>], scale: f64, level: f64) -> Self {
+        if samples.is_empty() {
+            panic!("at least one sample is required to estimate the uncertainty");
+        }
+        if level <= 0f64 || level >= 1f64 {
+            panic!("the interval level must be in the range (0, 1)");
+        }
+        let n = samples.len() as f64;
+        let mut mean = [0f64; 2];
+        let mut std_dev = [0f64; 2];
+        let mut interval = [(0f64, 0f64); 2];
+        for i in 0..=1 {
+            let mut goals: Vec<f64> = samples.iter().map(|s| s[i] * scale).collect();
+            mean[i] = goals.iter().sum::<f64>() / n;
+            std_dev[i] = (goals.iter().map(|g| (g - mean[i]).powi(2)).sum::<f64>() / n).sqrt();
+            goals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let tail = (1f64 - level) / 2f64;
+            interval[i] = (quantile(&goals, tail), quantile(&goals, 1f64 - tail));
+        }
+        let mut outcome = [0f64; 3];
+        for s in samples {
+            let result = [(s[0] * scale) as u8, (s[1] * scale) as u8];
+            outcome[Outcome::from(result).index()] += 1f64;
+        }
+        for o in outcome.iter_mut() {
+            *o /= n;
+        }
+        Uncertainty {
+            samples: samples.len(),
+            level,
+            mean,
+            std_dev,
+            interval,
+            outcome,
+        }
+    }
+
+    /// Returns the probability of the most likely outcome.
+    pub fn confidence(&self) -> f64 {
+        self.outcome.iter().cloned().fold(0f64, f64::max)
+    }
+
+    /// Returns true, if the two most likely outcomes are within ```COIN_FLIP_MARGIN```.
+    pub fn is_coin_flip(&self) -> bool {
+        let mut sorted = self.outcome;
+        sorted.sort_by(|a, b| b.partial_cmp(a).unwrap());
+        sorted[0] - sorted[1] < COIN_FLIP_MARGIN
+    }
+}
+
+/**
+    Samples the raw outputs for a single input.
+    ```MonteCarloDropout``` uses the first network only, ```DeepEnsemble``` uses all networks.
+**/
+pub fn sample(nets: &[NN], inputs: &[f64], mode: UncertaintyMode) -> Vec<Vec<f64>> {
+    if nets.is_empty() {
+        panic!("at least one network is required to estimate the uncertainty");
+    }
+    match mode {
+        UncertaintyMode::MonteCarloDropout { passes, rate } => {
+            let mut rng = thread_rng();
+            (0..passes)
+                .map(|_| nets[0].run_dropout(inputs, rate, &mut rng))
+                .collect()
+        }
+        UncertaintyMode::DeepEnsemble => nets.iter().map(|net| net.run(inputs)).collect(),
+    }
+}
+
+// linear interpolation between the closest ranks of a sorted slice
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q * (sorted.len() - 1) as f64;
+    let lower = pos.floor() as usize;
+    let upper = pos.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (pos - lower as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // n samples of the (normalized) goals, scaled by 10
+    fn samples(n: usize, goals: [f64; 2]) -> Vec<Vec<f64>> {
+        vec![vec![goals[0] / 10f64, goals[1] / 10f64]; n]
+    }
+
+    #[test]
+    fn outcome_is_the_share_of_the_sampled_results() {
+        let mut all = samples(48, [2f64, 1f64]);
+        all.extend(samples(12, [1f64, 1f64]));
+        all.extend(samples(40, [1f64, 2f64]));
+        let uncertainty = Uncertainty::from_samples(&all, 10f64, 0.9);
+        assert_eq!(uncertainty.samples, 100);
+        for (o, expected) in uncertainty.outcome.iter().zip(&[0.48, 0.12, 0.40]) {
+            assert!((o - expected).abs() < 1e-12);
+        }
+        assert!((uncertainty.confidence() - 0.48).abs() < 1e-12);
+        assert!(uncertainty.is_coin_flip());
+    }
+
+    #[test]
+    fn a_clear_favourite_is_no_coin_flip() {
+        let mut all = samples(80, [2f64, 0f64]);
+        all.extend(samples(20, [0f64, 0f64]));
+        assert!(!Uncertainty::from_samples(&all, 10f64, 0.9).is_coin_flip());
+    }
+
+    #[test]
+    fn spread_of_the_goals() {
+        let all: Vec<Vec<f64>> = (0..=10).map(|i| vec![f64::from(i) / 10f64, 0.1]).collect();
+        let uncertainty = Uncertainty::from_samples(&all, 10f64, 0.8);
+        assert!((uncertainty.mean[0] - 5f64).abs() < 1e-9);
+        assert!((uncertainty.std_dev[0] - 10f64.sqrt()).abs() < 1e-9);
+        assert!((uncertainty.interval[0].0 - 1f64).abs() < 1e-9);
+        assert!((uncertainty.interval[0].1 - 9f64).abs() < 1e-9);
+        assert!((uncertainty.mean[1] - 1f64).abs() < 1e-9);
+        assert!(uncertainty.std_dev[1].abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic]
+    fn no_samples_panic() {
+        Uncertainty::from_samples(&[], 10f64, 0.9);
+    }
+
+    #[test]
+    fn one_sample_per_pass_or_network() {
+        let nets = vec![NN::new(&[2, 3, 2]), NN::new(&[2, 3, 2])];
+        let passes = sample(&nets, &[0.5, 0.5], UncertaintyMode::MonteCarloDropout { passes: 7, rate: 0.2 });
+        assert_eq!(passes.len(), 7);
+        assert!(passes.iter().all(|s| s.len() == 2));
+        let members = sample(&nets, &[0.5, 0.5], UncertaintyMode::DeepEnsemble);
+        assert_eq!(members, vec![nets[0].run(&[0.5, 0.5]), nets[1].run(&[0.5, 0.5])]);
+    }
+}
@@ -0,0 +1,317 @@
+use crate::{
+    models::{Clubs, DataEntry, Match, Outcome},
+    ratings::OutcomeFit,
+    Markdown, Predictor,
+};
+use std::collections::HashMap;
+
+// the fit stops after this number of cycles or if no parameter changes more than CONVERGENCE
+const MAX_CYCLES: u32 = 200;
+const CONVERGENCE: f64 = 1e-6;
+
+/**
+    A paired comparison model with draws (Bradley-Terry, with draws as proposed by Davidson, 1970).
+    Each club has a strength, the strength difference of a match decides the outcome probabilities:
+
+    ```text
+    x = home + league[league of the match] + strength[home team] - strength[away team]
+    P(home) = exp(x / 2) / Z, P(draw) = nu / Z, P(away) = exp(-x / 2) / Z
+    Z = exp(x / 2) + nu + exp(-x / 2)
+    ```
+
+    * ```home```: The home advantage, shared by all leagues.
+    * ```league```: An intercept per league, ie. a league with a stronger home advantage.
+    * ```nu```: The draw parameter, the higher, the more draws between even clubs.
+
+    The parameters are fitted by maximum likelihood over the results. ```regularization``` pulls
+    strengths and league intercepts of clubs and leagues with few matches towards zero.
+
+    **Example**:
+
+    ```text
+    strengths: home team 0.4, away team -0.2, home advantage 0.3, nu 0.6
+    x = 0.9: P(home) = 1.568 / 2.806 = 0.56, P(draw) = 0.21, P(away) = 0.23
+    ```
+**/
+#[derive(Clone, Debug)]
+pub struct BradleyTerry {
+    pub regularization: f64,
+    home: f64,
+    nu: f64,
+    strengths: HashMap<String, f64>,
+    leagues: HashMap<String, f64>,
+    fit: OutcomeFit,
+}
+
+/// The strength of each club, strongest club first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ranking(pub Vec<(String, f64)>);
+
+// a parameter of the model, used by the coordinate-wise fit
+enum Parameter<'a> {
+    Home,
+    Draw,
+    Strength(&'a str),
+    League(&'a str),
+}
+
+impl Default for BradleyTerry {
+    fn default() -> Self {
+        BradleyTerry::new(0.1)
+    }
+}
+
+impl BradleyTerry {
+    pub fn new(regularization: f64) -> Self {
+        if regularization < 0f64 {
+            panic!("regularization must not be negative");
+        }
+        BradleyTerry {
+            regularization,
+            home: 0f64,
+            nu: 1f64,
+            strengths: HashMap::new(),
+            leagues: HashMap::new(),
+            fit: OutcomeFit::default(),
+        }
+    }
+
+    /// Fits the model to the matches with result. Previously fitted parameters are discarded.
+    pub fn fit_matches(&mut self, matches: &[Match]) {
+        *self = BradleyTerry::new(self.regularization);
+        let played: Vec<&Match> = matches.iter().filter(|m| m.result.is_some()).collect();
+        let mut clubs: Vec<String> = played
+            .iter()
+            .flat_map(|m| vec![m.home.clone(), m.away.clone()])
+            .collect();
+        clubs.sort();
+        clubs.dedup();
+        let mut leagues: Vec<String> = played.iter().map(|m| m.league.clone()).collect();
+        leagues.sort();
+        leagues.dedup();
+        for club in &clubs {
+            self.strengths.insert(club.clone(), 0f64);
+        }
+        for league in &leagues {
+            self.leagues.insert(league.clone(), 0f64);
+        }
+        for _ in 0..MAX_CYCLES {
+            let mut change = self.step(Parameter::Home, &played);
+            change = change.max(self.step(Parameter::Draw, &played));
+            for club in &clubs {
+                change = change.max(self.step(Parameter::Strength(club), &played));
+            }
+            for league in &leagues {
+                change = change.max(self.step(Parameter::League(league), &played));
+            }
+            if change < CONVERGENCE {
+                break;
+            }
+        }
+        for m in &played {
+            let p = self.probabilities(m);
+            self.fit.add(p[0] + p[1] / 2f64, m.result.unwrap());
+        }
+    }
+
+    /// Returns the probabilities of a home win, a draw and an away win.
+    pub fn probabilities(&self, m: &Match) -> [f64; 3] {
+        let x = self.difference(m);
+        let (home, away) = ((x / 2f64).exp(), (-x / 2f64).exp());
+        let z = home + self.nu + away;
+        [home / z, self.nu / z, away / z]
+    }
+
+    /**
+        Returns the expected goals of the home and the away team, the goal difference is fitted
+        linearly to the expected result ```P(home) + P(draw) / 2```.
+    **/
+    pub fn expected_goals(&self, m: &Match) -> [f64; 2] {
+        let p = self.probabilities(m);
+        self.fit.goals(p[0] + p[1] / 2f64)
+    }
+
+    /// Returns the strength of a club, zero for an average or an unknown club.
+    pub fn strength(&self, club: &str) -> f64 {
+        *self.strengths.get(club).unwrap_or(&0f64)
+    }
+
+    /// Returns the home advantage and the intercept of each league.
+    pub fn home_advantage(&self) -> (f64, &HashMap<String, f64>) {
+        (self.home, &self.leagues)
+    }
+
+    /// Returns the draw parameter ```nu```.
+    pub fn nu(&self) -> f64 {
+        self.nu
+    }
+
+    /// Ranks all clubs by their strength, clubs without a match get the average strength.
+    pub fn ranking(&self, clubs: &Clubs) -> Ranking {
+        let mut ranking: Vec<(String, f64)> = clubs
+            .data
+            .keys()
+            .map(|club| (club.name.clone(), self.strength(&club.name)))
+            .collect();
+        ranking.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+        Ranking(ranking)
+    }
+
+    // the strength difference of a match, including the home advantage
+    fn difference(&self, m: &Match) -> f64 {
+        self.home + *self.leagues.get(&m.league).unwrap_or(&0f64) + self.strength(&m.home)
+            - self.strength(&m.away)
+    }
+
+    // a newton step on a single parameter, returns the absolute change
+    fn step(&mut self, parameter: Parameter, played: &[&Match]) -> f64 {
+        let (mut gradient, mut curvature) = (0f64, 0f64);
+        for m in played {
+            let p = self.probabilities(m);
+            let outcome = Outcome::from(m.result.unwrap());
+            let observed = |o: Outcome| if outcome == o { 1f64 } else { 0f64 };
+            if let Parameter::Draw = parameter {
+                // the parameter is ln(nu)
+                gradient += observed(Outcome::Draw) - p[1];
+                curvature += p[1] * (1f64 - p[1]);
+                continue;
+            }
+            let coefficient = match parameter {
+                Parameter::Strength(club) if m.home == club => 1f64,
+                Parameter::Strength(club) if m.away == club => -1f64,
+                Parameter::League(league) if m.league == league => 1f64,
+                Parameter::Home => 1f64,
+                _ => 0f64,
+            };
+            if coefficient == 0f64 {
+                continue;
+            }
+            let score = (observed(Outcome::Home) - observed(Outcome::Away)) / 2f64;
+            let mean = (p[0] - p[2]) / 2f64;
+            gradient += coefficient * (score - mean);
+            curvature += (p[0] + p[2]) / 4f64 - mean * mean;
+        }
+        let (value, penalty) = match parameter {
+            Parameter::Home => (self.home, 0f64),
+            Parameter::Draw => (self.nu.ln(), 0f64),
+            Parameter::Strength(club) => (self.strength(club), self.regularization),
+            Parameter::League(league) => (self.leagues[league], self.regularization),
+        };
+        gradient -= penalty * value;
+        curvature += penalty;
+        if curvature <= 0f64 {
+            return 0f64;
+        }
+        let change = (gradient / curvature).clamp(-1f64, 1f64);
+        match parameter {
+            Parameter::Home => self.home += change,
+            Parameter::Draw => self.nu = (value + change).exp(),
+            Parameter::Strength(club) => *self.strengths.get_mut(club).unwrap() += change,
+            Parameter::League(league) => *self.leagues.get_mut(league).unwrap() += change,
+        }
+        change.abs()
+    }
+}
+
+/// Formats ```Ranking``` into a Markdown Table, including header.
+impl Markdown for Ranking {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Rank|Club|Strength|\n");
+        s.push_str("|-:|:-|-:|\n");
+        for (i, (club, strength)) in self.0.iter().enumerate() {
+            s.push_str(&format!("|{}|{}|{:.3}|\n", i + 1, club, strength));
+        }
+        s
+    }
+}
+
+/**
+    The model ignores the input features. The predicted scores are the expected goals,
+    the outcome probabilities come from the strength difference.
+**/
+impl Predictor for BradleyTerry {
+    fn fit(&mut self, _training_set: &[DataEntry], matches: &[Match]) {
+        self.fit_matches(matches);
+    }
+
+    fn predict_scores(&self, _entry: &DataEntry, m: &Match) -> [f64; 2] {
+        self.expected_goals(m)
+    }
+
+    fn predict_outcome(&self, _entry: &DataEntry, m: &Match) -> [f64; 3] {
+        self.probabilities(m)
+    }
+
+    fn name(&self) -> String {
+        String::from("Bradley-Terry")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::played;
+
+    fn matches() -> Vec<Match> {
+        vec![
+            played(0, "A", "B", [2, 0]),
+            played(1, "B", "C", [1, 1]),
+            played(2, "C", "A", [0, 1]),
+            played(3, "A", "C", [3, 1]),
+            played(4, "B", "A", [1, 1]),
+            played(5, "C", "B", [2, 1]),
+        ]
+    }
+
+    #[test]
+    fn probabilities_of_the_example() {
+        let mut model = BradleyTerry {
+            home: 0.3,
+            nu: 0.6,
+            ..BradleyTerry::default()
+        };
+        model.strengths.insert(String::from("A"), 0.4);
+        model.strengths.insert(String::from("B"), -0.2);
+        let p = model.probabilities(&played(0, "A", "B", [0, 0]));
+        assert!((p[0] - 0.56).abs() < 0.005);
+        assert!((p[1] - 0.21).abs() < 0.005);
+        assert!((p[2] - 0.23).abs() < 0.005);
+    }
+
+    #[test]
+    fn the_fit_matches_the_observed_draws_and_home_wins() {
+        let matches = matches();
+        let mut model = BradleyTerry::default();
+        model.fit_matches(&matches);
+        let (mut draws, mut margin) = (0f64, 0f64);
+        for m in &matches {
+            let p = model.probabilities(m);
+            draws += p[1];
+            margin += p[0] - p[2];
+        }
+        assert!((draws - 2f64).abs() < 1e-4);
+        // 3 home wins, 1 away win
+        assert!((margin - 2f64).abs() < 1e-4);
+        assert!(model.nu() > 0f64);
+    }
+
+    #[test]
+    fn clubs_are_ranked_by_strength() {
+        let matches = matches();
+        let mut model = BradleyTerry::default();
+        model.fit_matches(&matches);
+        let clubs = Clubs::from(&matches[..]);
+        let ranking = model.ranking(&clubs);
+        assert_eq!(ranking.0[0].0, "A");
+        assert_eq!(ranking.0.len(), 3);
+        assert_eq!(model.strength("Z"), 0f64);
+        assert_eq!(ranking.to_table().lines().count(), 2 + 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "regularization must not be negative")]
+    fn regularization_must_not_be_negative() {
+        BradleyTerry::new(-0.1);
+    }
+}
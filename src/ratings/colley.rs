@@ -0,0 +1,67 @@
+use crate::{
+    models::{Match, Outcome},
+    ratings::{clubs, solve},
+};
+use std::collections::HashMap;
+
+/**
+    Returns the Colley rating of each club. The Colley method only counts wins and losses,
+    adjusted for the strength of the opponents. Goal differences don't matter.
+    A draw counts as half a win and half a loss. The ratings are around 0.5.
+
+    **Example**:
+
+    ```text
+    A beats B, B beats C, A draws C
+    Colley: A 0.6, B 0.5, C 0.4
+    ```
+**/
+pub fn colley(matches: &[Match]) -> HashMap<String, f64> {
+    let clubs = clubs(matches);
+    let index: HashMap<&String, usize> = clubs.iter().enumerate().map(|(i, c)| (c, i)).collect();
+    let n = clubs.len();
+    let mut a = vec![vec![0f64; n]; n];
+    let mut b = vec![1f64; n];
+    for (i, row) in a.iter_mut().enumerate() {
+        row[i] = 2f64;
+    }
+    for m in matches {
+        if let Some(result) = m.result {
+            let (h, w) = (index[&m.home], index[&m.away]);
+            a[h][h] += 1f64;
+            a[w][w] += 1f64;
+            a[h][w] -= 1f64;
+            a[w][h] -= 1f64;
+            let score = match Outcome::from(result) {
+                Outcome::Home => 0.5,
+                Outcome::Draw => 0f64,
+                Outcome::Away => -0.5,
+            };
+            b[h] += score;
+            b[w] -= score;
+        }
+    }
+    clubs.into_iter().zip(solve(a, b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::played;
+
+    #[test]
+    fn ratings_count_wins_and_draws() {
+        let matches = vec![played(0, "A", "B", [2, 0]), played(1, "B", "C", [1, 0]), played(2, "A", "C", [1, 1])];
+        let ratings = colley(&matches);
+        assert!((ratings["A"] - 0.6).abs() < 1e-9);
+        assert!((ratings["B"] - 0.5).abs() < 1e-9);
+        assert!((ratings["C"] - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn goal_differences_dont_matter() {
+        let narrow = colley(&[played(0, "A", "B", [1, 0])]);
+        let wide = colley(&[played(0, "A", "B", [5, 0])]);
+        assert_eq!(narrow, wide);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{
+    models::{DataEntry, Match, Outcome},
+    ratings::OutcomeFit,
+    Predictor,
+};
+use chrono::{DateTime, FixedOffset};
+use std::collections::HashMap;
+
+/**
+    The settings of an Elo rating engine.
+
+    * ```initial```: The rating of a club before its first match.
+    * ```k```: The K-factor, the maximum change of a rating after a single match.
+    * ```home_advantage```: Rating points added to the home team when the expected result is calculated.
+    * ```goal_difference```: Multiplies the K-factor by the margin of victory:
+      1 for a win by one goal, 1.5 for two goals and ```(11 + n) / 8``` for n >= 3 goals.
+    * ```carry_over```: Share of the distance to ```initial``` a rating keeps into a new season.
+    * ```season_break```: A club that hasn't played for more than this number of days starts a new season.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EloSettings {
+    pub initial: f64,
+    pub k: f64,
+    pub home_advantage: f64,
+    pub goal_difference: bool,
+    pub carry_over: f64,
+    pub season_break: i64,
+}
+
+/**
+    An Elo rating engine. Processes matches in chronological order and keeps the rating history
+    of each club.
+
+    **Example**:
+
+    ```text
+    Home 1550, Away 1500, home advantage 100
+    expected result of the home team: 1 / (1 + 10^((1500 - 1650) / 400)) = 0.70
+    the home team wins 2 : 0, K = 20: 20 * 1.5 * (1 - 0.70) = +9 points for the home team
+    ```
+
+    **Note**:
+
+    * Elo predicts the expected result of the home team, ```E = P(home) + P(draw) / 2```. The draw
+      probability is the share of draws in the processed matches for an even match, and shrinks
+      as the match gets more uneven: ```P(draw) = draw rate * 4 * E * (1 - E)```,
+      at most ```2 * min(E, 1 - E)```.
+    * The expected goals are the mean goals per match, split by a goal difference that is fitted
+      linearly to ```E``` over the processed matches.
+**/
+#[derive(Clone, Debug)]
+pub struct Elo {
+    pub settings: EloSettings,
+    // ratings after each match of a club
+    history: HashMap<String, Vec<(DateTime<FixedOffset>, f64)>>,
+    fit: OutcomeFit,
+}
+
+impl Default for EloSettings {
+    fn default() -> Self {
+        EloSettings {
+            initial: 1500f64,
+            k: 20f64,
+            home_advantage: 100f64,
+            goal_difference: true,
+            carry_over: 0.8,
+            season_break: 60,
+        }
+    }
+}
+
+impl Default for Elo {
+    fn default() -> Self {
+        Elo::new(EloSettings::default())
+    }
+}
+
+impl Elo {
+    pub fn new(settings: EloSettings) -> Self {
+        if settings.k <= 0f64 {
+            panic!("the K-factor must be a positive number");
+        }
+        if !(0f64..=1f64).contains(&settings.carry_over) {
+            panic!("the carry-over must be in the range [0, 1]");
+        }
+        Elo {
+            settings,
+            history: HashMap::new(),
+            fit: OutcomeFit::default(),
+        }
+    }
+
+    /**
+        Processes matches in chronological order and updates the ratings of both clubs.
+        Matches without result are skipped. The matches must not be older than matches processed before.
+    **/
+    pub fn process(&mut self, matches: &[Match]) {
+        let mut sorted: Vec<&Match> = matches.iter().filter(|m| m.result.is_some()).collect();
+        sorted.sort_by_key(|m| m.date);
+        for m in sorted {
+            let result = m.result.unwrap();
+            let [home, away] = self.ratings(m);
+            let expected = self.expected(home, away);
+            let actual = match Outcome::from(result) {
+                Outcome::Home => 1f64,
+                Outcome::Draw => 0.5,
+                Outcome::Away => 0f64,
+            };
+            let difference = i32::from(result[0]) - i32::from(result[1]);
+            let change = self.settings.k * self.multiplier(difference.abs()) * (actual - expected);
+            self.push(&m.home, m.date, home + change);
+            self.push(&m.away, m.date, away - change);
+            self.fit.add(expected, result);
+        }
+    }
+
+    /// Removes all ratings and processed matches.
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.fit = OutcomeFit::default();
+    }
+
+    /**
+        Returns the rating of a club before a match on ```date```.
+        The rating is regressed towards ```initial```, if the club starts a new season.
+    **/
+    pub fn rating(&self, club: &str, date: &DateTime<FixedOffset>) -> f64 {
+        let last = self
+            .history
+            .get(club)
+            .and_then(|h| h.iter().rev().find(|(d, _)| d < date));
+        match last {
+            Some((d, rating)) if date.signed_duration_since(*d).num_days() > self.settings.season_break => {
+                self.settings.initial + self.settings.carry_over * (rating - self.settings.initial)
+            }
+            Some((_, rating)) => *rating,
+            None => self.settings.initial,
+        }
+    }
+
+    /// Returns the pre-match ratings of the home and the away team.
+    pub fn ratings(&self, m: &Match) -> [f64; 2] {
+        [self.rating(&m.home, &m.date), self.rating(&m.away, &m.date)]
+    }
+
+    /// Returns the ratings of a club after each of its processed matches.
+    pub fn history(&self, club: &str) -> &[(DateTime<FixedOffset>, f64)] {
+        self.history.get(club).map(|h| h.as_slice()).unwrap_or(&[])
+    }
+
+    /// Returns the probabilities of a home win, a draw and an away win, based on the pre-match ratings.
+    pub fn probabilities(&self, m: &Match) -> [f64; 3] {
+        let [home, away] = self.ratings(m);
+        self.fit.probabilities(self.expected(home, away))
+    }
+
+    /// Returns the expected goals of the home and the away team, based on the pre-match ratings.
+    pub fn expected_goals(&self, m: &Match) -> [f64; 2] {
+        let [home, away] = self.ratings(m);
+        self.fit.goals(self.expected(home, away))
+    }
+
+    // the expected result of the home team
+    fn expected(&self, home: f64, away: f64) -> f64 {
+        1f64 / (1f64 + 10f64.powf((away - home - self.settings.home_advantage) / 400f64))
+    }
+
+    fn multiplier(&self, difference: i32) -> f64 {
+        if !self.settings.goal_difference {
+            return 1f64;
+        }
+        match difference {
+            0 | 1 => 1f64,
+            2 => 1.5,
+            n => (11f64 + f64::from(n)) / 8f64,
+        }
+    }
+
+    fn push(&mut self, club: &str, date: DateTime<FixedOffset>, rating: f64) {
+        self.history
+            .entry(club.to_string())
+            .or_default()
+            .push((date, rating));
+    }
+}
+
+/**
+    Elo ignores the input features. ```fit``` processes the training matches from scratch,
+    the predictions use the ratings after the last training match.
+**/
+impl Predictor for Elo {
+    fn fit(&mut self, _training_set: &[DataEntry], matches: &[Match]) {
+        self.reset();
+        self.process(matches);
+    }
+
+    fn predict_scores(&self, _entry: &DataEntry, m: &Match) -> [f64; 2] {
+        self.expected_goals(m)
+    }
+
+    fn predict_outcome(&self, _entry: &DataEntry, m: &Match) -> [f64; 3] {
+        self.probabilities(m)
+    }
+
+    fn name(&self) -> String {
+        String::from("Elo")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        features::EloFeature,
+        testing::{date, entry, fixture, played},
+    };
+
+    #[test]
+    fn the_favourite_gains_few_points_for_an_expected_win() {
+        let mut elo = Elo::default();
+        elo.push("A", date(0), 1550f64);
+        elo.process(&[played(1, "A", "B", [2, 0])]);
+        let expected = 1f64 / (1f64 + 10f64.powf(-150f64 / 400f64));
+        assert!((expected - 0.70).abs() < 0.01);
+        let change = 20f64 * 1.5 * (1f64 - expected);
+        assert!((change - 9f64).abs() < 0.2);
+        assert!((elo.rating("A", &date(2)) - (1550f64 + change)).abs() < 1e-9);
+        assert!((elo.rating("B", &date(2)) - (1500f64 - change)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn the_goal_difference_multiplies_the_k_factor() {
+        let elo = Elo::default();
+        assert_eq!(elo.multiplier(1), 1f64);
+        assert_eq!(elo.multiplier(2), 1.5);
+        assert_eq!(elo.multiplier(3), 1.75);
+        let plain = Elo::new(EloSettings {
+            goal_difference: false,
+            ..EloSettings::default()
+        });
+        assert_eq!(plain.multiplier(5), 1f64);
+    }
+
+    #[test]
+    fn ratings_regress_towards_the_initial_rating_in_a_new_season() {
+        let mut elo = Elo::default();
+        elo.push("A", date(0), 1600f64);
+        assert_eq!(elo.rating("A", &date(0)), 1500f64);
+        assert_eq!(elo.rating("A", &date(60)), 1600f64);
+        assert!((elo.rating("A", &date(61)) - 1580f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn the_feature_holds_the_outcome_probabilities() {
+        let mut elo = Elo::default();
+        elo.push("A", date(0), 1550f64);
+        elo.push("B", date(0), 1500f64);
+        // a draw rate of 0.25
+        for result in &[[1, 1], [1, 0], [0, 1], [2, 0]] {
+            elo.fit.add(0.5, *result);
+        }
+        let feature = EloFeature::from((&elo, &fixture(1, "A", "B")));
+        for (p, expected) in feature.data.iter().zip(&[0.599, 0.209, 0.192]) {
+            assert!((p - expected).abs() < 5e-4, "{:?}", feature.data);
+        }
+    }
+
+    #[test]
+    fn fit_starts_from_scratch() {
+        let matches = vec![played(0, "A", "B", [3, 0]), played(1, "B", "A", [1, 1])];
+        let mut elo = Elo::default();
+        elo.process(&matches);
+        let once = elo.rating("A", &date(2));
+        elo.fit(&[], &matches);
+        assert_eq!(elo.rating("A", &date(2)), once);
+        assert_eq!(elo.history("A").len(), 2);
+        let p = elo.predict_outcome(&entry(), &played(2, "A", "B", [0, 0]));
+        assert!((p.iter().sum::<f64>() - 1f64).abs() < 1e-12);
+        assert!(p[0] > p[2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "K-factor must be a positive number")]
+    fn the_k_factor_must_be_positive() {
+        Elo::new(EloSettings {
+            k: 0f64,
+            ..EloSettings::default()
+        });
+    }
+}
@@ -0,0 +1,427 @@
+use crate::{
+    models::{DataEntry, Match, Outcome},
+    ratings::OutcomeFit,
+    Markdown, Predictor,
+};
+use chrono::{DateTime, FixedOffset};
+use std::{collections::HashMap, f64::consts::PI};
+
+// converts between the Glicko and the Glicko-2 scale
+const SCALE: f64 = 173.7178;
+// convergence of the volatility iteration
+const EPSILON: f64 = 0.000_001;
+
+/**
+    The settings of a Glicko-2 rating engine.
+
+    * ```rating```, ```deviation```, ```volatility```: The values of a club before its first match.
+    * ```tau```: Constrains the change of the volatility, reasonable values are between 0.3 and 1.2.
+    * ```period```: The length of a rating period in days. All matches of a period are rated at once,
+      the deviation of a club grows with each period it doesn't play.
+    * ```home_advantage```: Rating points added to the home team when the expected result is calculated.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlickoSettings {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub tau: f64,
+    pub period: i64,
+    pub home_advantage: f64,
+}
+
+/**
+    The rating of a club. ```deviation``` is the uncertainty of the rating, the true strength is
+    within ```rating +- 2 * deviation``` with a probability of about 95%. ```volatility``` is
+    the expected fluctuation of the strength, erratic results raise it.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlickoRating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+/// The ratings of all clubs, highest rating first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GlickoRatings(pub Vec<(String, GlickoRating)>);
+
+/**
+    A Glicko-2 rating engine (Glickman, 2012). Rating periods are derived from the match dates,
+    starting with the first processed match.
+
+    **Rationale**:
+
+    * Many clubs play only a few matches against each other, ie. cup matches between leagues.
+      Elo treats a club after two matches as well known as one after twenty. Glicko-2 tracks the
+      deviation of each rating and lets a result against a rarely seen club count less.
+
+    **Note**:
+
+    * The expected result ```E``` of a match accounts for the deviations of both clubs:
+      ```E = 1 / (1 + exp(-g(sqrt(phi_home^2 + phi_away^2)) * (mu_home + home advantage - mu_away)))```.
+      Uncertain ratings pull the probabilities towards an even match.
+    * Draws and expected goals are derived from ```E``` as for ```Elo```.
+**/
+#[derive(Clone, Debug)]
+pub struct Glicko {
+    pub settings: GlickoSettings,
+    // ratings on the Glicko-2 scale and the last rating period of each club
+    clubs: HashMap<String, (GlickoRating, i64)>,
+    origin: Option<DateTime<FixedOffset>>,
+    fit: OutcomeFit,
+}
+
+impl Default for GlickoSettings {
+    fn default() -> Self {
+        GlickoSettings {
+            rating: 1500f64,
+            deviation: 350f64,
+            volatility: 0.06,
+            tau: 0.5,
+            period: 7,
+            home_advantage: 60f64,
+        }
+    }
+}
+
+impl Default for Glicko {
+    fn default() -> Self {
+        Glicko::new(GlickoSettings::default())
+    }
+}
+
+impl Glicko {
+    pub fn new(settings: GlickoSettings) -> Self {
+        if settings.period < 1 {
+            panic!("a rating period must be at least one day");
+        }
+        if settings.deviation <= 0f64 || settings.volatility <= 0f64 || settings.tau <= 0f64 {
+            panic!("deviation, volatility and tau must be positive numbers");
+        }
+        Glicko {
+            settings,
+            clubs: HashMap::new(),
+            origin: None,
+            fit: OutcomeFit::default(),
+        }
+    }
+
+    /**
+        Processes the matches with result period by period and updates the ratings.
+        The matches must not be older than matches processed before.
+    **/
+    pub fn process(&mut self, matches: &[Match]) {
+        let mut sorted: Vec<&Match> = matches.iter().filter(|m| m.result.is_some()).collect();
+        sorted.sort_by_key(|m| m.date);
+        if self.origin.is_none() {
+            self.origin = sorted.first().map(|m| m.date);
+        }
+        let mut start = 0;
+        while start < sorted.len() {
+            let period = self.period(&sorted[start].date);
+            let end = start
+                + sorted[start..]
+                    .iter()
+                    .take_while(|m| self.period(&m.date) == period)
+                    .count();
+            self.rate_period(&sorted[start..end], period);
+            start = end;
+        }
+    }
+
+    /// Removes all ratings and processed matches.
+    pub fn reset(&mut self) {
+        self.clubs.clear();
+        self.origin = None;
+        self.fit = OutcomeFit::default();
+    }
+
+    /**
+        Returns the rating of a club before a match on ```date```. The deviation includes
+        the periods the club hasn't played since its last match.
+    **/
+    pub fn rating(&self, club: &str, date: &DateTime<FixedOffset>) -> GlickoRating {
+        let scaled = self.scaled(club, self.period(date));
+        GlickoRating {
+            rating: scaled.rating * SCALE + self.settings.rating,
+            deviation: scaled.deviation * SCALE,
+            volatility: scaled.volatility,
+        }
+    }
+
+    /// Returns the ratings of all clubs after their last rated period, highest rating first.
+    pub fn ratings(&self) -> GlickoRatings {
+        let mut ratings: Vec<(String, GlickoRating)> = self
+            .clubs
+            .iter()
+            .map(|(club, (r, _))| {
+                let rating = GlickoRating {
+                    rating: r.rating * SCALE + self.settings.rating,
+                    deviation: r.deviation * SCALE,
+                    volatility: r.volatility,
+                };
+                (club.clone(), rating)
+            })
+            .collect();
+        ratings.sort_by(|a, b| b.1.rating.partial_cmp(&a.1.rating).unwrap());
+        GlickoRatings(ratings)
+    }
+
+    /// Returns the probabilities of a home win, a draw and an away win, based on the pre-match ratings.
+    pub fn probabilities(&self, m: &Match) -> [f64; 3] {
+        self.fit.probabilities(self.expected(m))
+    }
+
+    /// Returns the expected goals of the home and the away team, based on the pre-match ratings.
+    pub fn expected_goals(&self, m: &Match) -> [f64; 2] {
+        self.fit.goals(self.expected(m))
+    }
+
+    // the expected result of the home team, including the deviations of both clubs
+    fn expected(&self, m: &Match) -> f64 {
+        let period = self.period(&m.date);
+        let (home, away) = (self.scaled(&m.home, period), self.scaled(&m.away, period));
+        let deviation = (home.deviation.powi(2) + away.deviation.powi(2)).sqrt();
+        expected(home.rating + self.settings.home_advantage / SCALE, away.rating, deviation)
+    }
+
+    fn period(&self, date: &DateTime<FixedOffset>) -> i64 {
+        match self.origin {
+            Some(origin) => (date.signed_duration_since(origin).num_days() / self.settings.period).max(0),
+            None => 0,
+        }
+    }
+
+    // the rating on the Glicko-2 scale at the start of a period
+    fn scaled(&self, club: &str, period: i64) -> GlickoRating {
+        match self.clubs.get(club) {
+            Some((r, last)) => {
+                let idle = (period - last).max(0) as f64;
+                GlickoRating {
+                    deviation: (r.deviation.powi(2) + idle * r.volatility.powi(2)).sqrt(),
+                    ..*r
+                }
+            }
+            None => GlickoRating {
+                rating: 0f64,
+                deviation: self.settings.deviation / SCALE,
+                volatility: self.settings.volatility,
+            },
+        }
+    }
+
+    fn rate_period(&mut self, matches: &[&Match], period: i64) {
+        // all matches of the period use the ratings at the start of the period
+        // the deviation grows by the idle periods, the update adds the current period
+        // the opponents of each club, the home advantage is added to the rating of the home team
+        let mut games: HashMap<String, Vec<(GlickoRating, f64)>> = HashMap::new();
+        let home_advantage = self.settings.home_advantage / SCALE;
+        for m in matches {
+            let result = m.result.unwrap();
+            let (home, away) = (self.scaled(&m.home, period - 1), self.scaled(&m.away, period - 1));
+            let score = match Outcome::from(result) {
+                Outcome::Home => 1f64,
+                Outcome::Draw => 0.5,
+                Outcome::Away => 0f64,
+            };
+            let deviation = (home.deviation.powi(2) + away.deviation.powi(2)).sqrt();
+            self.fit.add(expected(home.rating + home_advantage, away.rating, deviation), result);
+            games
+                .entry(m.home.clone())
+                .or_default()
+                .push((GlickoRating { rating: away.rating - home_advantage, ..away }, score));
+            games
+                .entry(m.away.clone())
+                .or_default()
+                .push((GlickoRating { rating: home.rating + home_advantage, ..home }, 1f64 - score));
+        }
+        let updated: Vec<(String, GlickoRating)> = games
+            .iter()
+            .map(|(club, games)| (club.clone(), self.update(self.scaled(club, period - 1), games)))
+            .collect();
+        for (club, r) in updated {
+            self.clubs.insert(club, (r, period));
+        }
+    }
+
+    // the Glicko-2 update of a single club for one period
+    fn update(&self, r: GlickoRating, games: &[(GlickoRating, f64)]) -> GlickoRating {
+        let (mut v, mut delta) = (0f64, 0f64);
+        for (opponent, score) in games {
+            let g = g(opponent.deviation);
+            let e = expected(r.rating, opponent.rating, opponent.deviation);
+            v += g * g * e * (1f64 - e);
+            delta += g * (score - e);
+        }
+        let v = 1f64 / v;
+        let volatility = self.volatility(r, v, v * delta);
+        let pre = (r.deviation.powi(2) + volatility.powi(2)).sqrt();
+        let deviation = 1f64 / (1f64 / pre.powi(2) + 1f64 / v).sqrt();
+        GlickoRating {
+            rating: r.rating + deviation.powi(2) * delta,
+            deviation,
+            volatility,
+        }
+    }
+
+    // the new volatility by the Illinois algorithm (step 5 of Glicko-2)
+    fn volatility(&self, r: GlickoRating, v: f64, delta: f64) -> f64 {
+        let tau = self.settings.tau;
+        let phi2 = r.deviation.powi(2);
+        let a = (r.volatility.powi(2)).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta * delta - phi2 - v - ex) / (2f64 * (phi2 + v + ex).powi(2)) - (x - a) / (tau * tau)
+        };
+        let mut big_a = a;
+        let mut big_b = if delta * delta > phi2 + v {
+            (delta * delta - phi2 - v).ln()
+        } else {
+            let mut k = 1f64;
+            while f(a - k * tau) < 0f64 {
+                k += 1f64;
+            }
+            a - k * tau
+        };
+        let (mut f_a, mut f_b) = (f(big_a), f(big_b));
+        while (big_b - big_a).abs() > EPSILON {
+            let big_c = big_a + (big_a - big_b) * f_a / (f_b - f_a);
+            let f_c = f(big_c);
+            if f_c * f_b <= 0f64 {
+                big_a = big_b;
+                f_a = f_b;
+            } else {
+                f_a /= 2f64;
+            }
+            big_b = big_c;
+            f_b = f_c;
+        }
+        (big_a / 2f64).exp()
+    }
+}
+
+/// Formats ```GlickoRatings``` into a Markdown Table, including header.
+impl Markdown for GlickoRatings {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Rank|Club|Rating|Deviation|Volatility|\n");
+        s.push_str("|-:|:-|-:|-:|-:|\n");
+        for (i, (club, r)) in self.0.iter().enumerate() {
+            s.push_str(&format!(
+                "|{}|{}|{:.0}|{:.0}|{:.4}|\n",
+                i + 1,
+                club,
+                r.rating,
+                r.deviation,
+                r.volatility
+            ));
+        }
+        s
+    }
+}
+
+fn g(deviation: f64) -> f64 {
+    1f64 / (1f64 + 3f64 * deviation.powi(2) / (PI * PI)).sqrt()
+}
+
+fn expected(rating: f64, opponent: f64, deviation: f64) -> f64 {
+    1f64 / (1f64 + (-g(deviation) * (rating - opponent)).exp())
+}
+
+/**
+    Glicko-2 ignores the input features. ```fit``` processes the training matches from scratch,
+    the predictions use the ratings after the last training period.
+**/
+impl Predictor for Glicko {
+    fn fit(&mut self, _training_set: &[DataEntry], matches: &[Match]) {
+        self.reset();
+        self.process(matches);
+    }
+
+    fn predict_scores(&self, _entry: &DataEntry, m: &Match) -> [f64; 2] {
+        self.expected_goals(m)
+    }
+
+    fn predict_outcome(&self, _entry: &DataEntry, m: &Match) -> [f64; 3] {
+        self.probabilities(m)
+    }
+
+    fn name(&self) -> String {
+        String::from("Glicko-2")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{date, played};
+
+    fn scaled(rating: f64, deviation: f64) -> GlickoRating {
+        GlickoRating {
+            rating: (rating - 1500f64) / SCALE,
+            deviation: deviation / SCALE,
+            volatility: 0.06,
+        }
+    }
+
+    #[test]
+    fn updates_the_example_of_the_glicko_2_paper() {
+        let glicko = Glicko::default();
+        let games = vec![
+            (scaled(1400f64, 30f64), 1f64),
+            (scaled(1550f64, 100f64), 0f64),
+            (scaled(1700f64, 300f64), 0f64),
+        ];
+        let r = glicko.update(scaled(1500f64, 200f64), &games);
+        assert!((r.rating * SCALE + 1500f64 - 1464.06).abs() < 0.01);
+        assert!((r.deviation * SCALE - 151.52).abs() < 0.01);
+        assert!((r.volatility - 0.05999).abs() < 0.00001);
+    }
+
+    #[test]
+    fn the_deviation_shrinks_with_matches_and_grows_while_idle() {
+        let mut glicko = Glicko::default();
+        glicko.process(&[played(0, "A", "B", [2, 0]), played(8, "B", "A", [0, 1])]);
+        let a = glicko.rating("A", &date(14));
+        assert!(a.rating > 1500f64);
+        assert!(a.deviation < 350f64);
+        assert!(glicko.rating("B", &date(14)).rating < 1500f64);
+        assert!(glicko.rating("A", &date(70)).deviation > a.deviation);
+        assert_eq!(glicko.rating("C", &date(14)).deviation, 350f64);
+        let ratings = glicko.ratings();
+        assert_eq!(ratings.0[0].0, "A");
+        assert_eq!(ratings.to_table().lines().count(), 2 + 2);
+    }
+
+    #[test]
+    fn matches_of_a_period_are_rated_at_once() {
+        let mut glicko = Glicko::default();
+        glicko.process(&[played(0, "A", "B", [1, 0]), played(1, "A", "B", [1, 0])]);
+        // both matches use the initial rating of the opponent, minus the home advantage
+        let opponent = GlickoRating {
+            rating: -60f64 / SCALE,
+            ..scaled(1500f64, 350f64)
+        };
+        let r = glicko.update(scaled(1500f64, 350f64), &[(opponent, 1f64), (opponent, 1f64)]);
+        assert!((glicko.rating("A", &date(7)).rating - (r.rating * SCALE + 1500f64)).abs() < 1e-9);
+        assert_eq!(glicko.period(&date(6)), 0);
+        assert_eq!(glicko.period(&date(7)), 1);
+    }
+
+    #[test]
+    fn uncertain_ratings_pull_towards_an_even_match() {
+        let certain = expected(0.5, 0f64, 0.1);
+        let uncertain = expected(0.5, 0f64, 2f64);
+        assert!(certain > uncertain && uncertain > 0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one day")]
+    fn a_rating_period_must_be_at_least_one_day() {
+        Glicko::new(GlickoSettings {
+            period: 0,
+            ..GlickoSettings::default()
+        });
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{
+    models::Match,
+    ratings::{clubs, solve},
+};
+use std::collections::HashMap;
+
+/**
+    Returns the Massey rating of each club: the ratings whose differences explain the goal
+    differences of all matches best (least squares). The ratings add up to zero.
+
+    **Example**:
+
+    ```text
+    A beats B 2 : 0, B beats C 1 : 0, A draws C 1 : 1
+    Massey: A 0.67, B -0.33, C -0.33
+    ```
+
+    **Note**:
+
+    * Ratings are only comparable within a group of clubs that are connected by matches.
+**/
+pub fn massey(matches: &[Match]) -> HashMap<String, f64> {
+    let clubs = clubs(matches);
+    let index: HashMap<&String, usize> = clubs.iter().enumerate().map(|(i, c)| (c, i)).collect();
+    let n = clubs.len();
+    let mut a = vec![vec![0f64; n]; n];
+    let mut b = vec![0f64; n];
+    for m in matches {
+        if let Some(result) = m.result {
+            let (h, w) = (index[&m.home], index[&m.away]);
+            let difference = f64::from(result[0]) - f64::from(result[1]);
+            a[h][h] += 1f64;
+            a[w][w] += 1f64;
+            a[h][w] -= 1f64;
+            a[w][h] -= 1f64;
+            b[h] += difference;
+            b[w] -= difference;
+        }
+    }
+    if n > 0 {
+        // the normal equations are singular, the last equation is replaced by: sum of all ratings = 0
+        a[n - 1] = vec![1f64; n];
+        b[n - 1] = 0f64;
+    }
+    clubs.into_iter().zip(solve(a, b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::played;
+
+    #[test]
+    fn ratings_fit_the_goal_differences() {
+        let matches = vec![played(0, "A", "B", [2, 0]), played(1, "B", "C", [1, 0]), played(2, "A", "C", [1, 1])];
+        let ratings = massey(&matches);
+        assert!((ratings["A"] - 2f64 / 3f64).abs() < 1e-9);
+        assert!((ratings["B"] + 1f64 / 3f64).abs() < 1e-9);
+        assert!((ratings["C"] + 1f64 / 3f64).abs() < 1e-9);
+        assert!(ratings.values().sum::<f64>().abs() < 1e-9);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::models::Match;
+use chrono::NaiveDate;
+
+/// Bradley-Terry paired comparison model with draws, home advantage and league intercepts.
+pub mod bradley_terry;
+/// Colley ratings from wins, draws and losses, adjusted for the strength of the opponents.
+pub mod colley;
+/// Elo ratings with home advantage, goal difference multiplier and season carry-over.
+pub mod elo;
+/// Glicko-2 ratings with rating deviation and volatility, rated in periods derived from the match dates.
+pub mod glicko;
+/// Massey ratings, least-squares fit of the goal differences.
+pub mod massey;
+/// Pi-ratings with separate home and away ratings, updated by the error of the expected goal difference.
+pub mod pi;
+/// Power ranking table that combines Massey, Colley, Pi and Elo ratings.
+pub mod power;
+
+// the draw rate used before any match is processed
+const DEFAULT_DRAW_RATE: f64 = 0.25;
+
+/**
+    Turns the expected result of the home team ```E = P(home) + P(draw) / 2``` of a rating system
+    into outcome probabilities and expected goals, fitted to the processed matches.
+
+    * The draw probability is the share of draws in the processed matches for an even match,
+      and shrinks as the match gets more uneven: ```P(draw) = draw rate * 4 * E * (1 - E)```,
+      at most ```2 * min(E, 1 - E)```.
+    * The expected goals are the mean goals per match, split by a goal difference that is fitted
+      linearly to ```E```.
+**/
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OutcomeFit {
+    // sums over the processed matches: count, draws, goals, x, x^2, y, x * y
+    // with x = E - 0.5 and y = the goal difference
+    sums: [f64; 7],
+}
+
+impl OutcomeFit {
+    pub(crate) fn add(&mut self, expected: f64, result: [u8; 2]) {
+        let difference = f64::from(result[0]) - f64::from(result[1]);
+        let (x, y) = (expected - 0.5, difference);
+        let draw = if result[0] == result[1] { 1f64 } else { 0f64 };
+        let goals = f64::from(result[0]) + f64::from(result[1]);
+        for (sum, value) in self.sums.iter_mut().zip(&[1f64, draw, goals, x, x * x, y, x * y]) {
+            *sum += value;
+        }
+    }
+
+    pub(crate) fn probabilities(&self, expected: f64) -> [f64; 3] {
+        let draw_rate = if self.sums[0] < 1f64 {
+            DEFAULT_DRAW_RATE
+        } else {
+            self.sums[1] / self.sums[0]
+        };
+        // a draw rate above 0.5 would leave the favourite below zero, the win probabilities stay non-negative
+        let draw = (draw_rate * 4f64 * expected * (1f64 - expected)).min(2f64 * expected.min(1f64 - expected));
+        [expected - draw / 2f64, draw, 1f64 - expected - draw / 2f64]
+    }
+
+    pub(crate) fn goals(&self, expected: f64) -> [f64; 2] {
+        let [n, _, goals, x, xx, y, xy] = self.sums;
+        if n < 1f64 {
+            return [0f64, 0f64];
+        }
+        let variance = n * xx - x * x;
+        let slope = if variance > 0f64 { (n * xy - x * y) / variance } else { 0f64 };
+        let intercept = (y - slope * x) / n;
+        let difference = intercept + slope * (expected - 0.5);
+        let total = goals / n;
+        [
+            ((total + difference) / 2f64).max(0f64),
+            ((total - difference) / 2f64).max(0f64),
+        ]
+    }
+}
+
+/**
+    Returns the matches with result played on or before ```until```, all matches with result
+    if ```until``` is ```None```.
+**/
+pub fn played_until(matches: &[Match], until: Option<NaiveDate>) -> Vec<Match> {
+    matches
+        .iter()
+        .filter(|m| m.result.is_some())
+        .filter(|m| until.map(|d| m.date.naive_local().date() <= d).unwrap_or(true))
+        .cloned()
+        .collect()
+}
+
+// returns the clubs of the matches in alphabetical order
+pub(crate) fn clubs(matches: &[Match]) -> Vec<String> {
+    let mut clubs: Vec<String> = matches
+        .iter()
+        .flat_map(|m| vec![m.home.clone(), m.away.clone()])
+        .collect();
+    clubs.sort();
+    clubs.dedup();
+    clubs
+}
+
+/**
+    Solves the linear system ```a * x = b``` by Gaussian elimination with partial pivoting.
+    Variables without a usable pivot (a singular system) are set to zero.
+**/
+pub(crate) fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    let mut pivots = vec![None; n];
+    let mut row = 0;
+    for col in 0..n {
+        let best = (row..n).max_by(|&i, &j| a[i][col].abs().partial_cmp(&a[j][col].abs()).unwrap());
+        let best = match best {
+            Some(best) if a[best][col].abs() > 1e-12 => best,
+            _ => continue,
+        };
+        a.swap(row, best);
+        b.swap(row, best);
+        let pivot = a[row].clone();
+        for i in 0..n {
+            let factor = a[i][col] / pivot[col];
+            if i != row && factor != 0f64 {
+                for (x, p) in a[i].iter_mut().zip(&pivot).skip(col) {
+                    *x -= factor * p;
+                }
+                b[i] -= factor * b[row];
+            }
+        }
+        pivots[col] = Some(row);
+        row += 1;
+    }
+    (0..n)
+        .map(|col| pivots[col].map(|r| b[r] / a[r][col]).unwrap_or(0f64))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probabilities_of_an_even_match_use_the_draw_rate() {
+        let mut fit = OutcomeFit::default();
+        fit.add(0.5, [1, 1]);
+        fit.add(0.5, [2, 0]);
+        let p = fit.probabilities(0.5);
+        assert!((p[0] - 0.25).abs() < 1e-12);
+        assert!((p[1] - 0.5).abs() < 1e-12);
+        assert!((p[2] - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn probabilities_stay_non_negative_with_a_draw_rate_near_one() {
+        let mut fit = OutcomeFit::default();
+        for _ in 0..99 {
+            fit.add(0.5, [0, 0]);
+        }
+        fit.add(0.5, [1, 0]);
+        for expected in &[0.05, 0.2, 0.5, 0.8, 0.95] {
+            let p = fit.probabilities(*expected);
+            assert!(p.iter().all(|x| *x >= 0f64), "{:?} for E = {}", p, expected);
+            assert!((p.iter().sum::<f64>() - 1f64).abs() < 1e-12);
+            // the expected result is kept
+            assert!((p[0] + p[1] / 2f64 - expected).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::models::Match;
+use std::collections::HashMap;
+
+/**
+    The settings of the Pi-ratings.
+
+    * ```learning_rate```: How much the ratings of the playing venue move with the goal difference error.
+    * ```venue_rate```: The share of the change that is passed on to the rating of the other venue
+      (the home rating after an away match and vice versa).
+    * ```base```: The goal difference scale, a rating difference of ```base``` means an expected goal
+      difference of 9 goals.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PiSettings {
+    pub learning_rate: f64,
+    pub venue_rate: f64,
+    pub base: f64,
+}
+
+/**
+    Pi-ratings (Constantinou and Fenton, 2013). Each club has a home and an away rating, both
+    in expected goal differences against an average club. After each match both ratings of both
+    clubs move by the error of the expected goal difference.
+
+    ```text
+    expected goal difference = g(home rating of home team) - g(away rating of away team)
+    g(r) = sign(r) * (10^(|r| / base) - 1)
+    ```
+
+    **Rationale**:
+
+    * The error is weighted by ```base * log10(1 + |error|)```, so that a single big win
+      doesn't move the ratings more than a few narrow ones.
+
+    **Example**:
+
+    ```text
+    home team: home rating 0.5, away team: away rating 0.0
+    expected goal difference: 10^(0.5 / 3) - 1 = 0.47
+    ```
+**/
+#[derive(Clone, Debug)]
+pub struct PiRatings {
+    pub settings: PiSettings,
+    // home and away rating of each club
+    ratings: HashMap<String, [f64; 2]>,
+}
+
+impl Default for PiSettings {
+    fn default() -> Self {
+        PiSettings {
+            learning_rate: 0.035,
+            venue_rate: 0.7,
+            base: 3f64,
+        }
+    }
+}
+
+impl Default for PiRatings {
+    fn default() -> Self {
+        PiRatings::new(PiSettings::default())
+    }
+}
+
+impl PiRatings {
+    pub fn new(settings: PiSettings) -> Self {
+        if settings.learning_rate <= 0f64 || settings.base <= 0f64 {
+            panic!("learning rate and base must be positive numbers");
+        }
+        if settings.venue_rate < 0f64 || settings.venue_rate > 1f64 {
+            panic!("the venue rate must be between 0 and 1");
+        }
+        PiRatings {
+            settings,
+            ratings: HashMap::new(),
+        }
+    }
+
+    /// Processes the matches with result in chronological order and updates the ratings.
+    pub fn process(&mut self, matches: &[Match]) {
+        let mut sorted: Vec<&Match> = matches.iter().filter(|m| m.result.is_some()).collect();
+        sorted.sort_by_key(|m| m.date);
+        for m in sorted {
+            let result = m.result.unwrap();
+            let observed = f64::from(result[0]) - f64::from(result[1]);
+            let error = observed - self.expected_goal_difference(m);
+            let weighted = error.signum() * self.settings.base * (1f64 + error.abs()).log10();
+            let change = weighted * self.settings.learning_rate;
+            let venue = change * self.settings.venue_rate;
+            let home = self.ratings.entry(m.home.clone()).or_default();
+            home[0] += change;
+            home[1] += venue;
+            let away = self.ratings.entry(m.away.clone()).or_default();
+            away[1] -= change;
+            away[0] -= venue;
+        }
+    }
+
+    /// Discards all ratings.
+    pub fn reset(&mut self) {
+        self.ratings.clear();
+    }
+
+    /// Returns the home and the away rating of a club, zero for an unknown club.
+    pub fn rating(&self, club: &str) -> [f64; 2] {
+        *self.ratings.get(club).unwrap_or(&[0f64; 2])
+    }
+
+    /// Returns the expected goal difference (home minus away goals) of a match.
+    pub fn expected_goal_difference(&self, m: &Match) -> f64 {
+        self.goals(self.rating(&m.home)[0]) - self.goals(self.rating(&m.away)[1])
+    }
+
+    // the expected goal difference against an average club
+    fn goals(&self, rating: f64) -> f64 {
+        rating.signum() * (10f64.powf(rating.abs() / self.settings.base) - 1f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::played;
+
+    #[test]
+    fn expected_goal_difference_of_the_example() {
+        let mut pi = PiRatings::default();
+        pi.ratings.insert(String::from("A"), [0.5, 0f64]);
+        let m = played(0, "A", "B", [0, 0]);
+        assert!((pi.expected_goal_difference(&m) - 0.47).abs() < 0.005);
+    }
+
+    #[test]
+    fn both_venues_move_by_the_weighted_error() {
+        let mut pi = PiRatings::default();
+        pi.process(&[played(0, "A", "B", [2, 0])]);
+        let change = 0.035 * 3f64 * 3f64.log10();
+        let [home, away] = pi.rating("A");
+        assert!((home - change).abs() < 1e-12);
+        assert!((away - 0.7 * change).abs() < 1e-12);
+        let [home, away] = pi.rating("B");
+        assert!((away + change).abs() < 1e-12);
+        assert!((home + 0.7 * change).abs() < 1e-12);
+        pi.reset();
+        assert_eq!(pi.rating("A"), [0f64; 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the venue rate must be between 0 and 1")]
+    fn the_venue_rate_is_a_share() {
+        PiRatings::new(PiSettings {
+            venue_rate: 1.5,
+            ..PiSettings::default()
+        });
+    }
+}
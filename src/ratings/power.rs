@@ -0,0 +1,145 @@
+use crate::{
+    models::Match,
+    ratings::{clubs, colley::colley, elo::Elo, massey::massey, pi::PiRatings, played_until},
+    Markdown,
+};
+use chrono::NaiveDate;
+
+/// The rating a power ranking is ordered by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RankBy {
+    Massey,
+    Colley,
+    /// The mean of the home and the away Pi-rating.
+    Pi,
+    Elo,
+}
+
+/// The ratings of a club in a power ranking.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PowerRank {
+    pub club: String,
+    pub played: u32,
+    pub massey: f64,
+    pub colley: f64,
+    /// The home and the away Pi-rating.
+    pub pi: [f64; 2],
+    pub elo: f64,
+}
+
+/// The ratings of all clubs, best club first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PowerRanking(pub Vec<PowerRank>);
+
+impl PowerRank {
+    /// Returns the rating used for ordering.
+    pub fn rating(&self, by: RankBy) -> f64 {
+        match by {
+            RankBy::Massey => self.massey,
+            RankBy::Colley => self.colley,
+            RankBy::Pi => (self.pi[0] + self.pi[1]) / 2f64,
+            RankBy::Elo => self.elo,
+        }
+    }
+}
+
+impl PowerRanking {
+    /**
+        Rates all clubs with a result on or before ```until``` (all results if ```None```) by Massey,
+        Colley, Pi-ratings and Elo with default settings and orders them by the rating ```by```.
+
+        **Example**:
+
+        ```text
+        let ranking = PowerRanking::new(&matches, NaiveDate::from_ymd_opt(2019, 10, 1), RankBy::Massey);
+        println!("{}", ranking.to_table());
+        ```
+    **/
+    pub fn new(matches: &[Match], until: Option<NaiveDate>, by: RankBy) -> Self {
+        let played = played_until(matches, until);
+        let massey = massey(&played);
+        let colley = colley(&played);
+        let mut pi = PiRatings::default();
+        pi.process(&played);
+        let mut elo = Elo::default();
+        elo.process(&played);
+        let mut ranking: Vec<PowerRank> = clubs(&played)
+            .into_iter()
+            .map(|club| PowerRank {
+                played: played.iter().filter(|m| m.home == club || m.away == club).count() as u32,
+                massey: massey[&club],
+                colley: colley[&club],
+                pi: pi.rating(&club),
+                elo: elo.history(&club).last().map(|(_, r)| *r).unwrap_or(elo.settings.initial),
+                club,
+            })
+            .collect();
+        ranking.sort_by(|a, b| {
+            b.rating(by)
+                .partial_cmp(&a.rating(by))
+                .unwrap()
+                .then(a.club.cmp(&b.club))
+        });
+        PowerRanking(ranking)
+    }
+}
+
+/// Formats ```PowerRanking``` into a Markdown Table, including header.
+impl Markdown for PowerRanking {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Rank|Club|Played|Massey|Colley|Pi Home|Pi Away|Elo|\n");
+        s.push_str("|-:|:-|-:|-:|-:|-:|-:|-:|\n");
+        for (i, r) in self.0.iter().enumerate() {
+            s.push_str(&format!(
+                "|{}|{}|{}|{:.2}|{:.3}|{:.2}|{:.2}|{:.0}|\n",
+                i + 1,
+                r.club,
+                r.played,
+                r.massey,
+                r.colley,
+                r.pi[0],
+                r.pi[1],
+                r.elo
+            ));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, played};
+
+    fn matches() -> Vec<Match> {
+        vec![
+            played(0, "A", "B", [2, 0]),
+            played(1, "B", "C", [1, 0]),
+            played(2, "A", "C", [1, 1]),
+            fixture(3, "B", "A"),
+        ]
+    }
+
+    #[test]
+    fn clubs_are_ordered_by_the_selected_rating() {
+        let ranking = PowerRanking::new(&matches(), None, RankBy::Massey);
+        let clubs: Vec<&str> = ranking.0.iter().map(|r| r.club.as_str()).collect();
+        // B and C have the same Massey rating and are ordered by name
+        assert_eq!(clubs, vec!["A", "B", "C"]);
+        assert_eq!(ranking.0[0].played, 2);
+        assert!((ranking.0[0].colley - 0.6).abs() < 1e-9);
+        assert!(ranking.0[0].elo > 1500f64);
+        assert_eq!(ranking.to_table().lines().count(), 2 + 3);
+        let by_colley = PowerRanking::new(&matches(), None, RankBy::Colley);
+        assert_eq!(by_colley.0[2].club, "C");
+    }
+
+    #[test]
+    fn only_results_until_the_date_are_rated() {
+        let ranking = PowerRanking::new(&matches(), NaiveDate::from_ymd_opt(2019, 1, 2), RankBy::Elo);
+        assert_eq!(ranking.0.len(), 3);
+        assert_eq!(ranking.0.iter().map(|r| r.played).sum::<u32>(), 4);
+        assert_eq!(ranking.0[0].club, "A");
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{
+    models::{DataEntry, Match},
+    Prediction, Predictions, Predictor,
+};
+use chrono::{DateTime, Duration, FixedOffset, TimeZone};
+
+/// Returns 15:00 UTC of the day ```day``` days after 2019-01-01.
+pub(crate) fn date(day: i64) -> DateTime<FixedOffset> {
+    FixedOffset::east(0).ymd(2019, 1, 1).and_hms(15, 0, 0) + Duration::days(day)
+}
+
+/// Returns an entry without inputs, for models that ignore the features.
+pub(crate) fn entry() -> DataEntry {
+    DataEntry {
+        inputs: vec![],
+        outputs: vec![],
+    }
+}
+
+/// Returns a match of the league "League" played on ```date(day)```.
+pub(crate) fn played(day: i64, home: &str, away: &str, result: [u8; 2]) -> Match {
+    Match::new(date(day), String::from("League"), String::from(home), String::from(away), Some(result))
+}
+
+/// Returns a match of the league "League" on ```date(day)``` that has not been played yet.
+pub(crate) fn fixture(day: i64, home: &str, away: &str) -> Match {
+    Match::new(date(day), String::from("League"), String::from(home), String::from(away), None)
+}
+
+/// Returns the prediction of a match between "Home" and "Away" on ```date(day)``` that ended ```result```.
+pub(crate) fn prediction(day: i64, result: [u8; 2], goals: [f64; 2], probabilities: [f64; 3]) -> Prediction {
+    Prediction {
+        date: date(day),
+        teams: (String::from("Home"), String::from("Away")),
+        expected_scores: (result[0], result[1]),
+        predicted_scores: (goals[0] as u8, goals[1] as u8),
+        predicted_goals: goals,
+        probabilities,
+        uncertainty: None,
+    }
+}
+
+/// Wraps predictions, ie. made by ```prediction```.
+pub(crate) fn predictions(predictions: Vec<Prediction>) -> Predictions {
+    Predictions(predictions)
+}
+
+/// Predicts a home win 1 : 0, panics if it predicts a match that isn't later than its training matches.
+#[derive(Default)]
+pub(crate) struct Chronological(Option<DateTime<FixedOffset>>);
+
+impl Predictor for Chronological {
+    fn fit(&mut self, _training_set: &[DataEntry], matches: &[Match]) {
+        self.0 = matches.iter().map(|m| m.date).max();
+    }
+
+    fn predict_scores(&self, _entry: &DataEntry, m: &Match) -> [f64; 2] {
+        if self.0.map(|latest| latest >= m.date).unwrap_or(false) {
+            panic!("predicted a match played before the latest training match");
+        }
+        [1f64, 0f64]
+    }
+
+    fn predict_outcome(&self, entry: &DataEntry, m: &Match) -> [f64; 3] {
+        self.predict_scores(entry, m);
+        [0.5, 0.3, 0.2]
+    }
+
+    fn name(&self) -> String {
+        String::from("Chronological")
+    }
+}
@@ -0,0 +1,419 @@
+use crate::{
+    models::{DataEntry, Match, Outcome},
+    neural::nn::{HaltCondition, NN},
+    Markdown,
+};
+use rand::{seq::SliceRandom, thread_rng};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+// the network is trained in chunks of epochs, the error target is checked after each chunk
+const EPOCH_CHUNK: u32 = 1000;
+
+/**
+    A reusable network configuration: the hidden layer sizes and the trainer settings.
+    ```guru tune``` writes the best configuration as JSON file,
+    ```guru --experiment <file>``` trains the network with it.
+
+    **Example**:
+
+    ```text
+    { "hidden": [12, 8, 5], "rate": 0.2, "momentum": 0.3, "error": 0.05 }
+    ```
+**/
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Experiment {
+    pub hidden: Vec<u32>,
+    pub rate: f64,
+    pub momentum: f64,
+    pub error: f64,
+}
+
+/// The candidate values for each setting of an ```Experiment```.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchSpace {
+    pub hidden: Vec<Vec<u32>>,
+    pub rate: Vec<f64>,
+    pub momentum: Vec<f64>,
+    pub error: Vec<f64>,
+}
+
+/**
+    How the search space is explored.
+
+    * ```Grid```: Evaluates every combination of the candidate values.
+    * ```Random(n)```: Evaluates n randomly picked combinations.
+    * ```SuccessiveHalving { candidates, eta }```: Evaluates randomly picked combinations with a small
+      epoch budget, keeps the best ```1 / eta``` of them, multiplies the budget by ```eta```
+      and repeats until a single candidate or the maximum budget is reached.
+**/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    Grid,
+    Random(usize),
+    SuccessiveHalving { candidates: usize, eta: usize },
+}
+
+/**
+    The cross-validated score of an ```Experiment```, trained for at most ```budget``` epochs per fold.
+
+    * ```epochs```: The mean number of epochs trained per fold, less than the budget if the
+      training error reached the error target early.
+    * ```mse```: Mean squared error of the network outputs on the validation matches.
+    * ```result```, ```winner```: Share of correct results and correct winners (or draws).
+**/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trial {
+    pub experiment: Experiment,
+    pub budget: u32,
+    pub epochs: u32,
+    pub mse: f64,
+    pub result: f64,
+    pub winner: f64,
+}
+
+/// The evaluated trials of a search, best first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trials(pub Vec<Trial>);
+
+impl Experiment {
+    /// Returns the layer sizes for ```NN::new```.
+    pub fn layer_sizes(&self, inputs: usize, outputs: usize) -> Vec<u32> {
+        let mut sizes = vec![inputs as u32];
+        sizes.extend_from_slice(&self.hidden);
+        sizes.push(outputs as u32);
+        sizes
+    }
+
+    /**
+        Trains the network until the training error is at or below ```error```,
+        or ```max_epochs``` epochs have passed. Returns the number of trained epochs.
+    **/
+    pub fn train(&self, net: &mut NN, examples: &[(Vec<f64>, Vec<f64>)], max_epochs: u32) -> u32 {
+        let mut epochs = 0;
+        while epochs < max_epochs {
+            let chunk = EPOCH_CHUNK.min(max_epochs - epochs);
+            let error = net
+                .train(examples)
+                .halt_condition(HaltCondition::Epochs(chunk))
+                .rate(self.rate)
+                .momentum(self.momentum)
+                .go();
+            epochs += chunk;
+            if error <= self.error {
+                break;
+            }
+        }
+        epochs
+    }
+}
+
+impl SearchSpace {
+    /// Returns every combination of the candidate values.
+    pub fn grid(&self) -> Vec<Experiment> {
+        let mut experiments = vec![];
+        for hidden in &self.hidden {
+            for &rate in &self.rate {
+                for &momentum in &self.momentum {
+                    for &error in &self.error {
+                        experiments.push(Experiment {
+                            hidden: hidden.clone(),
+                            rate,
+                            momentum,
+                            error,
+                        });
+                    }
+                }
+            }
+        }
+        experiments
+    }
+
+    /// Returns n combinations, each value is picked randomly from its candidates.
+    pub fn sample(&self, n: usize) -> Vec<Experiment> {
+        let mut rng = thread_rng();
+        (0..n)
+            .map(|_| Experiment {
+                hidden: self.hidden.choose(&mut rng).unwrap().clone(),
+                rate: *self.rate.choose(&mut rng).unwrap(),
+                momentum: *self.momentum.choose(&mut rng).unwrap(),
+                error: *self.error.choose(&mut rng).unwrap(),
+            })
+            .collect()
+    }
+}
+
+/**
+    Splits ```len``` chronologically sorted entries into ```k``` rolling-origin folds.
+    The entries are cut into k + 1 blocks. Fold i trains on the blocks 0..=i
+    and validates on block i + 1. No fold validates on matches played before its training matches.
+
+    **Example**:
+
+    ```text
+    len = 10, k = 3, blocks [0, 1] [2, 3] [4, 5] [6, 7, 8, 9]
+    fold 0: training 0..2, validation 2..4
+    fold 1: training 0..4, validation 4..6
+    fold 2: training 0..6, validation 6..10
+    ```
+**/
+pub fn time_series_folds(len: usize, k: usize) -> Vec<(Range<usize>, Range<usize>)> {
+    if k < 1 || len < k + 1 {
+        panic!("time series cross validation requires at least k + 1 entries and k >= 1");
+    }
+    let block = len / (k + 1);
+    (0..k)
+        .map(|i| {
+            let end = if i == k - 1 { len } else { block * (i + 2) };
+            (0..block * (i + 1), block * (i + 1)..end)
+        })
+        .collect()
+}
+
+/**
+    Scores an experiment by time series cross validation (see ```time_series_folds```).
+    ```entries``` and ```matches``` must be sorted by date and have the same order,
+    all matches must have a result. ```scale``` denormalizes the outputs into goals.
+**/
+pub fn evaluate(
+    experiment: &Experiment,
+    entries: &[DataEntry],
+    matches: &[Match],
+    k: usize,
+    max_epochs: u32,
+    scale: f64,
+) -> Trial {
+    let examples: Vec<(Vec<f64>, Vec<f64>)> = entries
+        .iter()
+        .map(|e| (e.inputs.clone(), e.outputs.clone()))
+        .collect();
+    let sizes = experiment.layer_sizes(entries[0].inputs.len(), entries[0].outputs.len());
+    let (mut mse, mut results, mut winners, mut validated) = (0f64, 0, 0, 0);
+    let mut epochs = 0;
+    for (training, validation) in time_series_folds(entries.len(), k) {
+        let mut net = NN::new(&sizes);
+        epochs += experiment.train(&mut net, &examples[training], max_epochs);
+        for i in validation {
+            let outputs = net.run(&examples[i].0);
+            mse += outputs
+                .iter()
+                .zip(examples[i].1.iter())
+                .map(|(o, t)| (t - o).powi(2))
+                .sum::<f64>()
+                / outputs.len() as f64;
+            let predicted = [(outputs[0] * scale) as u8, (outputs[1] * scale) as u8];
+            let actual = matches[i].result.unwrap();
+            if predicted == actual {
+                results += 1;
+            }
+            if Outcome::from(predicted) == Outcome::from(actual) {
+                winners += 1;
+            }
+            validated += 1;
+        }
+    }
+    let n = f64::from(validated);
+    Trial {
+        experiment: experiment.clone(),
+        budget: max_epochs,
+        epochs: (f64::from(epochs) / k as f64).round() as u32,
+        mse: mse / n,
+        result: f64::from(results) / n,
+        winner: f64::from(winners) / n,
+    }
+}
+
+/**
+    Searches the space with the given strategy and returns all evaluated trials,
+    ranked by the validation MSE. For successive halving, trials of later rounds
+    (larger budgets) are ranked first.
+**/
+pub fn search(
+    space: &SearchSpace,
+    strategy: Strategy,
+    entries: &[DataEntry],
+    matches: &[Match],
+    k: usize,
+    max_epochs: u32,
+    scale: f64,
+) -> Trials {
+    let mut trials: Vec<Trial> = match strategy {
+        Strategy::Grid => evaluate_all(&space.grid(), entries, matches, k, max_epochs, scale),
+        Strategy::Random(n) => evaluate_all(&space.sample(n), entries, matches, k, max_epochs, scale),
+        Strategy::SuccessiveHalving { candidates, eta } => {
+            if eta < 2 {
+                panic!("successive halving requires eta >= 2");
+            }
+            let mut experiments = space.sample(candidates);
+            let mut budget = first_budget(candidates, eta, max_epochs);
+            let mut all = vec![];
+            loop {
+                println!("Successive Halving: {} candidates, {} epochs", experiments.len(), budget);
+                let mut round = evaluate_all(&experiments, entries, matches, k, budget, scale);
+                rank(&mut round);
+                all.extend(round.iter().cloned());
+                if round.len() <= 1 || budget >= max_epochs {
+                    break;
+                }
+                let keep = (round.len() / eta).max(1);
+                experiments = round.into_iter().take(keep).map(|t| t.experiment).collect();
+                budget = (budget * eta as u32).min(max_epochs);
+            }
+            all
+        }
+    };
+    match strategy {
+        Strategy::SuccessiveHalving { .. } => trials.sort_by(|a, b| {
+            b.budget
+                .cmp(&a.budget)
+                .then(a.mse.partial_cmp(&b.mse).unwrap())
+        }),
+        _ => rank(&mut trials),
+    }
+    Trials(trials)
+}
+
+impl Trials {
+    /// Returns the best trial.
+    pub fn best(&self) -> Option<&Trial> {
+        self.0.first()
+    }
+}
+
+/// Formats ```Trials``` into a Markdown Table, including header.
+impl Markdown for Trials {
+    fn to_table(&self) -> String {
+        let mut s = String::new();
+        s.push_str("|Rank|Hidden layers|Rate|Momentum|Error|Budget|Epochs|Validation MSE|Result|Winner|\n");
+        s.push_str("|-:|:-:|-:|-:|-:|-:|-:|-:|-:|-:|\n");
+        for (i, trial) in self.0.iter().enumerate() {
+            let hidden: Vec<String> = trial.experiment.hidden.iter().map(|h| h.to_string()).collect();
+            s.push_str(&format!(
+                "|{}|{}|{}|{}|{}|{}|{}|{:.5}|{:.0}%|{:.0}%|\n",
+                i + 1,
+                hidden.join("-"),
+                trial.experiment.rate,
+                trial.experiment.momentum,
+                trial.experiment.error,
+                trial.budget,
+                trial.epochs,
+                trial.mse,
+                trial.result * 100f64,
+                trial.winner * 100f64
+            ));
+        }
+        s
+    }
+}
+
+// the budget of the first round of successive halving, so that the last round gets max_epochs,
+// at least one chunk but never more than max_epochs
+fn first_budget(candidates: usize, eta: usize, max_epochs: u32) -> u32 {
+    let mut rounds = 1;
+    let mut remaining = candidates;
+    while remaining > eta {
+        remaining /= eta;
+        rounds += 1;
+    }
+    (max_epochs / (eta as u32).pow(rounds - 1)).max(EPOCH_CHUNK).min(max_epochs)
+}
+
+fn evaluate_all(
+    experiments: &[Experiment],
+    entries: &[DataEntry],
+    matches: &[Match],
+    k: usize,
+    max_epochs: u32,
+    scale: f64,
+) -> Vec<Trial> {
+    experiments
+        .iter()
+        .enumerate()
+        .map(|(i, experiment)| {
+            println!("Evaluating Candidate {} of {}...", i + 1, experiments.len());
+            evaluate(experiment, entries, matches, k, max_epochs, scale)
+        })
+        .collect()
+}
+
+fn rank(trials: &mut [Trial]) {
+    trials.sort_by(|a, b| a.mse.partial_cmp(&b.mse).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn space() -> SearchSpace {
+        SearchSpace {
+            hidden: vec![vec![4], vec![8, 4]],
+            rate: vec![0.1, 0.3],
+            momentum: vec![0.2],
+            error: vec![0.05, 0.01],
+        }
+    }
+
+    fn trial(mse: f64) -> Trial {
+        Trial {
+            experiment: space().grid()[0].clone(),
+            budget: 1000,
+            epochs: 1000,
+            mse,
+            result: 0.5,
+            winner: 0.5,
+        }
+    }
+
+    #[test]
+    fn folds_roll_the_origin_forward() {
+        assert_eq!(time_series_folds(10, 3), vec![(0..2, 2..4), (0..4, 4..6), (0..6, 6..10)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least k + 1 entries")]
+    fn folds_need_more_entries_than_folds() {
+        time_series_folds(3, 3);
+    }
+
+    #[test]
+    fn halving_budget_stays_within_the_maximum() {
+        // three rounds: 27, 9 and 3 candidates
+        assert_eq!(first_budget(27, 3, 9000), 1000);
+        assert_eq!(first_budget(27, 3, 3000), 1000);
+        assert_eq!(first_budget(27, 3, 500), 500);
+    }
+
+    #[test]
+    fn grid_has_every_combination() {
+        let grid = space().grid();
+        assert_eq!(grid.len(), 2 * 2 * 2);
+        assert!(grid.contains(&Experiment {
+            hidden: vec![8, 4],
+            rate: 0.3,
+            momentum: 0.2,
+            error: 0.01
+        }));
+    }
+
+    #[test]
+    fn samples_pick_candidate_values() {
+        let space = space();
+        for experiment in space.sample(20) {
+            assert!(space.grid().contains(&experiment));
+        }
+    }
+
+    #[test]
+    fn layer_sizes_wrap_the_hidden_layers() {
+        assert_eq!(space().grid()[4].layer_sizes(6, 2), vec![6, 8, 4, 2]);
+    }
+
+    #[test]
+    fn trials_are_ranked_by_validation_mse() {
+        let mut trials = vec![trial(0.3), trial(0.1), trial(0.2)];
+        rank(&mut trials);
+        let trials = Trials(trials);
+        assert_eq!(trials.best().unwrap().mse, 0.1);
+        assert_eq!(trials.to_table().lines().count(), 2 + 3);
+    }
+}
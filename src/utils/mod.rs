@@ -1,6 +1,9 @@
-use crate::{models::{Match, Sets}, neural::nn::NN};
+use crate::{evaluation::{calibration::Calibrator, ledger::Ledger}, models::{Match, Sets}, neural::{ensemble::NetworkEnsemble, nn::NN}, tune::Experiment};
 use rand::prelude::*;
-use std::{fs::File, io::prelude::*};
+use std::{
+    fs::{File, OpenOptions},
+    io::prelude::*,
+};
 
 /// A collection of utility functions.
 
@@ -53,6 +56,77 @@ pub fn save_network(nn: &NN) -> std::io::Result<()> {
     f.sync_all()?;
     Ok(())
 }
+/// Loads a trained network ensemble from file
+pub fn load_ensemble() -> std::io::Result<NetworkEnsemble> {
+    println!("loading ensemble..");
+    let mut file = File::open("guru.ensemble")?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(NetworkEnsemble::from_json(&contents))
+}
+
+/// Saves a trained network ensemble to file
+pub fn save_ensemble(ensemble: &NetworkEnsemble) -> std::io::Result<()> {
+    println!("saving ensemble..");
+    let mut f = File::create("guru.ensemble")?;
+    f.write_all(ensemble.to_json().as_bytes())?;
+    f.sync_all()?;
+    Ok(())
+}
+/// Loads the calibrator of a trained model from file
+pub fn load_calibrator() -> std::io::Result<Calibrator> {
+    println!("loading calibrator..");
+    let mut file = File::open("guru.calibration")?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Saves the calibrator of a trained model to file
+pub fn save_calibrator(calibrator: &Calibrator) -> std::io::Result<()> {
+    println!("saving calibrator..");
+    let mut f = File::create("guru.calibration")?;
+    f.write_all(serde_json::to_string_pretty(calibrator)?.as_bytes())?;
+    f.sync_all()?;
+    Ok(())
+}
+
+/// Loads the prediction ledger from file
+pub fn load_ledger(file_name: &str) -> std::io::Result<Ledger> {
+    println!("loading ledger from: {:?}", &file_name);
+    let mut file = File::open(file_name)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ledger::from_lines(&contents).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Appends the entries of a ledger to the ledger file, the file is created if it doesn't exist
+pub fn append_ledger(file_name: &str, ledger: &Ledger) -> std::io::Result<()> {
+    println!("appending {} predictions to: {:?}", ledger.0.len(), &file_name);
+    let mut f = OpenOptions::new().create(true).append(true).open(file_name)?;
+    f.write_all(ledger.to_lines().as_bytes())?;
+    f.sync_all()?;
+    Ok(())
+}
+
+/// Loads an experiment (network configuration) from a JSON file
+pub fn load_experiment(file_name: &str) -> std::io::Result<Experiment> {
+    println!("loading experiment from: {:?}", &file_name);
+    let mut file = File::open(file_name)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Saves an experiment (network configuration) to a JSON file
+pub fn save_experiment(experiment: &Experiment, file_name: &str) -> std::io::Result<()> {
+    println!("saving experiment to: {:?}", &file_name);
+    let mut f = File::create(file_name)?;
+    f.write_all(serde_json::to_string_pretty(experiment)?.as_bytes())?;
+    f.sync_all()?;
+    Ok(())
+}
+
 /// Simple normalization function
 pub fn normalize(v: f64, min: f64, max: f64) -> f64 {
     if (max - min) == 0.0 {